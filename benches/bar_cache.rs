@@ -0,0 +1,52 @@
+//compares parsing a large csv with loading it again through the bar cache
+//run with: cargo bench --bench bar_cache
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use menudo::prelude::*;
+use std::io::Write;
+
+const BARS: usize = 200_000;
+
+//writes BARS one-minute bars to a temporary csv file
+fn large_csv() -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    writeln!(file, "timestamp,open,high,low,close,volume,symbol").unwrap();
+    let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z").unwrap();
+    for i in 0..BARS {
+        let timestamp = start + chrono::Duration::minutes(i as i64);
+        let close = 4000.0 + 50.0 * ((i as f64) / 500.0).sin();
+        writeln!(
+            file,
+            "{},{},{},{},{},1000,ES",
+            timestamp.to_rfc3339(),
+            close - 0.5,
+            close + 1.0,
+            close - 1.0,
+            close
+        )
+        .unwrap();
+    }
+    file.flush().unwrap();
+    file
+}
+
+fn bar_cache(c: &mut Criterion) {
+    let file = large_csv();
+    let options = LoaderOptions::default();
+
+    let mut group = c.benchmark_group("load_200k_bars");
+    group.sample_size(10);
+    group.bench_function("parse", |b| {
+        b.iter(|| load_csv_with_options(file.path(), &options).unwrap())
+    });
+
+    let mut cache = BarCache::new(1);
+    cache.load(file.path(), &options).unwrap();
+    group.bench_function("cached", |b| {
+        b.iter(|| cache.load(file.path(), &options).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bar_cache);
+criterion_main!(benches);
@@ -0,0 +1,118 @@
+//registers a user-defined strategy by name and runs it through BacktestConfiguration
+//run with: cargo run --example custom_strategy
+
+use menudo::prelude::*;
+use std::io::Write;
+
+//channel breakout: goes long above the highest close of the lookback window,
+//flattens below the lowest close
+struct ChannelBreakout {
+    symbol: String,
+    lookback: usize,
+    qty: u32,
+    //breakouts seen during the run, read back after it through downcast_ref
+    breakouts: usize,
+}
+
+impl Strategy for ChannelBreakout {
+    fn on_start(&mut self, _context: &mut StrategyContext) {}
+
+    fn on_bar(&mut self, context: &mut StrategyContext, bar: &Bar) {
+        if context.bar_count() <= self.lookback {
+            return;
+        }
+
+        //channel from the bars before the current one
+        let closes = context.get_close_prices(self.lookback + 1);
+        let window = &closes[..closes.len() - 1];
+        let upper = window.iter().cloned().fold(f64::MIN, f64::max);
+        let lower = window.iter().cloned().fold(f64::MAX, f64::min);
+
+        let net_qty = context.current_position().map(|p| p.net_qty).unwrap_or(0);
+
+        if bar.close > upper {
+            self.breakouts += 1;
+        }
+
+        if bar.close > upper && net_qty == 0 {
+            let _ = context.market_order(self.symbol.clone(), self.qty, OrderSide::Buy);
+        } else if bar.close < lower && net_qty > 0 {
+            let _ = context.market_order(self.symbol.clone(), net_qty as u32, OrderSide::Sell);
+        }
+    }
+
+    fn on_end(&mut self, context: &mut StrategyContext) {
+        if let Some(position) = context.current_position() {
+            if position.is_long() {
+                let qty = position.net_qty.unsigned_abs();
+                let _ = context.market_order(self.symbol.clone(), qty, OrderSide::Sell);
+            }
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Channel Breakout"
+    }
+
+    fn required_lookback(&self) -> usize {
+        self.lookback + 1
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    //plug the strategy in by name
+    register_strategy("channel_breakout", |params, symbol, qty| {
+        let lookback = params.get("lookback").copied().unwrap_or(20.0) as usize;
+        Ok(Box::new(ChannelBreakout {
+            symbol: symbol.to_string(),
+            lookback,
+            qty,
+            breakouts: 0,
+        }))
+    });
+
+    //write a small synthetic dataset
+    let data_path = std::env::temp_dir().join("menudo_custom_strategy_example.csv");
+    let mut file = std::fs::File::create(&data_path)?;
+    writeln!(file, "timestamp,open,high,low,close,volume,symbol")?;
+    let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z")?;
+    for i in 0..120 {
+        let timestamp = start + chrono::Duration::days(i);
+        let close = 4000.0 + 50.0 * ((i as f64) / 10.0).sin() + i as f64;
+        writeln!(
+            file,
+            "{},{},{},{},{},1000,ES",
+            timestamp.to_rfc3339(),
+            close - 1.0,
+            close + 2.0,
+            close - 2.0,
+            close
+        )?;
+    }
+
+    //select the registered strategy through the config
+    let mut params = ParamMap::new();
+    params.insert("lookback".to_string(), 10.0);
+
+    let config = BacktestConfiguration {
+        data_path,
+        strategy_type: StrategyType::Custom,
+        strategy_params: StrategyParams::Custom(CustomParams {
+            name: "channel_breakout".to_string(),
+            qty: 1,
+            params,
+        }),
+        ..BacktestConfiguration::default()
+    };
+
+    let (mut engine, mut strategy) = config.build_engine()?;
+    let result = engine.run(&mut strategy);
+
+    println!("Strategy: {}", strategy.name());
+    if let Some(breakout) = strategy.downcast_ref::<ChannelBreakout>() {
+        println!("Breakouts: {}", breakout.breakouts);
+    }
+    result.summary.pretty_print_table();
+
+    Ok(())
+}
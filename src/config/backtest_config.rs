@@ -1,4 +1,21 @@
-use crate::instrument::FuturesContract;
+use crate::data::{filter_by_date_range, filter_by_symbol, load_csv_cached, Bar, LoaderOptions};
+use crate::engine::{
+    BacktestConfig, BacktestEngine, BracketFillPolicy, ExecutionMode, LimitFillMode, MarkPrice,
+    MarketTif, PlacementCheck, SignalFillMode, StopTrigger,
+};
+use crate::instrument::{ContractKind, FuturesContract, PnlMethod};
+use crate::metrics::{CurrencyFormat, DrawdownMode};
+use crate::portfolio::{AccountMode, CostTiming, FeeSettlement};
+use crate::strategy::{
+    keltner::{KeltnerMode, KeltnerStrategy},
+    registry::{create_registered, ParamMap, StrategyRegistry},
+    rsi_reversion::RsiReversionStrategy,
+    sizing::{PositionSizing, VolTarget},
+    sma_crossover::SmaCrossoverStrategy,
+    Strategy,
+};
+use anyhow::Context;
+use chrono::NaiveDate;
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
@@ -7,6 +24,9 @@ use std::path::PathBuf;
 pub enum StrategyType {
     SmaCrossover,
     RsiReversion,
+    Keltner,
+    //user-defined strategy built through a strategy registry
+    Custom,
 }
 
 impl StrategyType {
@@ -15,6 +35,7 @@ impl StrategyType {
         match s.to_lowercase().as_str() {
             "sma" | "sma_crossover" => Some(StrategyType::SmaCrossover),
             "rsi" | "rsi_reversion" => Some(StrategyType::RsiReversion),
+            "keltner" => Some(StrategyType::Keltner),
             _ => None,
         }
     }
@@ -25,7 +46,18 @@ impl StrategyType {
 pub struct SmaParams {
     pub fast_window: usize,
     pub slow_window: usize,
+    #[serde(default)]
+    pub trend_window: Option<usize>,
     pub qty: u32,
+    //fraction of equity to commit per entry (percent-of-equity sizing, overrides qty)
+    #[serde(default)]
+    pub size_fraction: Option<f64>,
+    //protective stop-loss distance from the entry price, in ticks
+    #[serde(default)]
+    pub stop_loss_ticks: Option<u32>,
+    //protective take-profit distance from the entry price, in ticks
+    #[serde(default)]
+    pub take_profit_ticks: Option<u32>,
 }
 
 impl Default for SmaParams {
@@ -33,18 +65,63 @@ impl Default for SmaParams {
         SmaParams {
             fast_window: 20,
             slow_window: 50,
+            trend_window: None,
             qty: 1,
+            size_fraction: None,
+            stop_loss_ticks: None,
+            take_profit_ticks: None,
         }
     }
 }
 
+impl SmaParams {
+    //checks that the windows and quantity are usable
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.fast_window == 0 {
+            anyhow::bail!("fast window must be greater than 0");
+        }
+        if self.slow_window <= self.fast_window {
+            anyhow::bail!(
+                "slow window ({}) must be greater than fast window ({})",
+                self.slow_window,
+                self.fast_window
+            );
+        }
+        if self.trend_window == Some(0) {
+            anyhow::bail!("trend filter window must be greater than 0");
+        }
+        if self.qty == 0 {
+            anyhow::bail!("quantity must be greater than 0");
+        }
+        validate_size_fraction(self.size_fraction)?;
+        validate_exit_ticks(self.stop_loss_ticks, self.take_profit_ticks)?;
+        Ok(())
+    }
+}
+
 //rsi reversion strategy parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RsiParams {
     pub lookback: usize,
     pub oversold: f64,
     pub overbought: f64,
+    //consecutive bars rsi must stay beyond a threshold before entering
+    #[serde(default = "default_confirmation_bars")]
+    pub confirmation_bars: usize,
     pub qty: u32,
+    //fraction of equity to commit per entry (percent-of-equity sizing, overrides qty)
+    #[serde(default)]
+    pub size_fraction: Option<f64>,
+    //protective stop-loss distance from the entry price, in ticks
+    #[serde(default)]
+    pub stop_loss_ticks: Option<u32>,
+    //protective take-profit distance from the entry price, in ticks
+    #[serde(default)]
+    pub take_profit_ticks: Option<u32>,
+}
+
+fn default_confirmation_bars() -> usize {
+    1
 }
 
 impl Default for RsiParams {
@@ -53,16 +130,218 @@ impl Default for RsiParams {
             lookback: 14,
             oversold: 30.0,
             overbought: 70.0,
+            confirmation_bars: default_confirmation_bars(),
             qty: 1,
+            size_fraction: None,
+            stop_loss_ticks: None,
+            take_profit_ticks: None,
         }
     }
 }
 
+impl RsiParams {
+    //checks that the lookback, thresholds and quantity are usable
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.lookback == 0 {
+            anyhow::bail!("rsi lookback must be greater than 0");
+        }
+        if !(0.0..=100.0).contains(&self.oversold) || !(0.0..=100.0).contains(&self.overbought) {
+            anyhow::bail!("rsi thresholds must be within [0, 100]");
+        }
+        if self.oversold >= self.overbought {
+            anyhow::bail!(
+                "rsi lower threshold ({}) must be below upper threshold ({})",
+                self.oversold,
+                self.overbought
+            );
+        }
+        if self.confirmation_bars == 0 {
+            anyhow::bail!("rsi confirmation bars must be greater than 0");
+        }
+        if self.qty == 0 {
+            anyhow::bail!("quantity must be greater than 0");
+        }
+        validate_size_fraction(self.size_fraction)?;
+        validate_exit_ticks(self.stop_loss_ticks, self.take_profit_ticks)?;
+        Ok(())
+    }
+}
+
+//keltner channel strategy parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeltnerParams {
+    pub ema_period: usize,
+    pub atr_period: usize,
+    pub multiplier: f64,
+    #[serde(default)]
+    pub mode: KeltnerMode,
+    pub qty: u32,
+    //fraction of equity to commit per entry (percent-of-equity sizing, overrides qty)
+    #[serde(default)]
+    pub size_fraction: Option<f64>,
+}
+
+impl Default for KeltnerParams {
+    fn default() -> Self {
+        KeltnerParams {
+            ema_period: 20,
+            atr_period: 10,
+            multiplier: 2.0,
+            mode: KeltnerMode::default(),
+            qty: 1,
+            size_fraction: None,
+        }
+    }
+}
+
+impl KeltnerParams {
+    //checks that the periods, multiplier and quantity are usable
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.ema_period == 0 || self.atr_period == 0 {
+            anyhow::bail!("keltner ema and atr periods must be greater than 0");
+        }
+        if !(self.multiplier > 0.0 && self.multiplier.is_finite()) {
+            anyhow::bail!("keltner multiplier must be a positive number");
+        }
+        if self.qty == 0 {
+            anyhow::bail!("quantity must be greater than 0");
+        }
+        validate_size_fraction(self.size_fraction)?;
+        Ok(())
+    }
+}
+
+//checks that an equity fraction for position sizing is in (0, 1]
+fn validate_size_fraction(size_fraction: Option<f64>) -> anyhow::Result<()> {
+    if let Some(fraction) = size_fraction {
+        if !(fraction > 0.0 && fraction <= 1.0) {
+            anyhow::bail!("size fraction ({}) must be within (0, 1]", fraction);
+        }
+    }
+    Ok(())
+}
+
+//checks that protective exit distances, when set, are at least one tick
+fn validate_exit_ticks(
+    stop_loss_ticks: Option<u32>,
+    take_profit_ticks: Option<u32>,
+) -> anyhow::Result<()> {
+    if stop_loss_ticks == Some(0) {
+        anyhow::bail!("stop loss ticks must be greater than 0");
+    }
+    if take_profit_ticks == Some(0) {
+        anyhow::bail!("take profit ticks must be greater than 0");
+    }
+    Ok(())
+}
+
+//parameters for a user-defined strategy looked up by name in a registry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CustomParams {
+    pub name: String,
+    pub qty: u32,
+    #[serde(default)]
+    pub params: ParamMap,
+}
+
+impl CustomParams {
+    //checks that the name and quantity are usable
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.name.trim().is_empty() {
+            anyhow::bail!("custom strategy name must not be empty");
+        }
+        if self.qty == 0 {
+            anyhow::bail!("quantity must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
 //strategy-specific parameters
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum StrategyParams {
     Sma(SmaParams),
     Rsi(RsiParams),
+    Keltner(KeltnerParams),
+    Custom(CustomParams),
+}
+
+impl StrategyParams {
+    //validates the wrapped parameters
+    pub fn validate(&self) -> anyhow::Result<()> {
+        match self {
+            StrategyParams::Sma(params) => params.validate(),
+            StrategyParams::Rsi(params) => params.validate(),
+            StrategyParams::Keltner(params) => params.validate(),
+            StrategyParams::Custom(params) => params.validate(),
+        }
+    }
+
+    //returns the strategy type these parameters belong to
+    pub fn strategy_type(&self) -> StrategyType {
+        match self {
+            StrategyParams::Sma(_) => StrategyType::SmaCrossover,
+            StrategyParams::Rsi(_) => StrategyType::RsiReversion,
+            StrategyParams::Keltner(_) => StrategyType::Keltner,
+            StrategyParams::Custom(_) => StrategyType::Custom,
+        }
+    }
+
+    //constructs the strategy these parameters describe
+    //custom strategies are resolved through the process-wide registry (register_strategy)
+    pub fn build_strategy(&self, symbol: &str) -> anyhow::Result<Box<dyn Strategy>> {
+        match self {
+            StrategyParams::Custom(p) => create_registered(&p.name, &p.params, symbol, p.qty),
+            _ => self.build_strategy_with(symbol, &StrategyRegistry::new()),
+        }
+    }
+
+    //constructs the strategy, resolving custom strategies through the registry
+    pub fn build_strategy_with(
+        &self,
+        symbol: &str,
+        registry: &StrategyRegistry,
+    ) -> anyhow::Result<Box<dyn Strategy>> {
+        let strategy: Box<dyn Strategy> = match self {
+            StrategyParams::Sma(p) => Box::new(
+                SmaCrossoverStrategy::new(
+                    symbol.to_string(),
+                    p.fast_window,
+                    p.slow_window,
+                    p.trend_window,
+                    p.qty,
+                )
+                .with_sizing(PositionSizing::from_params(p.qty, p.size_fraction))
+                .with_protective_exits(p.stop_loss_ticks, p.take_profit_ticks),
+            ),
+            StrategyParams::Rsi(p) => Box::new(
+                RsiReversionStrategy::new(
+                    symbol.to_string(),
+                    p.lookback,
+                    p.oversold,
+                    p.overbought,
+                    p.qty,
+                )
+                .with_confirmation_bars(p.confirmation_bars)
+                .with_sizing(PositionSizing::from_params(p.qty, p.size_fraction))
+                .with_protective_exits(p.stop_loss_ticks, p.take_profit_ticks),
+            ),
+            StrategyParams::Keltner(p) => Box::new(
+                KeltnerStrategy::new(
+                    symbol.to_string(),
+                    p.ema_period,
+                    p.atr_period,
+                    p.multiplier,
+                    p.mode,
+                    p.qty,
+                )
+                .with_sizing(PositionSizing::from_params(p.qty, p.size_fraction)),
+            ),
+            StrategyParams::Custom(p) => registry.create(&p.name, &p.params, symbol, p.qty)?,
+        };
+
+        Ok(strategy)
+    }
 }
 
 //complete backtest configuration
@@ -71,6 +350,14 @@ pub struct BacktestConfiguration {
     //data
     pub data_path: PathBuf,
     pub symbol: String,
+    //inclusive date bounds on the bars to run over (optional)
+    #[serde(default)]
+    pub start_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub end_date: Option<NaiveDate>,
+    //csv header mapping for vendor files
+    #[serde(default)]
+    pub loader: LoaderOptions,
 
     //contract specification
     pub contract: ContractConfig,
@@ -78,7 +365,70 @@ pub struct BacktestConfiguration {
     //account settings
     pub initial_balance: f64,
     pub commission_per_contract: f64,
+    #[serde(default)]
+    pub min_commission_per_order: f64,
+    #[serde(default)]
+    pub commission_per_order: f64,
     pub slippage_per_contract: f64,
+    #[serde(default)]
+    pub cost_timing: CostTiming,
+    #[serde(default)]
+    pub fee_settlement: FeeSettlement,
+    #[serde(default)]
+    pub account_mode: AccountMode,
+    #[serde(default = "default_max_lookback")]
+    pub max_lookback: usize,
+    #[serde(default)]
+    pub warmup_bars: usize,
+    //calendar warmup in days from the first bar (the later of this and warmup_bars applies)
+    #[serde(default)]
+    pub warmup_days: Option<u32>,
+    #[serde(default)]
+    pub execution_mode: ExecutionMode,
+    #[serde(default)]
+    pub limit_fill_mode: LimitFillMode,
+    #[serde(default)]
+    pub stop_trigger: StopTrigger,
+    #[serde(default)]
+    pub max_participation: Option<f64>,
+    #[serde(default)]
+    pub market_tif: MarketTif,
+    #[serde(default)]
+    pub bracket_fill_policy: BracketFillPolicy,
+    #[serde(default)]
+    pub signal_delay_bars: u32,
+    #[serde(default)]
+    pub market_slippage_ticks: u32,
+    #[serde(default)]
+    pub placement_check: PlacementCheck,
+    #[serde(default)]
+    pub max_pyramid_entries: Option<usize>,
+    #[serde(default)]
+    pub vol_target: Option<VolTarget>,
+    #[serde(default)]
+    pub min_bars_between_trades: Option<usize>,
+    #[serde(default)]
+    pub max_drawdown_stop: Option<f64>,
+    #[serde(default = "default_equity_floor")]
+    pub equity_floor: Option<f64>,
+    #[serde(default)]
+    pub roll_dates: Vec<NaiveDate>,
+    #[serde(default)]
+    pub mark_price: MarkPrice,
+    #[serde(default)]
+    pub signal_fill_mode: SignalFillMode,
+    #[serde(default)]
+    pub drawdown_mode: DrawdownMode,
+    #[serde(default)]
+    pub intrabar_drawdown: bool,
+    #[serde(default)]
+    pub currency: CurrencyFormat,
+    //return periods per year for annualized ratios (none = infer from the bar spacing)
+    #[serde(default)]
+    pub periods_per_year: Option<f64>,
+    //seed for every stochastic component, so a saved config reproduces its run exactly
+    #[serde(default)]
+    pub seed: u64,
 
     //strategy
     pub strategy_type: StrategyType,
@@ -89,6 +439,14 @@ pub struct BacktestConfiguration {
     pub output_trades_csv: Option<PathBuf>,
 }
 
+fn default_max_lookback() -> usize {
+    BacktestConfig::default().max_lookback
+}
+
+fn default_equity_floor() -> Option<f64> {
+    BacktestConfig::default().equity_floor
+}
+
 //contract configuration (simpler than full futurescontract)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContractConfig {
@@ -99,12 +457,21 @@ pub struct ContractConfig {
     pub point_value: Option<f64>,
     pub initial_margin: Option<f64>,
     pub maintenance_margin: Option<f64>,
+    //notional multiplier when it differs from point_value (optional)
+    #[serde(default)]
+    pub multiplier: Option<f64>,
+    //linear or inverse pnl
+    #[serde(default)]
+    pub kind: ContractKind,
+    //how linear pnl is computed from a price move
+    #[serde(default)]
+    pub pnl_method: PnlMethod,
 }
 
 impl ContractConfig {
     //converts to a FuturesContract
     pub fn to_futures_contract(&self) -> FuturesContract {
-        FuturesContract::from_params(
+        let contract = FuturesContract::from_params(
             self.symbol.clone(),
             self.contract_month.clone(),
             self.tick_size,
@@ -112,7 +479,16 @@ impl ContractConfig {
             self.point_value,
             self.initial_margin,
             self.maintenance_margin,
-        )
+        );
+
+        let contract = contract
+            .with_kind(self.kind)
+            .with_pnl_method(self.pnl_method);
+
+        match self.multiplier {
+            Some(multiplier) => contract.with_multiplier(multiplier),
+            None => contract,
+        }
     }
 }
 
@@ -121,6 +497,9 @@ impl Default for BacktestConfiguration {
         BacktestConfiguration {
             data_path: PathBuf::from("data.csv"),
             symbol: "ES".to_string(),
+            start_date: None,
+            end_date: None,
+            loader: LoaderOptions::default(),
             contract: ContractConfig {
                 symbol: "ES".to_string(),
                 contract_month: "2025-03".to_string(),
@@ -129,10 +508,43 @@ impl Default for BacktestConfiguration {
                 point_value: Some(50.0),
                 initial_margin: Some(13000.0),
                 maintenance_margin: Some(12000.0),
+                multiplier: None,
+                kind: ContractKind::default(),
+                pnl_method: PnlMethod::default(),
             },
             initial_balance: 100000.0,
             commission_per_contract: 2.5,
+            min_commission_per_order: 0.0,
+            commission_per_order: 0.0,
             slippage_per_contract: 1.0,
+            cost_timing: CostTiming::default(),
+            fee_settlement: FeeSettlement::default(),
+            account_mode: AccountMode::default(),
+            max_lookback: default_max_lookback(),
+            warmup_bars: 0,
+            warmup_days: None,
+            execution_mode: ExecutionMode::default(),
+            limit_fill_mode: LimitFillMode::default(),
+            stop_trigger: StopTrigger::default(),
+            max_participation: None,
+            market_tif: MarketTif::default(),
+            bracket_fill_policy: BracketFillPolicy::default(),
+            signal_delay_bars: 0,
+            market_slippage_ticks: 0,
+            placement_check: PlacementCheck::default(),
+            max_pyramid_entries: None,
+            vol_target: None,
+            min_bars_between_trades: None,
+            max_drawdown_stop: None,
+            equity_floor: default_equity_floor(),
+            roll_dates: Vec::new(),
+            mark_price: MarkPrice::default(),
+            signal_fill_mode: SignalFillMode::default(),
+            drawdown_mode: DrawdownMode::default(),
+            intrabar_drawdown: false,
+            currency: CurrencyFormat::default(),
+            periods_per_year: None,
+            seed: 0,
             strategy_type: StrategyType::SmaCrossover,
             strategy_params: StrategyParams::Sma(SmaParams::default()),
             output_equity_csv: None,
@@ -142,6 +554,131 @@ impl Default for BacktestConfiguration {
 }
 
 impl BacktestConfiguration {
+    //converts to the engine's backtest config
+    pub fn to_engine_config(&self) -> BacktestConfig {
+        BacktestConfig {
+            initial_balance: self.initial_balance,
+            commission_per_contract: self.commission_per_contract,
+            min_commission_per_order: self.min_commission_per_order,
+            commission_per_order: self.commission_per_order,
+            slippage_per_contract: self.slippage_per_contract,
+            cost_timing: self.cost_timing,
+            fee_settlement: self.fee_settlement,
+            account_mode: self.account_mode,
+            max_lookback: self.max_lookback,
+            warmup_bars: self.warmup_bars,
+            warmup_period: self
+                .warmup_days
+                .map(|days| chrono::Duration::days(days as i64)),
+            execution_mode: self.execution_mode,
+            limit_fill_mode: self.limit_fill_mode,
+            stop_trigger: self.stop_trigger,
+            max_participation: self.max_participation,
+            market_tif: self.market_tif,
+            bracket_fill_policy: self.bracket_fill_policy,
+            signal_delay_bars: self.signal_delay_bars,
+            market_slippage_ticks: self.market_slippage_ticks,
+            placement_check: self.placement_check,
+            max_pyramid_entries: self.max_pyramid_entries,
+            vol_target: self.vol_target,
+            min_bars_between_trades: self.min_bars_between_trades,
+            max_drawdown_stop: self.max_drawdown_stop,
+            equity_floor: self.equity_floor,
+            roll_dates: self.roll_dates.clone(),
+            mark_price: self.mark_price,
+            signal_fill_mode: self.signal_fill_mode,
+            drawdown_mode: self.drawdown_mode,
+            intrabar_drawdown: self.intrabar_drawdown,
+            currency: self.currency.clone(),
+            periods_per_year: self.periods_per_year,
+            seed: self.seed,
+            //set per engine by load_engine and screen, not by the file
+            id_namespace: 0,
+            bars_pre_sorted: false,
+        }
+    }
+
+    //loads the data and builds a ready-to-run engine and strategy
+    //custom strategies are resolved through the process-wide registry
+    pub fn build_engine(&self) -> anyhow::Result<(BacktestEngine, Box<dyn Strategy>)> {
+        let engine = self.load_engine()?;
+        let strategy = self.strategy_params.build_strategy(&self.symbol)?;
+        Ok((engine, strategy))
+    }
+
+    //like build_engine, resolving custom strategies through the given registry
+    pub fn build_engine_with(
+        &self,
+        registry: &StrategyRegistry,
+    ) -> anyhow::Result<(BacktestEngine, Box<dyn Strategy>)> {
+        let engine = self.load_engine()?;
+        let strategy = self
+            .strategy_params
+            .build_strategy_with(&self.symbol, registry)?;
+        Ok((engine, strategy))
+    }
+
+    //validates the parameters, loads the data and builds the engine
+    fn load_engine(&self) -> anyhow::Result<BacktestEngine> {
+        self.validate_strategy()?;
+
+        //cached, so repeated runs over the same file only parse it once
+        let all_bars = load_csv_cached(&self.data_path, &self.loader)
+            .context(format!("Failed to load data from {:?}", self.data_path))?;
+
+        //load_csv already sorted the bars, so the engine can skip its own check
+        let mut engine_config = self.to_engine_config();
+        engine_config.bars_pre_sorted = true;
+
+        self.engine_for(
+            &all_bars,
+            &self.symbol,
+            self.contract.to_futures_contract(),
+            engine_config,
+        )
+    }
+
+    //checks that the strategy type matches its parameters and that they're valid
+    pub(crate) fn validate_strategy(&self) -> anyhow::Result<()> {
+        if self.strategy_params.strategy_type() != self.strategy_type {
+            anyhow::bail!(
+                "strategy_type {:?} does not match strategy_params for {:?}",
+                self.strategy_type,
+                self.strategy_params.strategy_type()
+            );
+        }
+        self.strategy_params.validate()
+    }
+
+    //builds an engine over one symbol's bars within the date range
+    //engine_config carries what differs per engine (id namespace, whether bars are pre-sorted)
+    pub(crate) fn engine_for(
+        &self,
+        all_bars: &[Bar],
+        symbol: &str,
+        contract: FuturesContract,
+        engine_config: BacktestConfig,
+    ) -> anyhow::Result<BacktestEngine> {
+        let bars = filter_by_symbol(all_bars, symbol);
+
+        if bars.is_empty() {
+            anyhow::bail!("No data found for symbol {}", symbol);
+        }
+
+        let bars = filter_by_date_range(&bars, self.start_date, self.end_date);
+
+        if bars.is_empty() {
+            anyhow::bail!(
+                "No data found for symbol {} between {:?} and {:?}",
+                symbol,
+                self.start_date,
+                self.end_date
+            );
+        }
+
+        Ok(BacktestEngine::new(engine_config, bars, contract))
+    }
+
     //load configuration from a JSON file
     pub fn from_json_file(path: &PathBuf) -> anyhow::Result<Self> {
         let contents = std::fs::read_to_string(path)?;
@@ -153,6 +690,136 @@ impl BacktestConfiguration {
     pub fn to_json_file(&self, path: &PathBuf) -> anyhow::Result<()> {
         let json = serde_json::to_string_pretty(self)?;
         std::fs::write(path, json)?;
-        Ok(()        )
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_lookback_survives_saved_config() {
+        let config = BacktestConfiguration {
+            max_lookback: 1234,
+            ..BacktestConfiguration::default()
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded: BacktestConfiguration = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded.max_lookback, 1234);
+        assert_eq!(loaded.to_engine_config().max_lookback, 1234);
+    }
+
+    #[test]
+    fn contract_config_applies_the_multiplier_override() {
+        let mut contract = BacktestConfiguration::default().contract;
+        let by_point_value = contract.to_futures_contract();
+        contract.multiplier = Some(10.0);
+        let overridden = contract.to_futures_contract();
+
+        assert_eq!(by_point_value.notional_value(4000.0, 1), 200000.0);
+        assert_eq!(overridden.notional_value(4000.0, 1), 40000.0);
+        assert_eq!(overridden.point_value, by_point_value.point_value);
+    }
+
+    #[test]
+    fn engine_config_round_trips_through_json() {
+        let config = BacktestConfig {
+            max_lookback: 42,
+            warmup_period: Some(chrono::Duration::days(10)),
+            max_pyramid_entries: Some(3),
+            max_drawdown_stop: Some(0.25),
+            equity_floor: None,
+            intrabar_drawdown: true,
+            seed: 7,
+            roll_dates: vec![chrono::NaiveDate::from_ymd_opt(2024, 3, 14).unwrap()],
+            ..BacktestConfig::new(25000.0, 1.25, 0.5)
+        };
+        let json = serde_json::to_string(&config).unwrap();
+        let loaded: BacktestConfig = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(loaded, config);
+    }
+
+    #[test]
+    fn engine_config_new_sets_balance_and_costs() {
+        let config = BacktestConfig::new(25000.0, 1.25, 0.5);
+
+        assert_eq!(config.initial_balance, 25000.0);
+        assert_eq!(config.commission_per_contract, 1.25);
+        assert_eq!(config.slippage_per_contract, 0.5);
+        assert_eq!(
+            config,
+            BacktestConfig {
+                initial_balance: 25000.0,
+                commission_per_contract: 1.25,
+                slippage_per_contract: 0.5,
+                ..BacktestConfig::default()
+            }
+        );
+    }
+
+    //writes an oscillating daily series for each symbol to a temporary csv file
+    fn csv(symbols: &[&str], days: usize) -> tempfile::NamedTempFile {
+        use std::io::Write;
+
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, "timestamp,open,high,low,close,volume,symbol").unwrap();
+        let start = chrono::DateTime::parse_from_rfc3339("2024-01-02T00:00:00Z").unwrap();
+        for i in 0..days {
+            let close = 4000.0 + 20.0 * (i as f64 / 5.0).sin();
+            for symbol in symbols {
+                writeln!(
+                    file,
+                    "{},{},{},{},{},1000,{}",
+                    (start + chrono::Duration::days(i as i64)).to_rfc3339(),
+                    close,
+                    close + 1.0,
+                    close - 1.0,
+                    close,
+                    symbol
+                )
+                .unwrap();
+            }
+        }
+        file.flush().unwrap();
+        file
+    }
+
+    fn sma_config(data_path: PathBuf) -> BacktestConfiguration {
+        BacktestConfiguration {
+            data_path,
+            strategy_params: StrategyParams::Sma(SmaParams {
+                fast_window: 3,
+                slow_window: 8,
+                ..SmaParams::default()
+            }),
+            ..BacktestConfiguration::default()
+        }
+    }
+
+    #[test]
+    fn build_engine_runs_the_configured_symbol_and_strategy() {
+        let file = csv(&["ES", "NQ"], 60);
+        let (mut engine, mut strategy) = sma_config(file.path().to_path_buf())
+            .build_engine()
+            .unwrap();
+        let result = engine.run(&mut strategy);
+
+        assert_eq!(strategy.name(), "SMA Crossover");
+        assert_eq!(result.equity_curve.len(), 60);
+        assert!(!result.trades.fills().is_empty());
+        assert!(result.trades.fills().iter().all(|fill| fill.symbol == "ES"));
+    }
+
+    #[test]
+    fn build_engine_rejects_params_of_another_strategy_type() {
+        let file = csv(&["ES"], 10);
+        let config = BacktestConfiguration {
+            strategy_type: StrategyType::RsiReversion,
+            ..sma_config(file.path().to_path_buf())
+        };
+        assert!(config.build_engine().is_err());
     }
 }
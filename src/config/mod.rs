@@ -1,5 +1,6 @@
 pub mod backtest_config;
 
 pub use backtest_config::{
-    BacktestConfiguration, ContractConfig, RsiParams, SmaParams, StrategyParams, StrategyType,
+    BacktestConfiguration, ContractConfig, CustomParams, KeltnerParams, RsiParams, SmaParams,
+    StrategyParams, StrategyType,
 };
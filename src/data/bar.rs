@@ -4,6 +4,8 @@ use thiserror::Error;
 
 #[derive(Error, Debug)]
 pub enum BarError {
+    #[error("Invalid OHLC values: {field} is not a finite number ({value})")]
+    NonFinitePrice { field: &'static str, value: f64 },
     #[error("Invalid OHLC values: high ({high}) < low ({low})")]
     InvalidHighLow { high: f64, low: f64 },
     #[error("Invalid OHLC values: close ({close}) outside high-low range [{low}, {high}]")]
@@ -25,6 +27,14 @@ pub struct Bar {
     pub volume: f64,
     pub open_interest: Option<f64>,
     pub symbol: String,
+    //best bid/ask at the bar, when the data carries quotes
+    #[serde(default)]
+    pub bid: Option<f64>,
+    #[serde(default)]
+    pub ask: Option<f64>,
+    //exchange daily settlement price, when the data carries it
+    #[serde(default)]
+    pub settlement: Option<f64>,
 }
 
 impl Bar {
@@ -40,6 +50,18 @@ impl Bar {
         open_interest: Option<f64>,
         symbol: String,
     ) -> Result<Self, BarError> {
+        //validate finite prices (NaN would slip through every comparison below)
+        for (field, value) in [
+            ("open", open),
+            ("high", high),
+            ("low", low),
+            ("close", close),
+        ] {
+            if !value.is_finite() {
+                return Err(BarError::NonFinitePrice { field, value });
+            }
+        }
+
         //validate high >= low
         if high < low {
             return Err(BarError::InvalidHighLow { high, low });
@@ -69,6 +91,9 @@ impl Bar {
             volume,
             open_interest,
             symbol,
+            bid: None,
+            ask: None,
+            settlement: None,
         })
     }
 
@@ -93,9 +118,33 @@ impl Bar {
             volume,
             open_interest,
             symbol,
+            bid: None,
+            ask: None,
+            settlement: None,
         }
     }
 
+    //attaches bid/ask quotes to the bar
+    pub fn with_quotes(mut self, bid: Option<f64>, ask: Option<f64>) -> Self {
+        self.bid = bid;
+        self.ask = ask;
+        self
+    }
+
+    //attaches a settlement price to the bar
+    pub fn with_settlement(mut self, settlement: Option<f64>) -> Self {
+        self.settlement = settlement;
+        self
+    }
+
+    //returns true if any of open, high, low or close is missing: not a finite number, or the
+    //zero some vendors write for bars with no trades
+    pub fn has_missing_price(&self) -> bool {
+        [self.open, self.high, self.low, self.close]
+            .iter()
+            .any(|&price| !price.is_finite() || price == 0.0)
+    }
+
     //returns the typical price (HLC/3)
     pub fn typical_price(&self) -> f64 {
         (self.high + self.low + self.close) / 3.0
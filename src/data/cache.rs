@@ -0,0 +1,265 @@
+use crate::data::bar::Bar;
+use crate::data::loader::{load_csv_with_options, LoaderOptions};
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::SystemTime;
+
+//files kept by the process-wide cache unless set_bar_cache_capacity says otherwise
+pub const DEFAULT_BAR_CACHE_CAPACITY: usize = 4;
+
+//identifies one parse of a file: rewriting the file or changing the loader options misses
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    path: PathBuf,
+    modified: SystemTime,
+    options: String,
+}
+
+//least-recently-used cache of parsed csv files
+//entries are shared as Arc<[Bar]>, so a hit costs a refcount rather than a copy
+#[derive(Debug)]
+pub struct BarCache {
+    capacity: usize,
+    //oldest use first; a hit moves the entry to the back
+    entries: IndexMap<CacheKey, Arc<[Bar]>>,
+}
+
+impl BarCache {
+    //creates a cache holding up to capacity files (0 disables caching)
+    pub fn new(capacity: usize) -> Self {
+        BarCache {
+            capacity,
+            entries: IndexMap::new(),
+        }
+    }
+
+    //loads bars from a csv file, parsing it only if this path, modification time and
+    //set of loader options isn't cached yet
+    pub fn load<P: AsRef<Path>>(&mut self, path: P, options: &LoaderOptions) -> Result<Arc<[Bar]>> {
+        let path = path.as_ref();
+        let key = cache_key(path, options)?;
+
+        if let Some(bars) = self.get(&key) {
+            return Ok(bars);
+        }
+
+        let bars: Arc<[Bar]> = load_csv_with_options(path, options)?.into();
+        self.insert(key, bars.clone());
+        Ok(bars)
+    }
+
+    //returns a cached parse, marking it most recently used
+    fn get(&mut self, key: &CacheKey) -> Option<Arc<[Bar]>> {
+        let bars = self.entries.shift_remove(key)?;
+        self.entries.insert(key.clone(), bars.clone());
+        Some(bars)
+    }
+
+    //caches a parse, evicting the least recently used files past the capacity
+    fn insert(&mut self, key: CacheKey, bars: Arc<[Bar]>) {
+        if self.capacity == 0 {
+            return;
+        }
+        //a rewritten file leaves its old parse behind, drop it along with the oldest
+        self.entries.retain(|cached, _| cached.path != key.path);
+        while self.entries.len() >= self.capacity {
+            self.entries.shift_remove_index(0);
+        }
+        self.entries.insert(key, bars);
+    }
+
+    //changes the capacity, evicting the least recently used files past it
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.entries.len() > capacity {
+            self.entries.shift_remove_index(0);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    //number of files currently cached
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl Default for BarCache {
+    fn default() -> Self {
+        BarCache::new(DEFAULT_BAR_CACHE_CAPACITY)
+    }
+}
+
+fn cache_key(path: &Path, options: &LoaderOptions) -> Result<CacheKey> {
+    let path = path
+        .canonicalize()
+        .context(format!("Failed to open CSV file: {:?}", path))?;
+    let modified = std::fs::metadata(&path)
+        .and_then(|meta| meta.modified())
+        .context(format!("Failed to read modification time of {:?}", path))?;
+
+    //sorted so the same mapping always gives the same key
+    let column_map: BTreeMap<_, _> = options.column_map.iter().collect();
+    #[allow(unused_mut)]
+    let mut options_key = format!("{:?};{:?}", column_map, options.missing_prices);
+    #[cfg(feature = "csv-autodetect")]
+    options_key.push_str(if options.auto_detect { ";auto" } else { "" });
+
+    Ok(CacheKey {
+        path,
+        modified,
+        options: options_key,
+    })
+}
+
+//process-wide cache used by load_csv_cached and the config flow
+fn global_cache() -> &'static RwLock<BarCache> {
+    static CACHE: OnceLock<RwLock<BarCache>> = OnceLock::new();
+    CACHE.get_or_init(|| RwLock::new(BarCache::default()))
+}
+
+//loads bars from a csv file through the process-wide cache, so repeated runs over the same
+//file (optimization loops, batch configs) parse it once
+//the file is parsed without holding the lock, so loads of other files aren't blocked; two
+//threads missing on the same file at once may both parse it
+pub fn load_csv_cached<P: AsRef<Path>>(path: P, options: &LoaderOptions) -> Result<Arc<[Bar]>> {
+    let path = path.as_ref();
+    let key = cache_key(path, options)?;
+
+    let cached = global_cache()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&key);
+    if let Some(bars) = cached {
+        return Ok(bars);
+    }
+
+    let bars: Arc<[Bar]> = load_csv_with_options(path, options)?.into();
+    global_cache()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(key, bars.clone());
+    Ok(bars)
+}
+
+//sets how many files the process-wide cache keeps (0 disables it and frees what it holds)
+pub fn set_bar_cache_capacity(capacity: usize) {
+    global_cache()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .set_capacity(capacity);
+}
+
+//drops every file held by the process-wide cache
+pub fn clear_bar_cache() {
+    global_cache()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn csv(closes: &[f64]) -> tempfile::NamedTempFile {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        write_bars(file.as_file_mut(), closes);
+        file
+    }
+
+    fn write_bars(file: &mut std::fs::File, closes: &[f64]) {
+        writeln!(file, "timestamp,open,high,low,close,volume,symbol").unwrap();
+        for (i, close) in closes.iter().enumerate() {
+            writeln!(
+                file,
+                "2024-01-{:02}T00:00:00Z,{},{},{},{},1000,ES",
+                i + 2,
+                close,
+                close + 1.0,
+                close - 1.0,
+                close
+            )
+            .unwrap();
+        }
+        file.flush().unwrap();
+    }
+
+    #[test]
+    fn second_load_shares_the_first_parse() {
+        let file = csv(&[4000.0, 4001.0]);
+        let mut cache = BarCache::new(2);
+
+        let first = cache.load(file.path(), &LoaderOptions::default()).unwrap();
+        let second = cache.load(file.path(), &LoaderOptions::default()).unwrap();
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn rewritten_file_is_parsed_again() {
+        let mut file = csv(&[4000.0]);
+        let mut cache = BarCache::new(2);
+        let first = cache.load(file.path(), &LoaderOptions::default()).unwrap();
+
+        let handle = file.as_file_mut();
+        handle.set_len(0).unwrap();
+        std::io::Seek::rewind(handle).unwrap();
+        write_bars(handle, &[4000.0, 4001.0]);
+        handle
+            .set_modified(SystemTime::now() + Duration::from_secs(10))
+            .unwrap();
+
+        let second = cache.load(file.path(), &LoaderOptions::default()).unwrap();
+        assert_eq!((first.len(), second.len()), (1, 2));
+        //the stale parse was dropped rather than kept alongside
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn least_recently_used_file_is_evicted() {
+        let (a, b, c) = (csv(&[4000.0]), csv(&[4001.0]), csv(&[4002.0]));
+        let options = LoaderOptions::default();
+        let mut cache = BarCache::new(2);
+
+        let first_a = cache.load(a.path(), &options).unwrap();
+        cache.load(b.path(), &options).unwrap();
+        //touch a, so b is the oldest when c comes in
+        cache.load(a.path(), &options).unwrap();
+        cache.load(c.path(), &options).unwrap();
+
+        assert_eq!(cache.len(), 2);
+        assert!(Arc::ptr_eq(
+            &first_a,
+            &cache.load(a.path(), &options).unwrap()
+        ));
+    }
+
+    #[test]
+    fn zero_capacity_caches_nothing() {
+        let file = csv(&[4000.0]);
+        let mut cache = BarCache::new(0);
+
+        let first = cache.load(file.path(), &LoaderOptions::default()).unwrap();
+        let second = cache.load(file.path(), &LoaderOptions::default()).unwrap();
+
+        assert!(!Arc::ptr_eq(&first, &second));
+        assert!(cache.is_empty());
+    }
+}
@@ -1,30 +1,152 @@
 use crate::data::bar::Bar;
-use anyhow::{Context, Result};
-use chrono::{DateTime, Utc};
-use csv::ReaderBuilder;
-use serde::Deserialize;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use csv::{ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
 use std::path::Path;
 
+//what to do with bars whose open, high, low or close is missing (blank, NaN or zero)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MissingPricePolicy {
+    //load the bars as they are: blanks load as NaN and zeros stay zero
+    //only for data that's cleaned after loading
+    Keep,
+    //fail the load, naming the first bad bar
+    #[default]
+    Error,
+    //drop the bar
+    Skip,
+    //replace the missing prices with the previous close of the same symbol
+    //a symbol's leading bars have nothing to carry and are dropped
+    ForwardFill,
+}
+
+impl MissingPricePolicy {
+    //parse missing price policy from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "keep" | "none" => Some(MissingPricePolicy::Keep),
+            "error" | "fail" => Some(MissingPricePolicy::Error),
+            "skip" | "drop" => Some(MissingPricePolicy::Skip),
+            "forward_fill" | "forward-fill" | "ffill" => Some(MissingPricePolicy::ForwardFill),
+            _ => None,
+        }
+    }
+}
+
+//options controlling how csv headers map onto bar fields
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LoaderOptions {
+    //maps a header in the file to the field it holds (eg "datetime" -> "timestamp")
+    #[serde(default)]
+    pub column_map: HashMap<String, String>,
+
+    //how bars with missing prices are handled
+    #[serde(default)]
+    pub missing_prices: MissingPricePolicy,
+
+    //rename common vendor aliases (datetime, vol, oi, ...) for headers not in column_map
+    #[cfg(feature = "csv-autodetect")]
+    #[serde(default)]
+    pub auto_detect: bool,
+}
+
+impl LoaderOptions {
+    //maps a single file header to the field name the loader expects
+    fn map_header(&self, header: &str) -> String {
+        let header = header.trim();
+        if let Some(field) = self.column_map.get(header) {
+            return field.clone();
+        }
+
+        #[cfg(feature = "csv-autodetect")]
+        if self.auto_detect {
+            if let Some(field) = header_alias(header) {
+                return field.to_string();
+            }
+        }
+
+        header.to_string()
+    }
+
+    //maps every header in the file's header row
+    fn map_headers(&self, headers: &StringRecord) -> StringRecord {
+        headers.iter().map(|h| self.map_header(h)).collect()
+    }
+}
+
+//returns the expected field for a common vendor header alias (case-insensitive)
+#[cfg(feature = "csv-autodetect")]
+fn header_alias(header: &str) -> Option<&'static str> {
+    let field = match header.to_lowercase().as_str() {
+        "datetime" | "date_time" | "date" | "time" | "ts" => "timestamp",
+        "o" | "open_price" => "open",
+        "h" | "high_price" => "high",
+        "l" | "low_price" => "low",
+        "c" | "last" | "close_price" => "close",
+        "vol" | "v" | "volume_traded" => "volume",
+        "oi" | "openinterest" | "open_int" => "open_interest",
+        "ticker" | "instrument" | "contract" | "sym" => "symbol",
+        "settle" | "settlement_price" => "settlement",
+        "bid_price" => "bid",
+        "ask_price" | "offer" => "ask",
+        _ => return None,
+    };
+    Some(field)
+}
+
+//prices are optional so blank cells reach the missing price policy instead of failing the parse
 #[derive(Debug, Deserialize)]
 struct CsvRecord {
     timestamp: String,
-    open: f64,
-    high: f64,
-    low: f64,
-    close: f64,
+    open: Option<f64>,
+    high: Option<f64>,
+    low: Option<f64>,
+    close: Option<f64>,
     volume: f64,
     #[serde(default)]
     open_interest: Option<f64>,
     symbol: String,
+    #[serde(default)]
+    bid: Option<f64>,
+    #[serde(default)]
+    ask: Option<f64>,
+    #[serde(default)]
+    settlement: Option<f64>,
 }
 
 //loads bars from a csv file
 pub fn load_csv<P: AsRef<Path>>(path: P) -> Result<Vec<Bar>> {
+    load_csv_with_options(path, &LoaderOptions::default())
+}
+
+//loads bars from a csv file, mapping its headers with the given options
+pub fn load_csv_with_options<P: AsRef<Path>>(path: P, options: &LoaderOptions) -> Result<Vec<Bar>> {
     let path = path.as_ref();
-    let mut reader = ReaderBuilder::new()
-        .has_headers(true)
-        .from_path(path)
-        .context(format!("Failed to open CSV file: {:?}", path))?;
+    let file = std::fs::File::open(path).context(format!("Failed to open CSV file: {:?}", path))?;
+
+    load_csv_reader_with_options(file, options)
+}
+
+//loads bars from any csv source (file, stdin, in-memory buffer)
+pub fn load_csv_reader<R: Read>(source: R) -> Result<Vec<Bar>> {
+    load_csv_reader_with_options(source, &LoaderOptions::default())
+}
+
+//loads bars from any csv source, mapping its headers with the given options
+pub fn load_csv_reader_with_options<R: Read>(
+    source: R,
+    options: &LoaderOptions,
+) -> Result<Vec<Bar>> {
+    let mut reader = ReaderBuilder::new().has_headers(true).from_reader(source);
+
+    let headers = reader
+        .headers()
+        .context("Failed to read CSV header row")?
+        .clone();
+    reader.set_headers(options.map_headers(&headers));
 
     let mut bars = Vec::new();
 
@@ -44,22 +166,67 @@ pub fn load_csv<P: AsRef<Path>>(path: P) -> Result<Vec<Bar>> {
         //create bar
         let bar = Bar::new_unchecked(
             timestamp,
-            record.open,
-            record.high,
-            record.low,
-            record.close,
+            record.open.unwrap_or(f64::NAN),
+            record.high.unwrap_or(f64::NAN),
+            record.low.unwrap_or(f64::NAN),
+            record.close.unwrap_or(f64::NAN),
             record.volume,
             record.open_interest,
             record.symbol,
-        );
+        )
+        .with_quotes(record.bid, record.ask)
+        .with_settlement(record.settlement);
 
         bars.push(bar);
     }
 
     //sort by timestamp to ensure chronological order
-    bars.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    bars.sort_by_key(|a| a.timestamp);
+
+    clean_missing_prices(bars, options.missing_prices)
+}
+
+//applies a missing price policy to chronologically ordered bars
+//a forward-filled bar's high and low are widened to cover the carried prices
+pub fn clean_missing_prices(bars: Vec<Bar>, policy: MissingPricePolicy) -> Result<Vec<Bar>> {
+    let mut last_close: HashMap<String, f64> = HashMap::new();
+    let mut cleaned = Vec::with_capacity(bars.len());
+
+    for mut bar in bars {
+        if bar.has_missing_price() {
+            match policy {
+                MissingPricePolicy::Keep => {}
+                MissingPricePolicy::Error => bail!(
+                    "Missing price in {} bar at {} (open {}, high {}, low {}, close {})",
+                    bar.symbol,
+                    bar.timestamp,
+                    bar.open,
+                    bar.high,
+                    bar.low,
+                    bar.close
+                ),
+                MissingPricePolicy::Skip => continue,
+                MissingPricePolicy::ForwardFill => {
+                    let previous = match last_close.get(&bar.symbol) {
+                        Some(&close) => close,
+                        None => continue,
+                    };
+                    for price in [&mut bar.open, &mut bar.high, &mut bar.low, &mut bar.close] {
+                        if !price.is_finite() || *price == 0.0 {
+                            *price = previous;
+                        }
+                    }
+                    bar.high = bar.high.max(bar.open).max(bar.close);
+                    bar.low = bar.low.min(bar.open).min(bar.close);
+                }
+            }
+        }
+
+        last_close.insert(bar.symbol.clone(), bar.close);
+        cleaned.push(bar);
+    }
 
-    Ok(bars)
+    Ok(cleaned)
 }
 
 //filters bars by symbol
@@ -69,3 +236,278 @@ pub fn filter_by_symbol(bars: &[Bar], symbol: &str) -> Vec<Bar> {
         .cloned()
         .collect()
 }
+
+//filters bars to those matching any of the given symbols
+//the result is in chronological order even if the input is grouped by symbol;
+//bars sharing a timestamp keep their input order
+pub fn filter_by_symbols(bars: &[Bar], symbols: &[&str]) -> Vec<Bar> {
+    let mut filtered: Vec<Bar> = bars
+        .iter()
+        .filter(|bar| symbols.contains(&bar.symbol.as_str()))
+        .cloned()
+        .collect();
+    filtered.sort_by_key(|bar| bar.timestamp);
+    filtered
+}
+
+//filters bars to a date range, by the bar's utc date
+//both bounds are inclusive whole days, so end includes every bar on that date
+//a missing bound leaves that side open; start after end yields no bars
+pub fn filter_by_date_range(
+    bars: &[Bar],
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> Vec<Bar> {
+    bars.iter()
+        .filter(|bar| {
+            let date = bar.timestamp.date_naive();
+            start.is_none_or(|start| date >= start) && end.is_none_or(|end| date <= end)
+        })
+        .cloned()
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Datelike;
+
+    //the second bar has a blank close and the third a vendor zero
+    const MISSING_CLOSES: &str = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T00:00:00Z,4000,4010,3990,4005,1000,ES
+2024-01-03T00:00:00Z,4005,4015,3995,,1000,ES
+2024-01-04T00:00:00Z,4010,4020,4000,0,1000,ES
+2024-01-05T00:00:00Z,4015,4025,4005,4020,1000,ES
+";
+
+    fn load(policy: MissingPricePolicy) -> Result<Vec<Bar>> {
+        let options = LoaderOptions {
+            missing_prices: policy,
+            ..LoaderOptions::default()
+        };
+        load_csv_reader_with_options(MISSING_CLOSES.as_bytes(), &options)
+    }
+
+    #[test]
+    fn blank_price_fails_the_load_by_default() {
+        let csv = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T00:00:00Z,4000,4010,3990,4005,1000,ES
+2024-01-03T00:00:00Z,4005,,3995,4010,1000,ES
+";
+        let err = load_csv_reader(csv.as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("Missing price"));
+        assert!(err.to_string().contains("2024-01-03"));
+    }
+
+    #[test]
+    fn non_finite_price_fails_the_load_by_default() {
+        let csv = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T00:00:00Z,4000,4010,3990,NaN,1000,ES
+";
+        assert!(load_csv_reader(csv.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn keep_loads_every_bar_when_asked() {
+        let bars = load(MissingPricePolicy::Keep).unwrap();
+        assert_eq!(bars.len(), 4);
+        assert!(bars[1].close.is_nan());
+        assert_eq!(bars[2].close, 0.0);
+    }
+
+    #[test]
+    fn error_names_the_first_missing_bar() {
+        let err = load(MissingPricePolicy::Error).unwrap_err();
+        assert!(err.to_string().contains("2024-01-03"));
+    }
+
+    #[test]
+    fn skip_drops_missing_bars() {
+        let closes: Vec<f64> = load(MissingPricePolicy::Skip)
+            .unwrap()
+            .iter()
+            .map(|bar| bar.close)
+            .collect();
+        assert_eq!(closes, vec![4005.0, 4020.0]);
+    }
+
+    #[test]
+    fn forward_fill_carries_the_previous_close() {
+        let bars = load(MissingPricePolicy::ForwardFill).unwrap();
+        let closes: Vec<f64> = bars.iter().map(|bar| bar.close).collect();
+        assert_eq!(closes, vec![4005.0, 4005.0, 4005.0, 4020.0]);
+        //the carried close sits inside the bar's range
+        assert!(bars
+            .iter()
+            .all(|bar| bar.low <= bar.close && bar.close <= bar.high));
+    }
+
+    #[test]
+    fn forward_fill_drops_a_leading_missing_bar() {
+        let csv = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T00:00:00Z,4000,4010,3990,,1000,ES
+2024-01-03T00:00:00Z,4005,4015,3995,4010,1000,ES
+";
+        let options = LoaderOptions {
+            missing_prices: MissingPricePolicy::ForwardFill,
+            ..LoaderOptions::default()
+        };
+        let bars = load_csv_reader_with_options(csv.as_bytes(), &options).unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].close, 4010.0);
+    }
+
+    #[test]
+    fn reader_and_path_load_the_same_bars() {
+        use std::io::Write;
+
+        let csv = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T00:00:00Z,4000,4010,3990,4005,1000,ES
+2024-01-03T00:00:00Z,4005,4015,3995,4010,1000,NQ
+";
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(csv.as_bytes()).unwrap();
+        file.flush().unwrap();
+
+        let from_path = load_csv(file.path()).unwrap();
+        let from_reader = load_csv_reader(csv.as_bytes()).unwrap();
+
+        assert_eq!(from_path.len(), from_reader.len());
+        for (a, b) in from_path.iter().zip(&from_reader) {
+            assert_eq!(a.timestamp, b.timestamp);
+            assert_eq!(a.open, b.open);
+            assert_eq!(a.symbol, b.symbol);
+        }
+    }
+
+    #[test]
+    fn reader_input_is_sorted_by_timestamp() {
+        let csv = "timestamp,open,high,low,close,volume,symbol
+2024-01-03T00:00:00Z,4005,4015,3995,4010,1000,ES
+2024-01-02T00:00:00Z,4000,4010,3990,4005,1000,ES
+";
+        let bars = load_csv_reader(csv.as_bytes()).unwrap();
+        assert!(bars[0].timestamp < bars[1].timestamp);
+    }
+
+    const VENDOR_HEADERS: &str = "datetime,o,h,l,last,vol,ticker
+2024-01-02T00:00:00Z,4000,4010,3990,4005,1000,ES
+";
+
+    #[test]
+    fn column_map_renames_vendor_headers() {
+        let column_map = [
+            ("datetime", "timestamp"),
+            ("o", "open"),
+            ("h", "high"),
+            ("l", "low"),
+            ("last", "close"),
+            ("vol", "volume"),
+            ("ticker", "symbol"),
+        ]
+        .into_iter()
+        .map(|(header, field)| (header.to_string(), field.to_string()))
+        .collect();
+        let options = LoaderOptions {
+            column_map,
+            ..LoaderOptions::default()
+        };
+
+        let bars = load_csv_reader_with_options(VENDOR_HEADERS.as_bytes(), &options).unwrap();
+        assert_eq!(bars.len(), 1);
+        assert_eq!((bars[0].close, bars[0].volume), (4005.0, 1000.0));
+        assert_eq!(bars[0].symbol, "ES");
+    }
+
+    #[test]
+    fn unmapped_vendor_headers_fail_to_load() {
+        assert!(load_csv_reader(VENDOR_HEADERS.as_bytes()).is_err());
+    }
+
+    #[cfg(feature = "csv-autodetect")]
+    #[test]
+    fn auto_detect_recognises_common_aliases() {
+        let options = LoaderOptions {
+            auto_detect: true,
+            ..LoaderOptions::default()
+        };
+
+        let bars = load_csv_reader_with_options(VENDOR_HEADERS.as_bytes(), &options).unwrap();
+        assert_eq!(bars[0].open, 4000.0);
+        assert_eq!(bars[0].symbol, "ES");
+    }
+
+    //three symbols grouped by symbol rather than by time
+    const GROUPED: &str = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T00:00:00Z,4000,4010,3990,4005,1000,ES
+2024-01-03T00:00:00Z,4005,4015,3995,4010,1000,ES
+2024-01-02T00:00:00Z,17000,17010,16990,17005,1000,NQ
+2024-01-03T00:00:00Z,17005,17015,16995,17010,1000,NQ
+2024-01-02T00:00:00Z,70,71,69,70.5,1000,CL
+";
+
+    #[test]
+    fn filter_by_symbols_keeps_any_listed_symbol_in_time_order() {
+        let bars = load_csv_reader(GROUPED.as_bytes()).unwrap();
+        let filtered = filter_by_symbols(&bars, &["NQ", "ES"]);
+
+        let order: Vec<(u32, &str)> = filtered
+            .iter()
+            .map(|bar| (bar.timestamp.day(), bar.symbol.as_str()))
+            .collect();
+        assert_eq!(order, vec![(2, "ES"), (2, "NQ"), (3, "ES"), (3, "NQ")]);
+        assert!(filter_by_symbols(&bars, &[]).is_empty());
+        assert_eq!(filter_by_symbols(&bars, &["CL", "GC"]).len(), 1);
+    }
+
+    //two bars on the 2nd, then one each on the 3rd and 4th
+    fn dated_bars() -> Vec<Bar> {
+        let csv = "timestamp,open,high,low,close,volume,symbol
+2024-01-02T09:00:00Z,4000,4010,3990,4005,1000,ES
+2024-01-02T15:00:00Z,4005,4015,3995,4010,1000,ES
+2024-01-03T12:00:00Z,4010,4020,4000,4015,1000,ES
+2024-01-04T00:00:00Z,4015,4025,4005,4020,1000,ES
+";
+        load_csv_reader(csv.as_bytes()).unwrap()
+    }
+
+    fn days(bars: &[Bar]) -> Vec<u32> {
+        bars.iter().map(|bar| bar.timestamp.day()).collect()
+    }
+
+    fn date(day: u32) -> Option<NaiveDate> {
+        NaiveDate::from_ymd_opt(2024, 1, day)
+    }
+
+    #[test]
+    fn date_range_bounds_are_inclusive_whole_days() {
+        let bars = dated_bars();
+        assert_eq!(
+            days(&filter_by_date_range(&bars, date(2), date(3))),
+            vec![2, 2, 3]
+        );
+        assert_eq!(
+            days(&filter_by_date_range(&bars, date(3), date(3))),
+            vec![3]
+        );
+    }
+
+    #[test]
+    fn date_range_with_a_missing_bound_is_open_on_that_side() {
+        let bars = dated_bars();
+        assert_eq!(
+            days(&filter_by_date_range(&bars, date(3), None)),
+            vec![3, 4]
+        );
+        assert_eq!(
+            days(&filter_by_date_range(&bars, None, date(2))),
+            vec![2, 2]
+        );
+        assert_eq!(filter_by_date_range(&bars, None, None).len(), 4);
+    }
+
+    #[test]
+    fn date_range_with_start_after_end_is_empty() {
+        assert!(filter_by_date_range(&dated_bars(), date(4), date(2)).is_empty());
+    }
+}
@@ -1,5 +1,13 @@
 pub mod bar;
+pub mod cache;
 pub mod loader;
 
 pub use bar::Bar;
-pub use loader::{filter_by_symbol, load_csv};
+pub use cache::{
+    clear_bar_cache, load_csv_cached, set_bar_cache_capacity, BarCache, DEFAULT_BAR_CACHE_CAPACITY,
+};
+pub use loader::{
+    clean_missing_prices, filter_by_date_range, filter_by_symbol, filter_by_symbols, load_csv,
+    load_csv_reader, load_csv_reader_with_options, load_csv_with_options, LoaderOptions,
+    MissingPricePolicy,
+};
@@ -1,26 +1,193 @@
 use crate::data::Bar;
-use crate::engine::execution::ExecutionEngine;
+use crate::engine::execution::{
+    BracketFillPolicy, ExecutionEngine, ExecutionMode, ExitReason, Fill, LimitFillMode, MarketTif,
+    Order, OrderSide, OrderStats, PlacementCheck, StopTrigger,
+};
 use crate::instrument::FuturesContract;
-use crate::metrics::{calculate_equity_curve, EquityPoint, SummaryMetrics};
-use crate::portfolio::Account;
-use crate::strategy::{Strategy, StrategyContext};
-use std::collections::HashMap;
+use crate::metrics::{
+    calculate_equity_curve_intrabar, slippage_stats, CurrencyFormat, DrawdownMode, EquityPoint,
+    SummaryMetrics, TradingCalendar,
+};
+use crate::portfolio::{Account, AccountMode, CostTiming, FeeSettlement, RoundTrip, TradeLog};
+use crate::strategy::{sizing::VolTarget, Strategy, StrategyContext};
+use chrono::NaiveDate;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
 
 //result of a backtest
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BacktestResult {
     pub summary: SummaryMetrics,
     pub equity_curve: Vec<EquityPoint>,
-    pub trades: Vec<crate::engine::execution::Fill>,
+    pub trades: TradeLog,
+    //time of the bar on which the drawdown stop or the equity floor halted trading, if either did
+    #[serde(default)]
+    pub halted_at: Option<chrono::DateTime<chrono::Utc>>,
+    //number of times an open position was rolled to the next contract
+    #[serde(default)]
+    pub rolls: usize,
+    //true if equity fell to the equity floor and the account was flattened and halted
+    #[serde(default)]
+    pub bankrupt: bool,
+    //peak gross contracts held at once across all symbols (hedged lots count on both sides)
+    #[serde(default)]
+    pub max_concurrent_contracts: u32,
+    //peak gross contracts held in each symbol traded
+    #[serde(default)]
+    pub max_contracts_by_symbol: HashMap<String, u32>,
+    //submitted, filled, cancelled and expired order counts
+    #[serde(default)]
+    pub order_stats: OrderStats,
+    //seed the run was made with, so it can be reproduced
+    #[serde(default)]
+    pub seed: u64,
+}
+
+impl BacktestResult {
+    //completed trades with entry, exit and realized pnl, from the account's position accounting
+    pub fn round_trips(&self) -> Vec<RoundTrip> {
+        self.trades.round_trips()
+    }
+
+    //saves the result to a JSON file
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    //loads a result previously written by save
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let result = serde_json::from_str(&contents)?;
+        Ok(result)
+    }
+}
+
+//engine state after a bar, passed to the run_with_hook callback
+#[derive(Debug)]
+pub struct BarState<'a> {
+    pub index: usize,
+    pub bar: &'a Bar,
+    pub account: &'a Account,
+    pub pending_orders: &'a [Order],
+}
+
+//price used to mark open positions to market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MarkPrice {
+    //mark every bar at its close
+    #[default]
+    Close,
+    //mark the last bar of each day at its settlement price (close if missing)
+    Settlement,
+}
+
+impl MarkPrice {
+    //parse mark price from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "close" => Some(MarkPrice::Close),
+            "settlement" | "settle" => Some(MarkPrice::Settlement),
+            _ => None,
+        }
+    }
+}
+
+//price at which market orders submitted by the strategy are filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum SignalFillMode {
+    //fill at the open of the bar after the signal, so any gap from the signal close is paid
+    #[default]
+    NextOpen,
+    //fill at the close of the signal bar (optimistic; useful to measure the gap cost)
+    SignalClose,
+}
+
+impl SignalFillMode {
+    //parse signal fill mode from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "next_open" | "next-open" | "open" => Some(SignalFillMode::NextOpen),
+            "signal_close" | "signal-close" | "close" => Some(SignalFillMode::SignalClose),
+            _ => None,
+        }
+    }
 }
 
 //configuration for a backtest
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct BacktestConfig {
     pub initial_balance: f64,
     pub commission_per_contract: f64,
+    //least commission per order side, and a flat ticket fee per order side
+    pub min_commission_per_order: f64,
+    pub commission_per_order: f64,
     pub slippage_per_contract: f64,
+    pub cost_timing: CostTiming,
+    //whether costs leave cash per fill or once a day
+    pub fee_settlement: FeeSettlement,
+    //whether opposing fills net or are held as separate lots
+    pub account_mode: AccountMode,
     pub max_lookback: usize,
+    //bars the strategy doesn't see at the start of the run
+    //equity is still marked (flat) on these bars so the curve covers every bar
+    pub warmup_bars: usize,
+    //warmup as a span of time from the first bar; bars before first + period are skipped
+    //when both are set, warmup ends at whichever cutoff comes later
+    pub warmup_period: Option<chrono::Duration>,
+    pub execution_mode: ExecutionMode,
+    pub limit_fill_mode: LimitFillMode,
+    //whether stops trigger on a touch or need a close beyond the stop
+    pub stop_trigger: StopTrigger,
+    //largest fraction of a bar's volume market orders may fill, and what happens to the rest
+    pub max_participation: Option<f64>,
+    pub market_tif: MarketTif,
+    //which bracket leg fills when a bar contains both the take-profit and the stop
+    pub bracket_fill_policy: BracketFillPolicy,
+    //bars strategy orders are held after the signal before they can fill
+    //applied on top of the next-open (or signal-close) fill, so 1 fills one bar later
+    pub signal_delay_bars: u32,
+    //ticks of adverse price slippage on market fills (on top of slippage_per_contract)
+    pub market_slippage_ticks: u32,
+    //how limit/stop orders priced on the wrong side of the last close are handled
+    pub placement_check: PlacementCheck,
+    pub max_pyramid_entries: Option<usize>,
+    //caps the position each bar at the contracts whose one-atr move is worth the target risk
+    pub vol_target: Option<VolTarget>,
+    //reject entries for this many bars, counting the bar of the last fill
+    pub min_bars_between_trades: Option<usize>,
+    //halt trading and flatten once drawdown from peak equity exceeds this fraction
+    pub max_drawdown_stop: Option<f64>,
+    //equity at or below which the account is bankrupt: it is flattened, trading halts and
+    //the result is flagged (none = trade on with any equity)
+    pub equity_floor: Option<f64>,
+    pub mark_price: MarkPrice,
+    //when the strategy's market orders fill: next bar's open or the signal bar's close
+    pub signal_fill_mode: SignalFillMode,
+    pub drawdown_mode: DrawdownMode,
+    //measure drawdown at each bar's worst-case equity (low for longs, high for shorts)
+    pub intrabar_drawdown: bool,
+    //currency used to format monetary output
+    pub currency: CurrencyFormat,
+    //return periods per year for annualizing sharpe, sortino and volatility
+    //(none = infer from the spacing of the bars)
+    pub periods_per_year: Option<f64>,
+    //seed for every stochastic component of a run; the same seed, data and config give the
+    //same result
+    pub seed: u64,
+    //dates on which an open position is rolled to the next contract
+    //the roll closes and reopens the position at the open of the first bar on or after each date,
+    //so it pays commission and slippage on both sides like any other fills
+    pub roll_dates: Vec<NaiveDate>,
+    //namespace for order/fill ids, distinct per engine when merging results
+    pub id_namespace: u32,
+    //trust that bars are already in chronological order and skip the check in new()
+    //unsorted bars then run out of order: fills, equity marks and indicators all come out wrong
+    pub bars_pre_sorted: bool,
 }
 
 impl Default for BacktestConfig {
@@ -28,8 +195,55 @@ impl Default for BacktestConfig {
         BacktestConfig {
             initial_balance: 100000.0,
             commission_per_contract: 2.5,
+            min_commission_per_order: 0.0,
+            commission_per_order: 0.0,
             slippage_per_contract: 1.0,
+            cost_timing: CostTiming::default(),
+            fee_settlement: FeeSettlement::default(),
+            account_mode: AccountMode::default(),
             max_lookback: 500,
+            warmup_bars: 0,
+            warmup_period: None,
+            execution_mode: ExecutionMode::default(),
+            limit_fill_mode: LimitFillMode::default(),
+            stop_trigger: StopTrigger::default(),
+            max_participation: None,
+            market_tif: MarketTif::default(),
+            bracket_fill_policy: BracketFillPolicy::default(),
+            signal_delay_bars: 0,
+            market_slippage_ticks: 0,
+            placement_check: PlacementCheck::default(),
+            max_pyramid_entries: None,
+            vol_target: None,
+            min_bars_between_trades: None,
+            max_drawdown_stop: None,
+            equity_floor: Some(0.0),
+            mark_price: MarkPrice::default(),
+            signal_fill_mode: SignalFillMode::default(),
+            drawdown_mode: DrawdownMode::default(),
+            intrabar_drawdown: false,
+            currency: CurrencyFormat::default(),
+            periods_per_year: None,
+            seed: 0,
+            roll_dates: Vec::new(),
+            id_namespace: 0,
+            bars_pre_sorted: false,
+        }
+    }
+}
+
+impl BacktestConfig {
+    //creates a config with the given balance and per-contract costs, defaults for the rest
+    pub fn new(
+        initial_balance: f64,
+        commission_per_contract: f64,
+        slippage_per_contract: f64,
+    ) -> Self {
+        BacktestConfig {
+            initial_balance,
+            commission_per_contract,
+            slippage_per_contract,
+            ..BacktestConfig::default()
         }
     }
 }
@@ -42,40 +256,112 @@ pub struct BacktestEngine {
     account: Account,
     execution: ExecutionEngine,
     equity_history: Vec<(chrono::DateTime<chrono::Utc>, f64)>,
+    worst_equity_history: Vec<f64>,
+    halted_at: Option<chrono::DateTime<chrono::Utc>>,
+    rolls: usize,
+    bankrupt: bool,
+    max_concurrent_contracts: u32,
+    max_contracts_by_symbol: HashMap<String, u32>,
+    //ids of the orders submitted by rolls, whose fills aren't the strategy's trading
+    roll_orders: HashSet<u64>,
 }
 
 impl BacktestEngine {
     //creates a new backtest engine
-    pub fn new(config: BacktestConfig, bars: Vec<Bar>, contract: FuturesContract) -> Self {
-        let account = Account::new(
+    //bars out of chronological order are sorted by timestamp unless config.bars_pre_sorted is set
+    pub fn new(config: BacktestConfig, mut bars: Vec<Bar>, contract: FuturesContract) -> Self {
+        if !config.bars_pre_sorted && !bars.windows(2).all(|w| w[0].timestamp <= w[1].timestamp) {
+            warn!("bars are not in chronological order, sorting by timestamp");
+            bars.sort_by_key(|bar| bar.timestamp);
+        }
+
+        let mut account = Account::new(
             config.initial_balance,
             config.commission_per_contract,
             config.slippage_per_contract,
         );
+        account.set_order_commission(config.min_commission_per_order, config.commission_per_order);
+        account.set_cost_timing(config.cost_timing);
+        account.set_fee_settlement(config.fee_settlement);
+        account.set_mode(config.account_mode);
+        let mut execution = ExecutionEngine::with_execution_mode(config.execution_mode);
+        execution.set_limit_fill_mode(config.limit_fill_mode);
+        execution.set_stop_trigger(config.stop_trigger);
+        execution.set_max_participation(config.max_participation);
+        execution.set_market_tif(config.market_tif);
+        execution.set_bracket_fill_policy(config.bracket_fill_policy);
+        execution.set_market_slippage_ticks(config.market_slippage_ticks, contract.tick_size);
+        execution.set_id_namespace(config.id_namespace);
 
         BacktestEngine {
             config,
             bars,
             contract,
             account,
-            execution: ExecutionEngine::new(),
+            execution,
             equity_history: Vec::new(),
+            worst_equity_history: Vec::new(),
+            halted_at: None,
+            rolls: 0,
+            bankrupt: false,
+            max_concurrent_contracts: 0,
+            max_contracts_by_symbol: HashMap::new(),
+            roll_orders: HashSet::new(),
         }
     }
 
     //runs the backtest with the given strategy
     pub fn run(&mut self, strategy: &mut Box<dyn Strategy>) -> BacktestResult {
+        self.run_with_hook(strategy, |_| {})
+    }
+
+    //runs the backtest, calling hook with the engine state after each bar
+    //useful for stepping through a strategy while debugging
+    pub fn run_with_hook<F>(
+        &mut self,
+        strategy: &mut Box<dyn Strategy>,
+        mut hook: F,
+    ) -> BacktestResult
+    where
+        F: FnMut(&BarState),
+    {
+        //size history to whichever is larger: configured lookback or strategy requirement
+        let lookback = self.config.max_lookback.max(strategy.required_lookback());
+
         //create strategy context
         let mut context = StrategyContext::new(
-            self.contract.symbol.clone(),
-            self.config.max_lookback,
+            self.contract.clone(),
+            lookback,
             &mut self.execution as *mut ExecutionEngine,
             &mut self.account as *mut Account,
         );
+        context.set_max_pyramid_entries(self.config.max_pyramid_entries);
+        context.set_vol_target(self.config.vol_target);
+        context.set_min_bars_between_trades(self.config.min_bars_between_trades);
+        context.set_placement_check(self.config.placement_check);
+        context.set_signal_delay_bars(self.config.signal_delay_bars);
+
+        info!(
+            "starting backtest: {} bars of {}, lookback {}",
+            self.bars.len(),
+            self.contract.symbol,
+            lookback
+        );
 
         //call strategy initialization
         strategy.on_start(&mut context);
 
+        let warmup_end = self.warmup_end();
+        if warmup_end > 0 {
+            debug!("warmup: strategy starts at bar {}", warmup_end);
+        }
+
+        let mut peak_equity = self.account.equity;
+
+        let mut roll_dates = self.config.roll_dates.clone();
+        roll_dates.sort();
+        let mut next_roll = 0;
+
         //main backtest loop
         for i in 0..self.bars.len() {
             let bar = self.bars[i].clone();
@@ -83,63 +369,137 @@ impl BacktestEngine {
             //update context with new bar
             context.push_bar(bar.clone());
 
-            //call strategy
-            strategy.on_bar(&mut context, &bar);
+            //roll an open position on the first bar on or after a roll date
+            //the roll orders fill at this bar's open, ahead of the strategy's orders
+            let mut roll_due = false;
+            while next_roll < roll_dates.len()
+                && roll_dates[next_roll] <= bar.timestamp.date_naive()
+            {
+                roll_due = true;
+                next_roll += 1;
+            }
+            if roll_due && i > 0 {
+                self.roll_position(bar.timestamp);
+            }
 
-            //process any pending orders from previous bar
-            //orders submitted on this bar will be filled at next bar's open
-            if i > 0 {
-                let fills = self.execution.process_orders(bar.open, bar.high, bar.low);
+            //process any pending orders from previous bars before the strategy sees this one,
+            //so orders submitted on this bar can't fill at its own open
+            let fills = self.execution.process_bar(&bar);
+            self.book_strategy_fills(&mut context, fills);
 
-                //process fills
-                for fill in fills {
-                    self.account.process_fill(fill, &self.contract);
-                }
+            //call strategy (not during warmup; the bar is still in the context's history)
+            //orders submitted on this bar fill at the next bar's open unless filling at the close
+            if i >= warmup_end {
+                strategy.on_bar(&mut context, &bar);
+            }
+
+            if self.config.signal_fill_mode == SignalFillMode::SignalClose {
+                let fills = self
+                    .execution
+                    .fill_market_orders_at(bar.close, bar.timestamp);
+                self.book_strategy_fills(&mut context, fills);
+            }
+
+            //accrued daily fees are charged on the last bar of the trading day
+            if self.is_last_bar_of_day(i) {
+                self.account.settle_fees();
             }
 
             //update account equity
             let mut prices = HashMap::new();
-            prices.insert(self.contract.symbol.clone(), bar.close);
+            prices.insert(self.contract.symbol.clone(), self.mark_price_at(i));
 
             let mut contracts = HashMap::new();
             contracts.insert(self.contract.symbol.clone(), self.contract.clone());
 
             self.account.update_equity(&prices, &contracts);
 
+            //margin check is informational only; the engine doesn't liquidate on a breach
+            if self.account.is_margin_breach(&contracts) {
+                warn!(
+                    "{}: equity {:.2} below maintenance margin",
+                    bar.timestamp, self.account.equity
+                );
+            }
+
             //record equity
             self.equity_history
                 .push((bar.timestamp, self.account.equity));
+            self.worst_equity_history.push(self.worst_case_equity(&bar));
+
+            //drawdown stop: flatten and halt for the rest of the run
+            peak_equity = peak_equity.max(self.account.equity);
+            if self.drawdown_stop_breached(peak_equity) && !context.is_halted() {
+                warn!(
+                    "{}: drawdown stop breached (peak {:.2}, equity {:.2}), halting",
+                    bar.timestamp, peak_equity, self.account.equity
+                );
+                context.halt();
+                self.flatten(bar.timestamp, ExitReason::DrawdownStop);
+                self.halted_at = Some(bar.timestamp);
+            }
+
+            //equity floor: the account is bankrupt, flatten and halt for the rest of the run
+            if !self.bankrupt
+                && self
+                    .config
+                    .equity_floor
+                    .is_some_and(|floor| self.account.equity <= floor)
+            {
+                warn!(
+                    "{}: equity {:.2} at or below the equity floor, account bankrupt",
+                    bar.timestamp, self.account.equity
+                );
+                self.bankrupt = true;
+                if !context.is_halted() {
+                    context.halt();
+                    self.flatten(bar.timestamp, ExitReason::EquityFloor);
+                    self.halted_at = Some(bar.timestamp);
+                }
+            }
+
+            hook(&BarState {
+                index: i,
+                bar: &bar,
+                account: &self.account,
+                pending_orders: self.execution.pending_orders(),
+            });
         }
 
-        //process any remaining orders at final bar
-        if !self.bars.is_empty() {
-            let last_bar = self.bars.last().unwrap();
-            let fills = self
+        //end-of-run order semantics: there is no next bar, so market orders submitted on the
+        //final bar and in on_end fill at the final bar's close, stamped with its timestamp
+        //resting limit/stop orders have already been checked against the final bar and expire
+        //an empty dataset has nothing to fill against, so its orders expire unfilled
+        //orders still held by the signal delay are released, as no later bar can come
+        self.execution.release_held_orders();
+        if let Some(last_bar) = self.bars.last() {
+            for fill in self
                 .execution
-                .process_orders(last_bar.close, last_bar.high, last_bar.low);
-
-            for fill in fills {
-                self.account.process_fill(fill, &self.contract);
+                .fill_market_orders_at(last_bar.close, last_bar.timestamp)
+            {
+                self.book_fill(fill);
             }
         }
 
-        //call strategy finalization
+        //call strategy finalization (sees the position after the final bar's fills)
         strategy.on_end(&mut context);
 
-        //process final orders
-        if !self.bars.is_empty() {
-            let last_bar = self.bars.last().unwrap();
-            let fills = self
+        self.execution.release_held_orders();
+        if let Some(last_bar) = self.bars.last() {
+            for fill in self
                 .execution
-                .process_orders(last_bar.close, last_bar.high, last_bar.low);
-
-            for fill in fills {
-                self.account.process_fill(fill, &self.contract);
+                .fill_market_orders_at(last_bar.close, last_bar.timestamp)
+            {
+                self.book_fill(fill);
             }
+            self.account.settle_fees();
 
             //final equity update
             let mut prices = HashMap::new();
-            prices.insert(self.contract.symbol.clone(), last_bar.close);
+            prices.insert(
+                self.contract.symbol.clone(),
+                self.mark_price_at(self.bars.len() - 1),
+            );
 
             let mut contracts = HashMap::new();
             contracts.insert(self.contract.symbol.clone(), self.contract.clone());
@@ -150,28 +510,239 @@ impl BacktestEngine {
             if let Some(last) = self.equity_history.last_mut() {
                 last.1 = self.account.equity;
             }
+            if let Some(worst) = self.worst_equity_history.last_mut() {
+                *worst = worst.min(self.account.equity);
+            }
         }
 
+        //orders still resting after the final bar can never fill
+        self.execution.expire_all_orders();
+
+        info!(
+            "backtest finished: final equity {:.2}, {} fill(s)",
+            self.account.equity,
+            self.account.trade_log.len()
+        );
+
         //build result
         self.build_result()
     }
 
+    //returns the index of the first bar the strategy sees, combining the bar-count and
+    //calendar warmups
+    fn warmup_end(&self) -> usize {
+        let period_end = match (self.config.warmup_period, self.bars.first()) {
+            (Some(period), Some(first)) => {
+                let cutoff = first.timestamp + period;
+                self.bars.partition_point(|bar| bar.timestamp < cutoff)
+            }
+            _ => 0,
+        };
+        self.config.warmup_bars.max(period_end)
+    }
+
+    //books fills of the strategy's orders, starting the entry cooldown and counting add-ons
+    //towards the pyramiding limit
+    //roll fills are only booked: a roll neither starts the cooldown nor resets the add-ons
+    fn book_strategy_fills(&mut self, context: &mut StrategyContext, fills: Vec<Fill>) {
+        let mut strategy_filled = false;
+        for fill in fills {
+            if self.roll_orders.contains(&fill.order_id) {
+                self.book_fill(fill);
+                continue;
+            }
+            strategy_filled = true;
+            let symbol = fill.symbol.clone();
+            let order_id = fill.order_id;
+            let net_before = self
+                .account
+                .get_position(&symbol)
+                .map_or(0, |position| position.net_qty);
+            self.book_fill(fill);
+            context.record_pyramid_fill(&symbol, order_id, net_before);
+        }
+        if strategy_filled {
+            context.record_fill();
+        }
+    }
+
+    //books a fill against the engine's contract
+    //the context rejects orders for other symbols, so a fill for one is dropped (and logged)
+    //rather than priced with the wrong contract
+    fn book_fill(&mut self, fill: Fill) {
+        if let Err(err) = self.account.process_fill(fill, &self.contract) {
+            error!("{}", err);
+        }
+        self.track_max_contracts();
+    }
+
+    //updates the peak gross contracts held, per symbol and in total
+    fn track_max_contracts(&mut self) {
+        let mut total = 0;
+        for (symbol, position) in &self.account.open_positions {
+            let held = position.long_qty() + position.short_qty();
+            total += held;
+            let max = self
+                .max_contracts_by_symbol
+                .entry(symbol.clone())
+                .or_insert(0);
+            *max = (*max).max(held);
+        }
+        self.max_concurrent_contracts = self.max_concurrent_contracts.max(total);
+    }
+
+    //returns the price used to mark equity at bar i
+    //settlement marking applies only to the last bar of each trading day
+    fn mark_price_at(&self, i: usize) -> f64 {
+        let bar = &self.bars[i];
+
+        match self.config.mark_price {
+            MarkPrice::Close => bar.close,
+            MarkPrice::Settlement => match bar.settlement {
+                Some(settlement) if self.is_last_bar_of_day(i) => settlement,
+                _ => bar.close,
+            },
+        }
+    }
+
+    //returns true if bar i is the last bar of its trading day (or of the data)
+    fn is_last_bar_of_day(&self, i: usize) -> bool {
+        self.bars
+            .get(i + 1)
+            .map(|next| next.timestamp.date_naive() != self.bars[i].timestamp.date_naive())
+            .unwrap_or(true)
+    }
+
+    //returns true if equity has fallen further below peak than the drawdown stop allows
+    fn drawdown_stop_breached(&self, peak_equity: f64) -> bool {
+        match self.config.max_drawdown_stop {
+            Some(limit) if peak_equity > 0.0 => {
+                (peak_equity - self.account.equity) / peak_equity > limit
+            }
+            _ => false,
+        }
+    }
+
+    //submits orders closing the open position and reopening it in the next contract
+    //long and short lots of a hedging account are rolled separately
+    fn roll_position(&mut self, timestamp: chrono::DateTime<chrono::Utc>) {
+        let (long_qty, short_qty) = match self.account.get_position(&self.contract.symbol) {
+            Some(position) if !position.is_flat() => (position.long_qty(), position.short_qty()),
+            _ => return,
+        };
+
+        let legs = [
+            (long_qty, OrderSide::Sell, OrderSide::Buy),
+            (short_qty, OrderSide::Buy, OrderSide::Sell),
+        ];
+        for (qty, close_side, open_side) in legs {
+            if qty == 0 {
+                continue;
+            }
+
+            debug!(
+                "{}: rolling {} {} {:?} into the next contract",
+                timestamp, qty, self.contract.symbol, open_side
+            );
+            let close_id = self.execution.market_order(
+                timestamp,
+                self.contract.symbol.clone(),
+                qty,
+                close_side,
+            );
+            self.execution.tag_order(close_id, ExitReason::Roll);
+            let open_id = self.execution.market_order(
+                timestamp,
+                self.contract.symbol.clone(),
+                qty,
+                open_side,
+            );
+            self.roll_orders.extend([close_id, open_id]);
+        }
+        self.rolls += 1;
+    }
+
+    //cancels pending orders and submits market orders closing the open position
+    fn flatten(&mut self, timestamp: chrono::DateTime<chrono::Utc>, reason: ExitReason) {
+        self.execution.cancel_all_orders();
+
+        let (long_qty, short_qty) = match self.account.get_position(&self.contract.symbol) {
+            Some(position) => (position.long_qty(), position.short_qty()),
+            None => return,
+        };
+
+        for (qty, side) in [(long_qty, OrderSide::Sell), (short_qty, OrderSide::Buy)] {
+            if qty > 0 {
+                let id =
+                    self.execution
+                        .market_order(timestamp, self.contract.symbol.clone(), qty, side);
+                self.execution.tag_order(id, reason);
+            }
+        }
+    }
+
+    //returns equity with the open position marked at the bar's adverse extreme
+    fn worst_case_equity(&self, bar: &Bar) -> f64 {
+        match self.account.get_position(&self.contract.symbol) {
+            Some(position) if !position.is_flat() => {
+                let worst_price = if position.is_long() {
+                    bar.low
+                } else {
+                    bar.high
+                };
+                let worst =
+                    self.account.cash + position.unrealized_pnl(worst_price, &self.contract);
+                worst.min(self.account.equity)
+            }
+            _ => self.account.equity,
+        }
+    }
+
     fn build_result(&self) -> BacktestResult {
         let timestamps: Vec<_> = self.equity_history.iter().map(|(t, _)| *t).collect();
         let equity_values: Vec<_> = self.equity_history.iter().map(|(_, e)| *e).collect();
 
-        let equity_curve =
-            calculate_equity_curve(&timestamps, &equity_values, self.config.initial_balance);
+        //close-only marking measures drawdown at the marked equity itself
+        let worst_equity_values = if self.config.intrabar_drawdown {
+            &self.worst_equity_history
+        } else {
+            &equity_values
+        };
+
+        let equity_curve = calculate_equity_curve_intrabar(
+            &timestamps,
+            &equity_values,
+            worst_equity_values,
+            self.config.initial_balance,
+            self.config.drawdown_mode,
+        );
 
         let trades = self.account.trade_log.clone();
 
-        let summary =
-            SummaryMetrics::from_backtest(&equity_curve, &trades, self.config.initial_balance);
+        let calendar =
+            TradingCalendar::default().with_periods_per_year(self.config.periods_per_year);
+        let summary = SummaryMetrics::from_backtest_with_calendar(
+            &equity_curve,
+            &trades,
+            self.config.initial_balance,
+            &calendar,
+        )
+        .with_drawdown_mode(self.config.drawdown_mode)
+        .with_currency(self.config.currency.clone())
+        .with_max_concurrent_contracts(self.max_concurrent_contracts)
+        .with_slippage_stats(slippage_stats(&trades, self.contract.tick_value));
 
         BacktestResult {
             summary,
             equity_curve,
             trades,
+            halted_at: self.halted_at,
+            rolls: self.rolls,
+            bankrupt: self.bankrupt,
+            max_concurrent_contracts: self.max_concurrent_contracts,
+            max_contracts_by_symbol: self.max_contracts_by_symbol.clone(),
+            order_stats: self.execution.order_stats(),
+            seed: self.config.seed,
         }
     }
 
@@ -185,3 +756,838 @@ impl BacktestEngine {
         &self.contract
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::execution::OrderRejection;
+    use chrono::{TimeZone, Utc};
+
+    //buys one contract at market on the second bar it sees
+    struct BuyOnce {
+        bars_seen: usize,
+    }
+
+    impl Strategy for BuyOnce {
+        fn on_start(&mut self, _context: &mut StrategyContext) {}
+
+        fn on_bar(&mut self, context: &mut StrategyContext, _bar: &Bar) {
+            self.bars_seen += 1;
+            if self.bars_seen == 2 {
+                context
+                    .market_order("ES".to_string(), 1, OrderSide::Buy)
+                    .unwrap();
+            }
+        }
+
+        fn on_end(&mut self, _context: &mut StrategyContext) {}
+
+        fn name(&self) -> &str {
+            "Buy Once"
+        }
+    }
+
+    //daily bars from (open, close) pairs, with the range just covering both
+    fn bars(prices: &[(f64, f64)]) -> Vec<Bar> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        prices
+            .iter()
+            .enumerate()
+            .map(|(i, &(open, close))| {
+                Bar::new(
+                    start + chrono::Duration::days(i as i64),
+                    open,
+                    open.max(close) + 1.0,
+                    open.min(close) - 1.0,
+                    close,
+                    1000.0,
+                    None,
+                    "ES".to_string(),
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    fn run(config: BacktestConfig, prices: &[(f64, f64)]) -> BacktestResult {
+        run_bars(config, bars(prices))
+    }
+
+    fn run_bars(config: BacktestConfig, bars: Vec<Bar>) -> BacktestResult {
+        let mut engine = BacktestEngine::new(config, bars, FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(BuyOnce { bars_seen: 0 });
+        engine.run(&mut strategy)
+    }
+
+    //the hook sees every bar once, after its fills and mark to market
+    #[test]
+    fn hook_runs_once_per_bar_with_the_engine_state() {
+        let prices = [
+            (4000.0, 4005.0),
+            (4010.0, 4015.0),
+            (4020.0, 4030.0),
+            (4030.0, 4010.0),
+        ];
+        let mut engine = BacktestEngine::new(
+            BacktestConfig::default(),
+            bars(&prices),
+            FuturesContract::es("H24"),
+        );
+        let mut strategy: Box<dyn Strategy> = Box::new(BuyOnce { bars_seen: 0 });
+
+        let mut seen = Vec::new();
+        let result = engine.run_with_hook(&mut strategy, |state| {
+            let position = state.account.get_position("ES").map_or(0, |p| p.net_qty);
+            seen.push((
+                state.index,
+                state.bar.timestamp,
+                state.account.equity,
+                position,
+                state.pending_orders.len(),
+            ));
+        });
+
+        let indices: Vec<usize> = seen.iter().map(|s| s.0).collect();
+        assert_eq!(indices, vec![0, 1, 2, 3]);
+
+        //equity matches the curve bar for bar
+        assert_eq!(result.equity_curve.len(), seen.len());
+        for (point, state) in result.equity_curve.iter().zip(&seen) {
+            assert_eq!(point.timestamp, state.1);
+            assert_eq!(point.equity, state.2);
+        }
+
+        //the order from bar 2 is pending after it and fills at bar 3's open
+        let positions: Vec<i32> = seen.iter().map(|s| s.3).collect();
+        let pending: Vec<usize> = seen.iter().map(|s| s.4).collect();
+        assert_eq!(positions, vec![0, 0, 1, 1]);
+        assert_eq!(pending, vec![0, 1, 0, 0]);
+        assert_eq!(seen[3].2 - seen[2].2, -20.0 * 50.0);
+    }
+
+    //an order submitted on bar n fills at bar n+1's open, never at bar n's own open
+    //(pending orders are processed before the strategy sees each bar)
+    #[test]
+    fn market_order_fills_at_next_bar_open() {
+        let result = run(
+            BacktestConfig::default(),
+            &[
+                (4000.0, 4005.0),
+                (4010.0, 4015.0),
+                (4020.0, 4030.0),
+                (4030.0, 4040.0),
+            ],
+        );
+
+        assert_eq!(result.trades.fills()[0].fill_price, 4020.0);
+    }
+
+    //the overnight gap between the signal close and the next open is the cost of next-open fills
+    #[test]
+    fn signal_close_skips_the_gap_cost() {
+        let gappy = [
+            (4000.0, 4005.0),
+            (4005.0, 4010.0),
+            (4030.0, 4035.0),
+            (4035.0, 4040.0),
+        ];
+        let next_open = run(BacktestConfig::default(), &gappy);
+        let signal_close = run(
+            BacktestConfig {
+                signal_fill_mode: SignalFillMode::SignalClose,
+                ..BacktestConfig::default()
+            },
+            &gappy,
+        );
+
+        assert_eq!(next_open.trades.fills()[0].fill_price, 4030.0);
+        assert_eq!(signal_close.trades.fills()[0].fill_price, 4010.0);
+
+        //20 points of gap on a 50-dollar point value
+        let final_equity = |result: &BacktestResult| result.equity_curve.last().unwrap().equity;
+        assert!((final_equity(&signal_close) - final_equity(&next_open) - 1000.0).abs() < 1e-6);
+    }
+
+    //a delayed entry fills one bar later, and its fill carries the bar it filled on
+    #[test]
+    fn signal_delay_fills_one_bar_later() {
+        let prices = [
+            (4000.0, 4005.0),
+            (4010.0, 4015.0),
+            (4020.0, 4025.0),
+            (4030.0, 4035.0),
+            (4040.0, 4045.0),
+        ];
+        let timestamps: Vec<_> = bars(&prices).iter().map(|bar| bar.timestamp).collect();
+
+        let immediate = run(BacktestConfig::default(), &prices);
+        let delayed = run(
+            BacktestConfig {
+                signal_delay_bars: 1,
+                ..BacktestConfig::default()
+            },
+            &prices,
+        );
+
+        let immediate = &immediate.trades.fills()[0];
+        let delayed = &delayed.trades.fills()[0];
+        assert_eq!(immediate.fill_price, 4020.0);
+        assert_eq!(immediate.timestamp, timestamps[2]);
+        assert_eq!(delayed.fill_price, 4030.0);
+        assert_eq!(delayed.timestamp, timestamps[3]);
+    }
+
+    //two bars a day, each settling 5 points above its close
+    #[test]
+    fn settlement_marks_the_last_bar_of_each_day() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 9, 0, 0).unwrap();
+        let settled: Vec<Bar> = (0..4)
+            .map(|i| {
+                let close = 4000.0 + 10.0 * i as f64;
+                let timestamp =
+                    start + chrono::Duration::days(i / 2) + chrono::Duration::hours(6 * (i % 2));
+                Bar::new(
+                    timestamp,
+                    close,
+                    close + 1.0,
+                    close - 1.0,
+                    close,
+                    1000.0,
+                    None,
+                    "ES".to_string(),
+                )
+                .unwrap()
+                .with_settlement(Some(close + 5.0))
+            })
+            .collect();
+
+        let by_close = run_bars(BacktestConfig::default(), settled.clone());
+        let by_settlement = run_bars(
+            BacktestConfig {
+                mark_price: MarkPrice::Settlement,
+                ..BacktestConfig::default()
+            },
+            settled,
+        );
+
+        //long one contract from the third bar's open
+        let gap = |i: usize| by_settlement.equity_curve[i].equity - by_close.equity_curve[i].equity;
+        assert!(gap(2).abs() < 1e-6);
+        assert!((gap(3) - 5.0 * 50.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn zero_bar_run_returns_an_empty_result() {
+        let result = run_bars(BacktestConfig::default(), Vec::new());
+
+        assert!(result.equity_curve.is_empty());
+        assert!(result.trades.fills().is_empty());
+        assert_eq!(result.summary.final_balance, 100000.0);
+        assert_eq!(result.summary.total_return, 0.0);
+    }
+
+    #[test]
+    fn one_bar_run_returns_flat_metrics() {
+        let result = run(BacktestConfig::default(), &[(4000.0, 4005.0)]);
+
+        assert_eq!(result.equity_curve.len(), 1);
+        assert_eq!(result.summary.cagr, 0.0);
+        assert_eq!(result.summary.sharpe_ratio, 0.0);
+        assert!(result.summary.total_return.is_finite());
+    }
+
+    //needs more history than the engine's configured lookback
+    struct WideWindow {
+        longest_history: usize,
+    }
+
+    impl Strategy for WideWindow {
+        fn on_start(&mut self, _context: &mut StrategyContext) {}
+
+        fn on_bar(&mut self, context: &mut StrategyContext, _bar: &Bar) {
+            self.longest_history = self.longest_history.max(context.get_all_bars().len());
+        }
+
+        fn on_end(&mut self, _context: &mut StrategyContext) {}
+
+        fn name(&self) -> &str {
+            "Wide Window"
+        }
+
+        fn required_lookback(&self) -> usize {
+            40
+        }
+    }
+
+    #[test]
+    fn history_covers_the_strategy_lookback() {
+        let prices: Vec<(f64, f64)> = (0..60).map(|i| (4000.0 + i as f64, 4000.0)).collect();
+        let config = BacktestConfig {
+            max_lookback: 10,
+            ..BacktestConfig::default()
+        };
+        let mut engine = BacktestEngine::new(config, bars(&prices), FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(WideWindow { longest_history: 0 });
+        engine.run(&mut strategy);
+
+        let strategy = strategy.downcast_ref::<WideWindow>().unwrap();
+        assert_eq!(strategy.longest_history, 40);
+    }
+
+    //long from the third bar's open; the fourth bar wicks 80 points down but closes higher
+    #[test]
+    fn intrabar_drawdown_sees_the_wick() {
+        let mut wicky = bars(&[
+            (4000.0, 4005.0),
+            (4010.0, 4015.0),
+            (4020.0, 4030.0),
+            (4030.0, 4040.0),
+            (4040.0, 4045.0),
+        ]);
+        wicky[3].low = 3950.0;
+
+        let close_only = run_bars(BacktestConfig::default(), wicky.clone());
+        let intrabar = run_bars(
+            BacktestConfig {
+                intrabar_drawdown: true,
+                ..BacktestConfig::default()
+            },
+            wicky,
+        );
+
+        //marked equity is identical; only the measured drawdown differs
+        assert_eq!(
+            close_only.equity_curve.last().unwrap().equity,
+            intrabar.equity_curve.last().unwrap().equity
+        );
+        assert!(intrabar.summary.max_drawdown > close_only.summary.max_drawdown);
+        assert!(intrabar.equity_curve[3].drawdown > close_only.equity_curve[3].drawdown);
+    }
+
+    #[test]
+    fn saved_result_loads_back() {
+        let result = run(
+            BacktestConfig::default(),
+            &[
+                (4000.0, 4005.0),
+                (4010.0, 4015.0),
+                (4020.0, 4030.0),
+                (4030.0, 4040.0),
+            ],
+        );
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("result.json");
+        result.save(&path).unwrap();
+        let loaded = BacktestResult::load(&path).unwrap();
+
+        assert_eq!(loaded.summary.num_trades, result.summary.num_trades);
+        assert!((loaded.summary.final_balance - result.summary.final_balance).abs() < 1e-6);
+        assert_eq!(loaded.equity_curve.len(), result.equity_curve.len());
+        for (loaded, original) in loaded.equity_curve.iter().zip(&result.equity_curve) {
+            assert_eq!(loaded.timestamp, original.timestamp);
+            assert!((loaded.equity - original.equity).abs() < 1e-6);
+        }
+        let fills = |result: &BacktestResult| {
+            result
+                .trades
+                .fills()
+                .iter()
+                .map(|fill| (fill.id, fill.qty, fill.fill_price, fill.timestamp))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fills(&loaded), fills(&result));
+        assert_eq!(loaded.round_trips().len(), result.round_trips().len());
+    }
+
+    #[test]
+    fn loading_a_missing_result_fails() {
+        let dir = tempfile::tempdir().unwrap();
+        assert!(BacktestResult::load(&dir.path().join("missing.json")).is_err());
+    }
+
+    //long one contract from the third bar's open, rolled on the fifth and seventh bars
+    #[test]
+    fn each_roll_pays_fees_on_both_legs() {
+        let prices: Vec<(f64, f64)> = (0..8)
+            .map(|i| (4000.0 + 10.0 * i as f64, 4005.0 + 10.0 * i as f64))
+            .collect();
+        let dates: Vec<_> = bars(&prices)
+            .iter()
+            .map(|bar| bar.timestamp.date_naive())
+            .collect();
+
+        let plain = run(BacktestConfig::default(), &prices);
+        let rolled = run(
+            BacktestConfig {
+                roll_dates: vec![dates[6], dates[4]],
+                ..BacktestConfig::default()
+            },
+            &prices,
+        );
+
+        assert_eq!(rolled.rolls, 2);
+        assert_eq!(rolled.trades.fills().len(), plain.trades.fills().len() + 4);
+
+        //every fill is one contract, so each roll leg costs what the entry cost
+        let fees_per_fill = plain.trades.fills()[0].fees();
+        assert!(fees_per_fill > 0.0);
+        let extra_fees = rolled.trades.total_fees() - plain.trades.total_fees();
+        assert!((extra_fees - 4.0 * fees_per_fill).abs() < 1e-6);
+
+        let final_equity = |result: &BacktestResult| result.equity_curve.last().unwrap().equity;
+        assert!((final_equity(&plain) - final_equity(&rolled) - extra_fees).abs() < 1e-6);
+    }
+
+    //submits the scripted market orders on the given bars (counted from 1), and optionally
+    //closes whatever is left open in on_end
+    struct Scripted {
+        orders: Vec<(usize, OrderSide, u32)>,
+        bars_seen: usize,
+        flatten_on_end: bool,
+        //rejected orders as (bar, reason)
+        rejections: Vec<(usize, OrderRejection)>,
+    }
+
+    impl Scripted {
+        fn new(orders: Vec<(usize, OrderSide, u32)>, flatten_on_end: bool) -> Self {
+            Scripted {
+                orders,
+                bars_seen: 0,
+                flatten_on_end,
+                rejections: Vec::new(),
+            }
+        }
+    }
+
+    impl Strategy for Scripted {
+        fn on_start(&mut self, _context: &mut StrategyContext) {}
+
+        fn on_bar(&mut self, context: &mut StrategyContext, _bar: &Bar) {
+            self.bars_seen += 1;
+            for &(bar, side, qty) in &self.orders {
+                //a rejected order (eg once trading has halted) is recorded, not placed
+                if bar == self.bars_seen {
+                    if let Err(rejection) = context.market_order("ES".to_string(), qty, side) {
+                        self.rejections.push((bar, rejection));
+                    }
+                }
+            }
+        }
+
+        fn on_end(&mut self, context: &mut StrategyContext) {
+            let net_qty = context.net_qty("ES");
+            if self.flatten_on_end && net_qty != 0 {
+                let side = if net_qty > 0 {
+                    OrderSide::Sell
+                } else {
+                    OrderSide::Buy
+                };
+                let id = context
+                    .market_order("ES".to_string(), net_qty.unsigned_abs(), side)
+                    .unwrap();
+                context.tag_order(id, ExitReason::EndOfRun);
+            }
+        }
+
+        fn name(&self) -> &str {
+            "Scripted"
+        }
+    }
+
+    //long from the third open, reversed short at the fifth, covered at the seventh
+    #[test]
+    fn round_trips_pair_entries_with_exits() {
+        let prices: Vec<(f64, f64)> = (0..8)
+            .map(|i| (4000.0 + 10.0 * i as f64, 4005.0 + 10.0 * i as f64))
+            .collect();
+        let mut engine = BacktestEngine::new(
+            BacktestConfig::default(),
+            bars(&prices),
+            FuturesContract::es("H24"),
+        );
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(
+            vec![
+                (2, OrderSide::Buy, 1),
+                (4, OrderSide::Sell, 2),
+                (6, OrderSide::Buy, 1),
+            ],
+            false,
+        ));
+        let result = engine.run(&mut strategy);
+
+        let trips = result.round_trips();
+        assert_eq!(trips.len(), 2);
+        assert_eq!(trips[0].side, OrderSide::Buy);
+        assert_eq!(
+            (trips[0].entry_price, trips[0].exit_price),
+            (4020.0, 4040.0)
+        );
+        assert!((trips[0].pnl - 1000.0).abs() < 1e-6);
+        assert_eq!(trips[1].side, OrderSide::Sell);
+        assert_eq!(
+            (trips[1].entry_price, trips[1].exit_price),
+            (4040.0, 4060.0)
+        );
+        assert!((trips[1].pnl + 1000.0).abs() < 1e-6);
+        assert_eq!(trips[1].exit_time, bars(&prices)[6].timestamp);
+    }
+
+    //thirty round trips at an unchanged price leave only the fees, with no float residue
+    #[test]
+    fn flat_strategy_ends_at_the_balance_minus_fees() {
+        let prices = vec![(4000.0, 4000.0); 62];
+        let orders = (1..=60)
+            .map(|bar| {
+                let side = if bar % 2 == 1 {
+                    OrderSide::Buy
+                } else {
+                    OrderSide::Sell
+                };
+                (bar, side, 1)
+            })
+            .collect();
+        let config = BacktestConfig {
+            commission_per_contract: 0.1,
+            slippage_per_contract: 0.07,
+            ..BacktestConfig::default()
+        };
+        let mut engine = BacktestEngine::new(config, bars(&prices), FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(orders, false));
+        let result = engine.run(&mut strategy);
+
+        assert_eq!(result.trades.fills().len(), 60);
+        assert_eq!(result.summary.final_balance, 99989.8);
+        assert_eq!(result.summary.total_return, -10.2);
+    }
+
+    fn run_scripted(orders: Vec<(usize, OrderSide, u32)>, bars: Vec<Bar>) -> BacktestResult {
+        let mut engine =
+            BacktestEngine::new(BacktestConfig::default(), bars, FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(orders, true));
+        engine.run(&mut strategy)
+    }
+
+    //on_end orders flatten at the final bar's close and carry its timestamp
+    #[test]
+    fn on_end_orders_flatten_at_the_final_close() {
+        let prices = [
+            (4000.0, 4005.0),
+            (4010.0, 4015.0),
+            (4020.0, 4030.0),
+            (4030.0, 4040.0),
+        ];
+        let last = bars(&prices)[3].clone();
+        let result = run_scripted(vec![(2, OrderSide::Buy, 1)], bars(&prices));
+
+        let fills = result.trades.fills();
+        assert_eq!(fills.len(), 2);
+        let exit = &fills[1];
+        assert_eq!(exit.fill_price, last.close);
+        assert_eq!(exit.timestamp, last.timestamp);
+        assert_eq!(exit.exit_reason, Some(ExitReason::EndOfRun));
+        assert_eq!(result.round_trips().len(), 1);
+    }
+
+    //an order placed on the final bar has no next open either, so it fills at the final close
+    #[test]
+    fn final_bar_order_fills_at_the_final_close() {
+        let prices = [(4000.0, 4005.0), (4010.0, 4015.0), (4020.0, 4030.0)];
+        let result = run_scripted(vec![(3, OrderSide::Buy, 1)], bars(&prices));
+
+        let fills = result.trades.fills();
+        assert_eq!(fills.len(), 2);
+        assert!(fills.iter().all(|fill| fill.fill_price == 4030.0));
+        assert!(result.trades.total_fees() > 0.0);
+    }
+
+    //warmup bars are marked as flat cash, so the curve lines up with the bars whatever the warmup
+    #[test]
+    fn equity_curve_covers_every_bar_through_warmup() {
+        let prices: Vec<(f64, f64)> = (0..12)
+            .map(|i| (4000.0 + 10.0 * i as f64, 4005.0 + 10.0 * i as f64))
+            .collect();
+        let timestamps: Vec<_> = bars(&prices).iter().map(|bar| bar.timestamp).collect();
+
+        for warmup_bars in [0, 3, 11, 20] {
+            let result = run(
+                BacktestConfig {
+                    warmup_bars,
+                    ..BacktestConfig::default()
+                },
+                &prices,
+            );
+
+            let curve_times: Vec<_> = result.equity_curve.iter().map(|p| p.timestamp).collect();
+            assert_eq!(curve_times, timestamps);
+            assert!(result.equity_curve[..warmup_bars.min(12)]
+                .iter()
+                .all(|point| point.equity == 100000.0));
+        }
+
+        //the strategy first sees bar 3, buys on bar 4 and fills at bar 5's open
+        let warmed = run(
+            BacktestConfig {
+                warmup_bars: 3,
+                ..BacktestConfig::default()
+            },
+            &prices,
+        );
+        assert_eq!(warmed.trades.fills()[0].timestamp, timestamps[5]);
+    }
+
+    //the strategy's order goes in on its second bar and fills at the next open, so the
+    //first fill lands two bars after the warmup ends
+    #[test]
+    fn calendar_warmup_skips_thirty_daily_bars() {
+        let prices: Vec<(f64, f64)> = (0..60)
+            .map(|i| (4000.0 + i as f64, 4005.0 + i as f64))
+            .collect();
+        let timestamps: Vec<_> = bars(&prices).iter().map(|bar| bar.timestamp).collect();
+        let first_fill = |warmup_bars: usize| {
+            let result = run(
+                BacktestConfig {
+                    warmup_bars,
+                    warmup_period: Some(chrono::Duration::days(30)),
+                    ..BacktestConfig::default()
+                },
+                &prices,
+            );
+            result.trades.fills()[0].timestamp
+        };
+
+        assert_eq!(first_fill(0), timestamps[32]);
+        //the later of the two cutoffs wins
+        assert_eq!(first_fill(10), timestamps[32]);
+        assert_eq!(first_fill(40), timestamps[42]);
+    }
+
+    //three flat bars a day for two days, with a fill on the second and third bar of each day
+    fn fee_settled_equity(fee_settlement: FeeSettlement) -> Vec<f64> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 9, 0, 0).unwrap();
+        let intraday: Vec<Bar> = (0..6)
+            .map(|i| {
+                let timestamp =
+                    start + chrono::Duration::days(i / 3) + chrono::Duration::hours(3 * (i % 3));
+                Bar::new(
+                    timestamp,
+                    4000.0,
+                    4001.0,
+                    3999.0,
+                    4000.0,
+                    1000.0,
+                    None,
+                    "ES".to_string(),
+                )
+                .unwrap()
+            })
+            .collect();
+        let config = BacktestConfig {
+            fee_settlement,
+            commission_per_contract: 2.5,
+            slippage_per_contract: 0.0,
+            ..BacktestConfig::default()
+        };
+        let mut engine = BacktestEngine::new(config, intraday, FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(
+            vec![
+                (1, OrderSide::Buy, 1),
+                (2, OrderSide::Sell, 1),
+                (4, OrderSide::Buy, 1),
+                (5, OrderSide::Sell, 1),
+            ],
+            false,
+        ));
+        let result = engine.run(&mut strategy);
+        result
+            .equity_curve
+            .iter()
+            .map(|point| point.equity)
+            .collect()
+    }
+
+    #[test]
+    fn daily_fee_settlement_steps_down_at_day_ends() {
+        //2.50 of commission per fill
+        assert_eq!(
+            fee_settled_equity(FeeSettlement::Immediate),
+            vec![100000.0, 99997.5, 99995.0, 99995.0, 99992.5, 99990.0]
+        );
+        assert_eq!(
+            fee_settled_equity(FeeSettlement::Daily),
+            vec![100000.0, 100000.0, 99995.0, 99995.0, 99995.0, 99990.0]
+        );
+    }
+
+    //long one es contract on 20000 of equity from 4020; the 500-point slide wipes it out
+    #[test]
+    fn blown_up_account_is_flattened_and_halted() {
+        let prices = [
+            (4000.0, 4005.0),
+            (4010.0, 4015.0),
+            (4020.0, 4000.0),
+            (3990.0, 3800.0),
+            (3700.0, 3520.0),
+            (3520.0, 3600.0),
+            (3600.0, 3700.0),
+        ];
+        let timestamps: Vec<_> = bars(&prices).iter().map(|bar| bar.timestamp).collect();
+        let config = BacktestConfig {
+            initial_balance: 20000.0,
+            ..BacktestConfig::default()
+        };
+        let mut engine = BacktestEngine::new(config, bars(&prices), FuturesContract::es("H24"));
+        //tries to buy again after the blow-up
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(
+            vec![(2, OrderSide::Buy, 1), (6, OrderSide::Buy, 1)],
+            false,
+        ));
+        let result = engine.run(&mut strategy);
+
+        assert!(result.bankrupt);
+        assert_eq!(result.halted_at, Some(timestamps[4]));
+        let fills = result.trades.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[1].exit_reason, Some(ExitReason::EquityFloor));
+        let scripted = strategy.downcast_ref::<Scripted>().unwrap();
+        assert_eq!(
+            scripted.rejections,
+            vec![(6, OrderRejection::TradingHalted)]
+        );
+        assert!(result
+            .equity_curve
+            .iter()
+            .all(|point| point.equity.is_finite()));
+        assert!(result.summary.total_return.is_finite());
+    }
+
+    //long one es contract from 4000; the 120-point drop is a 6% drawdown against a 5% stop
+    #[test]
+    fn drawdown_stop_flattens_and_rejects_later_orders() {
+        let prices = [
+            (4000.0, 4000.0),
+            (4000.0, 4000.0),
+            (3990.0, 3880.0),
+            (3880.0, 3900.0),
+            (3900.0, 3950.0),
+            (3950.0, 3950.0),
+        ];
+        let timestamps: Vec<_> = bars(&prices).iter().map(|bar| bar.timestamp).collect();
+        let config = BacktestConfig {
+            max_drawdown_stop: Some(0.05),
+            ..BacktestConfig::default()
+        };
+        let mut engine = BacktestEngine::new(config, bars(&prices), FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(
+            vec![(1, OrderSide::Buy, 1), (5, OrderSide::Buy, 1)],
+            false,
+        ));
+        let result = engine.run(&mut strategy);
+
+        assert!(!result.bankrupt);
+        assert_eq!(result.halted_at, Some(timestamps[2]));
+        let fills = result.trades.fills();
+        assert_eq!(fills.len(), 2);
+        assert_eq!(fills[1].exit_reason, Some(ExitReason::DrawdownStop));
+        assert_eq!(fills[1].timestamp, timestamps[3]);
+        assert_eq!(fills.iter().map(|fill| fill.qty).sum::<i32>(), 0);
+
+        let scripted = strategy.downcast_ref::<Scripted>().unwrap();
+        assert_eq!(
+            scripted.rejections,
+            vec![(5, OrderRejection::TradingHalted)]
+        );
+    }
+
+    //scaling in to three then out keeps the peak, not the final position, in both result and summary
+    #[test]
+    fn max_concurrent_contracts_records_the_peak() {
+        let prices = vec![(4000.0, 4000.0); 7];
+        let result = run_scripted(
+            vec![
+                (1, OrderSide::Buy, 2),
+                (2, OrderSide::Buy, 1),
+                (3, OrderSide::Sell, 2),
+                (5, OrderSide::Sell, 1),
+            ],
+            bars(&prices),
+        );
+
+        assert_eq!(result.max_concurrent_contracts, 3);
+        assert_eq!(result.summary.max_concurrent_contracts, 3);
+        assert_eq!(result.max_contracts_by_symbol.get("ES"), Some(&3));
+        assert_eq!(
+            SummaryMetrics::per_symbol(&result)["ES"].max_concurrent_contracts,
+            3
+        );
+    }
+
+    //the whole result, compared as json values so hash map order doesn't matter
+    #[test]
+    fn same_seed_and_config_give_identical_results() {
+        let prices: Vec<(f64, f64)> = (0..12)
+            .map(|i| (4000.0 + 7.0 * i as f64, 4003.0 + 5.0 * i as f64))
+            .collect();
+        let config = BacktestConfig {
+            seed: 42,
+            ..BacktestConfig::default()
+        };
+        let orders = vec![
+            (2, OrderSide::Buy, 2),
+            (5, OrderSide::Sell, 3),
+            (9, OrderSide::Buy, 1),
+        ];
+        let runs: Vec<_> = (0..2)
+            .map(|_| {
+                let mut engine =
+                    BacktestEngine::new(config.clone(), bars(&prices), FuturesContract::es("H24"));
+                let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(orders.clone(), true));
+                serde_json::to_value(engine.run(&mut strategy)).unwrap()
+            })
+            .collect();
+
+        assert_eq!(runs[0]["seed"], 42);
+        assert_eq!(runs[0], runs[1]);
+    }
+
+    //long from the second open, rolled at the fifth, with the strategy's orders on bars five
+    //and six; returns the peak contracts held
+    fn roll_then_enter(config: BacktestConfig, orders: Vec<(usize, OrderSide, u32)>) -> u32 {
+        let prices = vec![(4000.0, 4000.0); 9];
+        let roll_date = bars(&prices)[4].timestamp.date_naive();
+        let config = BacktestConfig {
+            roll_dates: vec![roll_date],
+            ..config
+        };
+        let mut engine = BacktestEngine::new(config, bars(&prices), FuturesContract::es("H24"));
+        let mut strategy: Box<dyn Strategy> = Box::new(Scripted::new(orders, true));
+        let result = engine.run(&mut strategy);
+        assert_eq!(result.rolls, 1);
+        result.max_concurrent_contracts
+    }
+
+    #[test]
+    fn roll_does_not_start_the_entry_cooldown() {
+        //the entry filled on bar two, so an add-on on bar six is clear of a three bar cooldown
+        let config = BacktestConfig {
+            min_bars_between_trades: Some(3),
+            ..BacktestConfig::default()
+        };
+        let orders = vec![(1, OrderSide::Buy, 1), (6, OrderSide::Buy, 1)];
+        assert_eq!(roll_then_enter(config, orders), 2);
+    }
+
+    #[test]
+    fn roll_does_not_reset_the_pyramid_count() {
+        //the add-on from bar three uses up the single add-on allowed, roll or not
+        let config = BacktestConfig {
+            max_pyramid_entries: Some(1),
+            ..BacktestConfig::default()
+        };
+        let orders = vec![
+            (1, OrderSide::Buy, 1),
+            (3, OrderSide::Buy, 1),
+            (6, OrderSide::Buy, 1),
+        ];
+        assert_eq!(roll_then_enter(config, orders), 2);
+    }
+}
@@ -1,5 +1,9 @@
+use crate::data::Bar;
 use chrono::{DateTime, Utc};
+use log::{debug, trace};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use thiserror::Error;
 
 //order side (buy or sell)
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -26,6 +30,213 @@ pub enum OrderType {
     Stop,
 }
 
+//how market orders are priced when filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ExecutionMode {
+    //fill at the bar price and charge the account's flat slippage
+    #[default]
+    Slippage,
+    //fill buys at the ask and sells at the bid when the bar carries quotes,
+    //falling back to slippage otherwise
+    SpreadFill,
+}
+
+impl ExecutionMode {
+    //parse execution mode from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "slippage" => Some(ExecutionMode::Slippage),
+            "spread" | "spread_fill" => Some(ExecutionMode::SpreadFill),
+            _ => None,
+        }
+    }
+}
+
+//when a resting limit order is considered filled
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum LimitFillMode {
+    //fill when the bar merely touches the limit price
+    //optimistic: at the exact touch there may have been no volume left at that price
+    #[default]
+    Touch,
+    //fill only when the bar trades through the limit price
+    Through,
+}
+
+impl LimitFillMode {
+    //parse limit fill mode from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "touch" => Some(LimitFillMode::Touch),
+            "through" => Some(LimitFillMode::Through),
+            _ => None,
+        }
+    }
+}
+
+//what happens to the part of a market order the participation cap kept from filling
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MarketTif {
+    //carry the remainder to the next bar's open
+    #[default]
+    FillOrCarry,
+    //cancel the remainder after the bar (ioc)
+    ImmediateOrCancel,
+}
+
+impl MarketTif {
+    //parse market time in force from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "carry" | "fill_or_carry" => Some(MarketTif::FillOrCarry),
+            "ioc" | "cancel" | "immediate_or_cancel" => Some(MarketTif::ImmediateOrCancel),
+            _ => None,
+        }
+    }
+}
+
+//what triggers a resting stop order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum StopTrigger {
+    //trigger when the bar trades through the stop, filling at the stop price
+    #[default]
+    Intrabar,
+    //trigger only when the bar closes beyond the stop, filling at the close
+    //ignores wicks through the stop; needs the bar's close (process_bar)
+    OnClose,
+}
+
+impl StopTrigger {
+    //parse stop trigger from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "intrabar" | "touch" => Some(StopTrigger::Intrabar),
+            "close" | "on_close" | "on-close" => Some(StopTrigger::OnClose),
+            _ => None,
+        }
+    }
+}
+
+//which bracket leg fills when one bar's range contains both the take-profit and the stop
+//without tick data the order in which the levels were hit is unknown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum BracketFillPolicy {
+    //assume the stop was hit first
+    #[default]
+    Pessimistic,
+    //assume the take-profit was hit first
+    Optimistic,
+    //split the quantity between the legs, favouring the level nearer the bar's open
+    ProRata,
+}
+
+impl BracketFillPolicy {
+    //parse bracket fill policy from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "pessimistic" => Some(BracketFillPolicy::Pessimistic),
+            "optimistic" => Some(BracketFillPolicy::Optimistic),
+            "prorata" | "pro_rata" | "pro-rata" => Some(BracketFillPolicy::ProRata),
+            _ => None,
+        }
+    }
+
+    //returns (take-profit qty, stop qty) for a bracket whose legs both trigger on one bar
+    fn split(&self, qty: u32, bar_open: f64, take_profit: f64, stop: f64) -> (u32, u32) {
+        match self {
+            BracketFillPolicy::Pessimistic => (0, qty),
+            BracketFillPolicy::Optimistic => (qty, 0),
+            BracketFillPolicy::ProRata => {
+                let to_target = (take_profit - bar_open).abs();
+                let to_stop = (stop - bar_open).abs();
+                if to_target + to_stop <= 0.0 {
+                    return (0, qty);
+                }
+
+                //the nearer level gets the larger share; exact halves round toward the stop
+                let target_share = to_stop / (to_target + to_stop);
+                let target_qty =
+                    ((qty as f64 * target_share - 0.5).ceil().max(0.0) as u32).min(qty);
+                (target_qty, qty - target_qty)
+            }
+        }
+    }
+}
+
+//why a position was reduced or closed, attached to exit orders and carried onto their fills
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ExitReason {
+    //the strategy's signal flipped to the other side
+    SignalFlip,
+    //the signal went neutral
+    NeutralZone,
+    //a profit target was reached
+    TakeProfit,
+    //a protective stop was hit
+    StopLoss,
+    //closed by the strategy at the end of the run
+    EndOfRun,
+    //flattened by the drawdown stop
+    DrawdownStop,
+    //flattened when equity fell to the equity floor (bankruptcy)
+    EquityFloor,
+    //closed to roll into the next contract
+    Roll,
+}
+
+//reason an order was rejected at submission
+#[derive(Error, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OrderRejection {
+    #[error("Order quantity must be greater than zero")]
+    ZeroQuantity,
+    #[error("Pyramiding limit reached: at most {max_entries} add-on entries allowed")]
+    PyramidLimit { max_entries: usize },
+    #[error("Trading halted by the drawdown stop or the equity floor")]
+    TradingHalted,
+    #[error("Position cap of {max_qty} contract(s) reached (volatility target)")]
+    VolatilityCap { max_qty: u32 },
+    #[error("Entry rejected: within {min_bars} bar(s) of the last fill")]
+    Cooldown { min_bars: usize },
+    #[error(
+        "{side:?} {order_type:?} at {price} is on the wrong side of the last price {reference}"
+    )]
+    InvertedPrice {
+        order_type: OrderType,
+        side: OrderSide,
+        price: f64,
+        reference: f64,
+    },
+    #[error("No bar yet to price the order from")]
+    NoReferencePrice,
+    #[error("No contract for symbol {symbol}")]
+    UnknownSymbol { symbol: String },
+}
+
+//what happens to a limit or stop submitted on the wrong side of the last price
+//(a buy limit above it, a sell limit below it, a buy stop below it, a sell stop above it)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PlacementCheck {
+    //accept without checking
+    Off,
+    //log a warning and accept
+    #[default]
+    Warn,
+    //reject with OrderRejection::InvertedPrice
+    Reject,
+}
+
+impl PlacementCheck {
+    //parse placement check from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "off" | "none" => Some(PlacementCheck::Off),
+            "warn" => Some(PlacementCheck::Warn),
+            "reject" | "strict" => Some(PlacementCheck::Reject),
+            _ => None,
+        }
+    }
+}
+
 //represents a trading order
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Order {
@@ -37,6 +248,18 @@ pub struct Order {
     pub order_type: OrderType,
     pub limit_price: Option<f64>,
     pub stop_price: Option<f64>,
+    //orders sharing a group are one-cancels-other (the legs of a bracket)
+    #[serde(default)]
+    pub oco_group: Option<u64>,
+    //set on orders that exit a position
+    #[serde(default)]
+    pub exit_reason: Option<ExitReason>,
+    //bars the order is held back before it can fill (signal-to-order latency)
+    #[serde(default)]
+    pub held_bars: u32,
+    //the fill may only shrink the opposite position, never flip or add to it
+    #[serde(default)]
+    pub reduce_only: bool,
 }
 
 impl Order {
@@ -57,6 +280,10 @@ impl Order {
             order_type: OrderType::Market,
             limit_price: None,
             stop_price: None,
+            oco_group: None,
+            exit_reason: None,
+            held_bars: 0,
+            reduce_only: false,
         }
     }
 
@@ -78,6 +305,10 @@ impl Order {
             order_type: OrderType::Limit,
             limit_price: Some(limit_price),
             stop_price: None,
+            oco_group: None,
+            exit_reason: None,
+            held_bars: 0,
+            reduce_only: false,
         }
     }
 
@@ -99,6 +330,10 @@ impl Order {
             order_type: OrderType::Stop,
             limit_price: None,
             stop_price: Some(stop_price),
+            oco_group: None,
+            exit_reason: None,
+            held_bars: 0,
+            reduce_only: false,
         }
     }
 
@@ -118,11 +353,24 @@ pub struct Fill {
     pub qty: i32, //signed: positive for long, negative for short
     pub side: OrderSide,
     pub fill_price: f64,
-    pub fees: f64, //total fees (commission + slippage)
+    #[serde(default, alias = "fees")]
+    pub commission: f64, //results saved before the split load their lumped fees here
+    #[serde(default)]
+    pub slippage: f64,
+    #[serde(default)]
+    pub filled_at_quote: bool, //true when priced off bid/ask, so no slippage is charged
+    #[serde(default)]
+    pub realized_pnl: f64, //pnl booked by the account when this fill reduced a position
+    #[serde(default)]
+    pub exit_reason: Option<ExitReason>, //copied from the order, if it was tagged as an exit
+    #[serde(default)]
+    pub reduce_only: bool, //copied from the order; the account clamps it to the opposite position
+    #[serde(default)]
+    pub risk_per_contract: Option<f64>, //initial risk of the position this fill reduced, if it had a stop
 }
 
 impl Fill {
-    pub fn from_order(fill_id: u64, order: &Order, fill_price: f64, fees: f64) -> Self {
+    pub fn from_order(fill_id: u64, order: &Order, fill_price: f64, commission: f64) -> Self {
         Fill {
             id: fill_id,
             order_id: order.id,
@@ -131,21 +379,187 @@ impl Fill {
             qty: order.signed_qty(),
             side: order.side,
             fill_price,
-            fees,
+            commission,
+            slippage: 0.0,
+            filled_at_quote: false,
+            realized_pnl: 0.0,
+            exit_reason: order.exit_reason,
+            reduce_only: order.reduce_only,
+            risk_per_contract: None,
         }
     }
 
+    //total fees (commission + slippage)
+    pub fn fees(&self) -> f64 {
+        self.commission + self.slippage
+    }
+
     //returns the notional value of the fill
     pub fn notional_value(&self, multiplier: f64) -> f64 {
         self.fill_price * multiplier * self.qty.abs() as f64
     }
 }
 
+//why a pending order didn't fill on a bar
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UnfilledReason {
+    //still held back by the signal delay
+    Held {
+        bars_left: u32,
+    },
+    //the bar didn't reach the limit (extreme is the bar low for buys, high for sells)
+    LimitNotReached {
+        side: OrderSide,
+        limit_price: f64,
+        extreme: f64,
+    },
+    //the bar didn't trade through the stop (extreme is the bar high for buys, low for sells)
+    StopNotTriggered {
+        side: OrderSide,
+        stop_price: f64,
+        extreme: f64,
+    },
+    //an on-close stop whose bar didn't close beyond it, however far the bar traded
+    StopNotClosedBeyond {
+        side: OrderSide,
+        stop_price: f64,
+        close: f64,
+    },
+    //the other leg of its one-cancels-other group filled
+    OcoSiblingFilled,
+    //a zero-size order, which never fills
+    ZeroQuantity,
+    //a limit or stop order without its price
+    MissingPrice,
+    //a market order cut by the participation cap, its remainder carried to the next bar
+    VolumeCapped {
+        remaining: u32,
+    },
+    //a market order cut by the participation cap, its remainder cancelled (ioc)
+    RemainderCancelled {
+        remaining: u32,
+    },
+}
+
+impl std::fmt::Display for UnfilledReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            UnfilledReason::Held { bars_left } => {
+                write!(f, "held back for {} more bar(s)", bars_left)
+            }
+            UnfilledReason::LimitNotReached {
+                side,
+                limit_price,
+                extreme,
+            } => {
+                let bar = if *side == OrderSide::Buy {
+                    "low"
+                } else {
+                    "high"
+                };
+                write!(
+                    f,
+                    "{:?} limit at {} not reached (bar {} {})",
+                    side, limit_price, bar, extreme
+                )
+            }
+            UnfilledReason::StopNotTriggered {
+                side,
+                stop_price,
+                extreme,
+            } => {
+                let bar = if *side == OrderSide::Buy {
+                    "high"
+                } else {
+                    "low"
+                };
+                write!(
+                    f,
+                    "{:?} stop at {} not triggered (bar {} {})",
+                    side, stop_price, bar, extreme
+                )
+            }
+            UnfilledReason::StopNotClosedBeyond {
+                side,
+                stop_price,
+                close,
+            } => write!(
+                f,
+                "{:?} stop at {} not triggered (bar close {})",
+                side, stop_price, close
+            ),
+            UnfilledReason::OcoSiblingFilled => write!(f, "cancelled, its oco sibling filled"),
+            UnfilledReason::ZeroQuantity => write!(f, "dropped, zero quantity"),
+            UnfilledReason::MissingPrice => write!(f, "dropped, no limit or stop price"),
+            UnfilledReason::VolumeCapped { remaining } => {
+                write!(f, "{} contract(s) over the volume cap, carried", remaining)
+            }
+            UnfilledReason::RemainderCancelled { remaining } => {
+                write!(
+                    f,
+                    "{} contract(s) over the volume cap, cancelled",
+                    remaining
+                )
+            }
+        }
+    }
+}
+
+//an order that didn't fill on a bar, and why
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnfilledOrder {
+    pub order_id: u64,
+    pub reason: UnfilledReason,
+}
+
+//everything that happened to the pending orders on one bar
+#[derive(Debug, Clone, Default)]
+pub struct ProcessResult {
+    pub fills: Vec<Fill>,
+    //orders left resting for a later bar
+    pub still_pending: Vec<UnfilledOrder>,
+    //orders dropped or cancelled on this bar without filling
+    pub skipped: Vec<UnfilledOrder>,
+}
+
+//counts of what happened to submitted orders, for judging fill quality
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct OrderStats {
+    pub submitted: usize,
+    pub filled: usize,
+    pub cancelled: usize,
+    //dropped without filling: invalid orders, or still resting when the run ended
+    pub expired: usize,
+    //sum over bars of orders that were pending but didn't fill on that bar
+    pub unfilled_order_bars: usize,
+}
+
+impl OrderStats {
+    //fraction of submitted orders that filled
+    pub fn fill_rate(&self) -> f64 {
+        if self.submitted == 0 {
+            0.0
+        } else {
+            self.filled as f64 / self.submitted as f64
+        }
+    }
+}
+
 //simulates order execution
 pub struct ExecutionEngine {
     next_order_id: u64,
     next_fill_id: u64,
     pending_orders: Vec<Order>,
+    execution_mode: ExecutionMode,
+    limit_fill_mode: LimitFillMode,
+    stop_trigger: StopTrigger,
+    bracket_fill_policy: BracketFillPolicy,
+    //largest fraction of a bar's volume market orders may fill on it (none = no cap)
+    max_participation: Option<f64>,
+    market_tif: MarketTif,
+    //adverse price offset applied to market fills that don't fill at a quote
+    market_slippage: f64,
+    order_stats: OrderStats,
 }
 
 impl ExecutionEngine {
@@ -154,11 +568,117 @@ impl ExecutionEngine {
             next_order_id: 1,
             next_fill_id: 1,
             pending_orders: Vec::new(),
+            execution_mode: ExecutionMode::default(),
+            limit_fill_mode: LimitFillMode::default(),
+            stop_trigger: StopTrigger::default(),
+            bracket_fill_policy: BracketFillPolicy::default(),
+            max_participation: None,
+            market_tif: MarketTif::default(),
+            market_slippage: 0.0,
+            order_stats: OrderStats::default(),
+        }
+    }
+
+    //creates an execution engine using the given execution mode
+    pub fn with_execution_mode(execution_mode: ExecutionMode) -> Self {
+        ExecutionEngine {
+            execution_mode,
+            ..Self::new()
         }
     }
 
+    //returns the execution mode
+    pub fn execution_mode(&self) -> ExecutionMode {
+        self.execution_mode
+    }
+
+    //sets when limit orders are considered filled
+    pub fn set_limit_fill_mode(&mut self, limit_fill_mode: LimitFillMode) {
+        self.limit_fill_mode = limit_fill_mode;
+    }
+
+    //returns the limit fill mode
+    pub fn limit_fill_mode(&self) -> LimitFillMode {
+        self.limit_fill_mode
+    }
+
+    //sets what triggers resting stop orders
+    pub fn set_stop_trigger(&mut self, stop_trigger: StopTrigger) {
+        self.stop_trigger = stop_trigger;
+    }
+
+    //returns the stop trigger
+    pub fn stop_trigger(&self) -> StopTrigger {
+        self.stop_trigger
+    }
+
+    //caps the contracts market orders fill on a bar at this fraction of its volume
+    //only process_bar sees the volume; the other process calls and signal-close fills don't cap
+    pub fn set_max_participation(&mut self, max_participation: Option<f64>) {
+        self.max_participation = max_participation;
+    }
+
+    pub fn max_participation(&self) -> Option<f64> {
+        self.max_participation
+    }
+
+    //sets whether the capped remainder of a market order is carried or cancelled
+    pub fn set_market_tif(&mut self, market_tif: MarketTif) {
+        self.market_tif = market_tif;
+    }
+
+    pub fn market_tif(&self) -> MarketTif {
+        self.market_tif
+    }
+
+    //sets which bracket leg fills when both trigger on the same bar
+    pub fn set_bracket_fill_policy(&mut self, bracket_fill_policy: BracketFillPolicy) {
+        self.bracket_fill_policy = bracket_fill_policy;
+    }
+
+    //returns the bracket fill policy
+    pub fn bracket_fill_policy(&self) -> BracketFillPolicy {
+        self.bracket_fill_policy
+    }
+
+    //moves market fills ticks * tick_size against the order (buys higher, sells lower)
+    //quote fills are left alone, as they already paid the spread
+    pub fn set_market_slippage_ticks(&mut self, ticks: u32, tick_size: f64) {
+        self.market_slippage = ticks as f64 * tick_size;
+    }
+
+    //returns the market fill price after the adverse tick offset
+    fn market_fill_price(&self, price: f64, side: OrderSide) -> f64 {
+        price + side.to_qty_sign() as f64 * self.market_slippage
+    }
+
+    //places order and fill ids in their own namespace (upper 32 bits)
+    //engines running side by side (eg one per symbol) get distinct namespaces so ids
+    //stay unique and deterministic when results are merged
+    //call before any orders are submitted, as it resets the id counters
+    pub fn set_id_namespace(&mut self, namespace: u32) {
+        let base = (namespace as u64) << 32;
+        self.next_order_id = base + 1;
+        self.next_fill_id = base + 1;
+    }
+
+    //returns the namespace an order or fill id was generated in
+    pub fn id_namespace(id: u64) -> u32 {
+        (id >> 32) as u32
+    }
+
+    //returns counts of submitted, filled, cancelled and expired orders
+    pub fn order_stats(&self) -> OrderStats {
+        self.order_stats
+    }
+
     //submits an order and returns its ID
     pub fn submit_order(&mut self, order: Order) -> u64 {
+        self.order_stats.submitted += 1;
+        debug!(
+            "order {} submitted: {:?} {:?} {} {} at {}",
+            order.id, order.side, order.order_type, order.qty, order.symbol, order.timestamp
+        );
         let id = order.id;
         self.pending_orders.push(order);
         id
@@ -198,67 +718,530 @@ impl ExecutionEngine {
         self.submit_order(order)
     }
 
+    //creates and submits a stop order
+    pub fn stop_order(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+        stop_price: f64,
+    ) -> u64 {
+        let order = Order::stop(self.next_order_id, timestamp, symbol, qty, side, stop_price);
+        self.next_order_id += 1;
+        self.submit_order(order)
+    }
+
+    //creates and submits a bracket exit: a take-profit limit and a protective stop on the same
+    //side, one-cancels-other; returns the (take-profit, stop) order ids
+    pub fn bracket_order(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+        take_profit: f64,
+        stop_price: f64,
+    ) -> (u64, u64) {
+        let group = self.next_order_id;
+
+        let mut target = Order::limit(
+            self.next_order_id,
+            timestamp,
+            symbol.clone(),
+            qty,
+            side,
+            take_profit,
+        );
+        target.oco_group = Some(group);
+        target.exit_reason = Some(ExitReason::TakeProfit);
+        self.next_order_id += 1;
+
+        let mut stop = Order::stop(self.next_order_id, timestamp, symbol, qty, side, stop_price);
+        stop.oco_group = Some(group);
+        stop.exit_reason = Some(ExitReason::StopLoss);
+        self.next_order_id += 1;
+
+        (self.submit_order(target), self.submit_order(stop))
+    }
+
+    //fills pending market orders at the given price (eg the signal bar's close), stamped with
+    //the given time (that bar's timestamp)
+    //limit and stop orders, and orders still held back, are left resting
+    pub fn fill_market_orders_at(&mut self, price: f64, timestamp: DateTime<Utc>) -> Vec<Fill> {
+        let mut fills = Vec::new();
+        let mut orders_to_keep = Vec::new();
+
+        let pending: Vec<Order> = self.pending_orders.drain(..).collect();
+        for order in pending {
+            if order.order_type != OrderType::Market || order.held_bars > 0 {
+                orders_to_keep.push(order);
+            } else if order.qty == 0 {
+                self.order_stats.expired += 1;
+            } else {
+                let fill_price = self.market_fill_price(price, order.side);
+                let mut fill = Fill::from_order(self.next_fill_id, &order, fill_price, 0.0);
+                fill.timestamp = timestamp;
+                fills.push(fill);
+                self.next_fill_id += 1;
+            }
+        }
+
+        for fill in &fills {
+            debug!(
+                "order {} filled at signal close: {} {} @ {}",
+                fill.order_id, fill.qty, fill.symbol, fill.fill_price
+            );
+        }
+
+        self.order_stats.filled += fills.len();
+        self.pending_orders = orders_to_keep;
+        fills
+    }
+
+    //holds a pending order back for the given number of bars before it can fill
+    //returns false if the order is no longer pending
+    pub fn delay_order(&mut self, order_id: u64, bars: u32) -> bool {
+        match self.pending_orders.iter_mut().find(|o| o.id == order_id) {
+            Some(order) => {
+                order.held_bars = bars;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //releases every held order so it can fill immediately (eg at the end of the run)
+    pub fn release_held_orders(&mut self) {
+        for order in &mut self.pending_orders {
+            order.held_bars = 0;
+        }
+    }
+
+    //tags a pending order as an exit for the given reason
+    //returns false if the order is no longer pending
+    pub fn tag_order(&mut self, order_id: u64, reason: ExitReason) -> bool {
+        match self.pending_orders.iter_mut().find(|o| o.id == order_id) {
+            Some(order) => {
+                order.exit_reason = Some(reason);
+                true
+            }
+            None => false,
+        }
+    }
+
+    //marks a pending order as reduce-only
+    //returns false if the order is no longer pending
+    pub fn set_reduce_only(&mut self, order_id: u64) -> bool {
+        match self.pending_orders.iter_mut().find(|o| o.id == order_id) {
+            Some(order) => {
+                order.reduce_only = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //returns the price a resting limit or stop order fills at on this bar, if it triggers
+    //without the bar's close, stops trigger intrabar whatever the stop trigger
+    fn trigger_price(
+        &self,
+        order: &Order,
+        bar_high: f64,
+        bar_low: f64,
+        bar_close: Option<f64>,
+    ) -> Option<f64> {
+        match order.order_type {
+            OrderType::Market => None,
+            //touch: limit buy fills if low <= limit_price, sell if high >= limit_price
+            //through: the bar must trade strictly beyond the limit price
+            OrderType::Limit => {
+                let limit_price = order.limit_price?;
+                let filled = match (order.side, self.limit_fill_mode) {
+                    (OrderSide::Buy, LimitFillMode::Touch) => bar_low <= limit_price,
+                    (OrderSide::Buy, LimitFillMode::Through) => bar_low < limit_price,
+                    (OrderSide::Sell, LimitFillMode::Touch) => bar_high >= limit_price,
+                    (OrderSide::Sell, LimitFillMode::Through) => bar_high > limit_price,
+                };
+                filled.then_some(limit_price)
+            }
+            //stop buy triggers if high >= stop_price
+            //stop sell triggers if low <= stop_price
+            //once triggered, fills at stop price
+            //on close, the close must be beyond the stop, and fills there
+            OrderType::Stop => {
+                let stop_price = order.stop_price?;
+                if let (StopTrigger::OnClose, Some(close)) = (self.stop_trigger, bar_close) {
+                    let triggered = match order.side {
+                        OrderSide::Buy => close >= stop_price,
+                        OrderSide::Sell => close <= stop_price,
+                    };
+                    return triggered.then_some(close);
+                }
+                let triggered = match order.side {
+                    OrderSide::Buy => bar_high >= stop_price,
+                    OrderSide::Sell => bar_low <= stop_price,
+                };
+                triggered.then_some(stop_price)
+            }
+        }
+    }
+
+    //resolves bracket groups whose take-profit and stop both trigger on this bar
+    //returns the quantity each contested order fills for under the bracket fill policy
+    fn contested_bracket_qtys(
+        &self,
+        bar_open: f64,
+        bar_high: f64,
+        bar_low: f64,
+        bar_close: Option<f64>,
+    ) -> HashMap<u64, u32> {
+        //(order id, qty, trigger price) of the take-profit and stop legs per group
+        type Leg = (u64, u32, f64);
+        let mut legs: HashMap<u64, (Option<Leg>, Option<Leg>)> = HashMap::new();
+        for order in self.pending_orders.iter().filter(|o| o.held_bars == 0) {
+            let (Some(group), Some(price)) = (
+                order.oco_group,
+                self.trigger_price(order, bar_high, bar_low, bar_close),
+            ) else {
+                continue;
+            };
+            let entry = legs.entry(group).or_default();
+            match order.order_type {
+                OrderType::Limit => entry.0 = Some((order.id, order.qty, price)),
+                OrderType::Stop => entry.1 = Some((order.id, order.qty, price)),
+                OrderType::Market => {}
+            }
+        }
+
+        let mut qtys = HashMap::new();
+        for (target, stop) in legs.into_values() {
+            if let (
+                Some((target_id, target_qty, target_price)),
+                Some((stop_id, stop_qty, stop_price)),
+            ) = (target, stop)
+            {
+                let (to_target, to_stop) = self.bracket_fill_policy.split(
+                    target_qty.min(stop_qty),
+                    bar_open,
+                    target_price,
+                    stop_price,
+                );
+                qtys.insert(target_id, to_target);
+                qtys.insert(stop_id, to_stop);
+            }
+        }
+        qtys
+    }
+
     //processes pending orders against current bar and returns fills
     //market orders fill at the open of the next bar
     //limit orders fill if price crosses the limit during the bar
     pub fn process_orders(&mut self, bar_open: f64, bar_high: f64, bar_low: f64) -> Vec<Fill> {
+        self.process_orders_with_quotes(bar_open, bar_high, bar_low, None, None)
+    }
+
+    //processes pending orders like process_orders, using the bar's bid/ask
+    //for market fills when running in spreadfill mode
+    pub fn process_orders_with_quotes(
+        &mut self,
+        bar_open: f64,
+        bar_high: f64,
+        bar_low: f64,
+        bid: Option<f64>,
+        ask: Option<f64>,
+    ) -> Vec<Fill> {
+        self.process_orders_detailed(bar_open, bar_high, bar_low, bid, ask)
+            .fills
+    }
+
+    //processes pending orders against the full bar: like process_orders_with_quotes, and
+    //stops can be confirmed on the bar's close (StopTrigger::OnClose)
+    pub fn process_bar(&mut self, bar: &Bar) -> Vec<Fill> {
+        self.process_bar_detailed(bar).fills
+    }
+
+    //processes pending orders like process_bar, also reporting why each order that didn't
+    //fill is still resting or was dropped
+    //fills are stamped with the bar's timestamp, not the (earlier) submission time
+    pub fn process_bar_detailed(&mut self, bar: &Bar) -> ProcessResult {
+        let mut result = self.process_pending(
+            bar.open,
+            bar.high,
+            bar.low,
+            Some(bar.close),
+            Some(bar.volume),
+            bar.bid,
+            bar.ask,
+        );
+        for fill in &mut result.fills {
+            fill.timestamp = bar.timestamp;
+        }
+        result
+    }
+
+    //processes pending orders like process_orders_with_quotes, also reporting why each order
+    //that didn't fill is still resting or was dropped
+    pub fn process_orders_detailed(
+        &mut self,
+        bar_open: f64,
+        bar_high: f64,
+        bar_low: f64,
+        bid: Option<f64>,
+        ask: Option<f64>,
+    ) -> ProcessResult {
+        self.process_pending(bar_open, bar_high, bar_low, None, None, bid, ask)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_pending(
+        &mut self,
+        bar_open: f64,
+        bar_high: f64,
+        bar_low: f64,
+        bar_close: Option<f64>,
+        bar_volume: Option<f64>,
+        bid: Option<f64>,
+        ask: Option<f64>,
+    ) -> ProcessResult {
+        //contracts market orders may still fill on this bar under the participation cap
+        let mut volume_left = match (self.max_participation, bar_volume) {
+            (Some(fraction), Some(volume)) => Some((volume * fraction).floor().max(0.0) as u32),
+            _ => None,
+        };
+        //fills of market orders whose remainder carries on, not yet counted as filled orders
+        let mut partial_fills = 0;
         let mut fills = Vec::new();
-        let mut orders_to_keep = Vec::new();
+        let mut skipped = Vec::new();
+        let mut orders_to_keep: Vec<(Order, UnfilledReason)> = Vec::new();
+        let contested = self.contested_bracket_qtys(bar_open, bar_high, bar_low, bar_close);
+        let mut filled_groups = HashSet::new();
+
+        let pending: Vec<Order> = self.pending_orders.drain(..).collect();
+        for mut order in pending {
+            //zero-size orders never fill
+            if order.qty == 0 {
+                self.order_stats.expired += 1;
+                skipped.push(UnfilledOrder {
+                    order_id: order.id,
+                    reason: UnfilledReason::ZeroQuantity,
+                });
+                continue;
+            }
+
+            //still held back: count down and wait for a later bar
+            if order.held_bars > 0 {
+                order.held_bars -= 1;
+                let reason = UnfilledReason::Held {
+                    bars_left: order.held_bars,
+                };
+                orders_to_keep.push((order, reason));
+                continue;
+            }
+
+            //both bracket legs triggered: the policy decides how much each leg fills
+            if let Some(&qty) = contested.get(&order.id) {
+                if let (Some(group), Some(price)) = (
+                    order.oco_group,
+                    self.trigger_price(&order, bar_high, bar_low, bar_close),
+                ) {
+                    filled_groups.insert(group);
+                    if qty == 0 {
+                        self.order_stats.cancelled += 1;
+                        skipped.push(UnfilledOrder {
+                            order_id: order.id,
+                            reason: UnfilledReason::OcoSiblingFilled,
+                        });
+                    } else {
+                        order.qty = qty;
+                        fills.push(Fill::from_order(self.next_fill_id, &order, price, 0.0));
+                        self.next_fill_id += 1;
+                    }
+                }
+                continue;
+            }
+
+            //the other leg of this bracket already filled
+            if order
+                .oco_group
+                .is_some_and(|group| filled_groups.contains(&group))
+            {
+                self.order_stats.cancelled += 1;
+                skipped.push(UnfilledOrder {
+                    order_id: order.id,
+                    reason: UnfilledReason::OcoSiblingFilled,
+                });
+                continue;
+            }
 
-        for order in self.pending_orders.drain(..) {
             match order.order_type {
                 OrderType::Market => {
-                    //market orders fill at bar open
-                    let fill = Fill::from_order(self.next_fill_id, &order, bar_open, 0.0);
-                    self.next_fill_id += 1;
-                    fills.push(fill);
-                }
-                OrderType::Limit => {
-                    //limit buy fills if low <= limit_price
-                    //limit sell fills if high >= limit_price
-                    if let Some(limit_price) = order.limit_price {
-                        let filled = match order.side {
-                            OrderSide::Buy => bar_low <= limit_price,
-                            OrderSide::Sell => bar_high >= limit_price,
+                    let fill_qty = volume_left.map_or(order.qty, |left| order.qty.min(left));
+
+                    if fill_qty > 0 {
+                        //buys lift the ask, sells hit the bid
+                        let quote = match (self.execution_mode, order.side) {
+                            (ExecutionMode::SpreadFill, OrderSide::Buy) => ask,
+                            (ExecutionMode::SpreadFill, OrderSide::Sell) => bid,
+                            (ExecutionMode::Slippage, _) => None,
                         };
 
-                        if filled {
-                            let fill =
-                                Fill::from_order(self.next_fill_id, &order, limit_price, 0.0);
-                            self.next_fill_id += 1;
-                            fills.push(fill);
-                        } else {
-                            //keep for next bar
-                            orders_to_keep.push(order);
+                        //otherwise market orders fill at bar open, less the tick slippage
+                        let price = match quote {
+                            Some(quote) => quote,
+                            None => self.market_fill_price(bar_open, order.side),
+                        };
+                        let filled = Order {
+                            qty: fill_qty,
+                            ..order.clone()
+                        };
+                        let mut fill = Fill::from_order(self.next_fill_id, &filled, price, 0.0);
+                        fill.filled_at_quote = quote.is_some();
+                        self.next_fill_id += 1;
+                        fills.push(fill);
+                        if let Some(left) = volume_left.as_mut() {
+                            *left -= fill_qty;
+                        }
+                    }
+
+                    //the part over the participation cap
+                    let remaining = order.qty - fill_qty;
+                    if remaining > 0 {
+                        order.qty = remaining;
+                        match self.market_tif {
+                            MarketTif::FillOrCarry => {
+                                if fill_qty > 0 {
+                                    partial_fills += 1;
+                                }
+                                orders_to_keep
+                                    .push((order, UnfilledReason::VolumeCapped { remaining }));
+                            }
+                            MarketTif::ImmediateOrCancel => {
+                                //a partly filled order counts as filled, an unfilled one as cancelled
+                                if fill_qty == 0 {
+                                    self.order_stats.cancelled += 1;
+                                }
+                                skipped.push(UnfilledOrder {
+                                    order_id: order.id,
+                                    reason: UnfilledReason::RemainderCancelled { remaining },
+                                });
+                            }
                         }
                     }
                 }
-                OrderType::Stop => {
-                    //stop buy triggers if high >= stop_price
-                    //stop sell triggers if low <= stop_price
-                    if let Some(stop_price) = order.stop_price {
-                        let triggered = match order.side {
-                            OrderSide::Buy => bar_high >= stop_price,
-                            OrderSide::Sell => bar_low <= stop_price,
-                        };
+                OrderType::Limit | OrderType::Stop => {
+                    //orders missing their price can never fill
+                    let price_missing = match order.order_type {
+                        OrderType::Limit => order.limit_price.is_none(),
+                        _ => order.stop_price.is_none(),
+                    };
+                    if price_missing {
+                        self.order_stats.expired += 1;
+                        skipped.push(UnfilledOrder {
+                            order_id: order.id,
+                            reason: UnfilledReason::MissingPrice,
+                        });
+                        continue;
+                    }
 
-                        if triggered {
-                            //once triggered, fills at stop price
-                            let fill =
-                                Fill::from_order(self.next_fill_id, &order, stop_price, 0.0);
+                    match self.trigger_price(&order, bar_high, bar_low, bar_close) {
+                        Some(price) => {
+                            if let Some(group) = order.oco_group {
+                                filled_groups.insert(group);
+                            }
+                            fills.push(Fill::from_order(self.next_fill_id, &order, price, 0.0));
                             self.next_fill_id += 1;
-                            fills.push(fill);
-                        } else {
-                            //keep for next bar
-                            orders_to_keep.push(order);
+                        }
+                        //keep for next bar
+                        None => {
+                            let reason =
+                                self.not_triggered_reason(&order, bar_high, bar_low, bar_close);
+                            orders_to_keep.push((order, reason));
                         }
                     }
                 }
             }
         }
 
-        self.pending_orders = orders_to_keep;
-        fills
+        //drop resting legs whose sibling filled later in this bar
+        let (cancelled, orders_to_keep): (Vec<_>, Vec<_>) =
+            orders_to_keep.into_iter().partition(|(order, _)| {
+                order
+                    .oco_group
+                    .is_some_and(|group| filled_groups.contains(&group))
+            });
+        self.order_stats.cancelled += cancelled.len();
+        skipped.extend(cancelled.into_iter().map(|(order, _)| UnfilledOrder {
+            order_id: order.id,
+            reason: UnfilledReason::OcoSiblingFilled,
+        }));
+
+        for fill in &fills {
+            debug!(
+                "order {} filled: {} {} @ {}",
+                fill.order_id, fill.qty, fill.symbol, fill.fill_price
+            );
+        }
+        let still_pending: Vec<UnfilledOrder> = orders_to_keep
+            .iter()
+            .map(|(order, reason)| UnfilledOrder {
+                order_id: order.id,
+                reason: *reason,
+            })
+            .collect();
+        for unfilled in still_pending.iter().chain(&skipped) {
+            trace!(
+                "order {} not filled: {}",
+                unfilled.order_id,
+                unfilled.reason
+            );
+        }
+
+        self.order_stats.filled += fills.len() - partial_fills;
+        self.order_stats.unfilled_order_bars += orders_to_keep.len();
+        self.pending_orders = orders_to_keep.into_iter().map(|(order, _)| order).collect();
+        ProcessResult {
+            fills,
+            still_pending,
+            skipped,
+        }
+    }
+
+    //describes why a resting limit or stop order didn't trigger on the bar
+    fn not_triggered_reason(
+        &self,
+        order: &Order,
+        bar_high: f64,
+        bar_low: f64,
+        bar_close: Option<f64>,
+    ) -> UnfilledReason {
+        if let (OrderType::Stop, StopTrigger::OnClose, Some(close)) =
+            (order.order_type, self.stop_trigger, bar_close)
+        {
+            return UnfilledReason::StopNotClosedBeyond {
+                side: order.side,
+                stop_price: order.stop_price.unwrap_or_default(),
+                close,
+            };
+        }
+        let extreme = match order.side {
+            OrderSide::Buy if order.order_type == OrderType::Limit => bar_low,
+            OrderSide::Buy => bar_high,
+            OrderSide::Sell if order.order_type == OrderType::Limit => bar_high,
+            OrderSide::Sell => bar_low,
+        };
+        match order.order_type {
+            OrderType::Stop => UnfilledReason::StopNotTriggered {
+                side: order.side,
+                stop_price: order.stop_price.unwrap_or_default(),
+                extreme,
+            },
+            _ => UnfilledReason::LimitNotReached {
+                side: order.side,
+                limit_price: order.limit_price.unwrap_or_default(),
+                extreme,
+            },
+        }
     }
 
     //returns the number of pending orders
@@ -266,8 +1249,40 @@ impl ExecutionEngine {
         self.pending_orders.len()
     }
 
+    //returns the orders waiting to be filled
+    pub fn pending_orders(&self) -> &[Order] {
+        &self.pending_orders
+    }
+
+    //cancels a single pending order
+    //returns false if the order is no longer pending
+    pub fn cancel_order(&mut self, order_id: u64) -> bool {
+        match self.pending_orders.iter().position(|o| o.id == order_id) {
+            Some(index) => {
+                self.pending_orders.remove(index);
+                self.order_stats.cancelled += 1;
+                debug!("order {} cancelled", order_id);
+                true
+            }
+            None => false,
+        }
+    }
+
     //cancels all pending orders
     pub fn cancel_all_orders(&mut self) {
+        if !self.pending_orders.is_empty() {
+            debug!("cancelling {} pending order(s)", self.pending_orders.len());
+        }
+        self.order_stats.cancelled += self.pending_orders.len();
+        self.pending_orders.clear();
+    }
+
+    //drops all pending orders as expired (eg orders still resting when the run ends)
+    pub fn expire_all_orders(&mut self) {
+        if !self.pending_orders.is_empty() {
+            debug!("expiring {} pending order(s)", self.pending_orders.len());
+        }
+        self.order_stats.expired += self.pending_orders.len();
         self.pending_orders.clear();
     }
 }
@@ -277,3 +1292,273 @@ impl Default for ExecutionEngine {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn bar(open: f64, high: f64, low: f64, close: f64) -> Bar {
+        Bar::new(
+            Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            open,
+            high,
+            low,
+            close,
+            1000.0,
+            None,
+            "ES".to_string(),
+        )
+        .unwrap()
+    }
+
+    fn market(execution: &mut ExecutionEngine, side: OrderSide, qty: u32) -> u64 {
+        execution.market_order(Utc::now(), "ES".to_string(), qty, side)
+    }
+
+    #[test]
+    fn spread_fill_buys_at_the_ask_and_sells_at_the_bid() {
+        let mut execution = ExecutionEngine::with_execution_mode(ExecutionMode::SpreadFill);
+        market(&mut execution, OrderSide::Buy, 1);
+        market(&mut execution, OrderSide::Sell, 1);
+
+        let quoted = bar(4000.0, 4010.0, 3990.0, 4005.0).with_quotes(Some(3999.75), Some(4000.25));
+        let fills = execution.process_bar(&quoted);
+
+        assert_eq!(fills[0].fill_price, 4000.25);
+        assert_eq!(fills[1].fill_price, 3999.75);
+        assert!(fills.iter().all(|fill| fill.filled_at_quote));
+    }
+
+    #[test]
+    fn spread_fill_falls_back_to_the_open_without_quotes() {
+        let mut execution = ExecutionEngine::with_execution_mode(ExecutionMode::SpreadFill);
+        market(&mut execution, OrderSide::Buy, 1);
+
+        let fills = execution.process_bar(&bar(4000.0, 4010.0, 3990.0, 4005.0));
+
+        assert_eq!(fills[0].fill_price, 4000.0);
+        assert!(!fills[0].filled_at_quote);
+    }
+
+    fn limit_fills_on_exact_low(mode: LimitFillMode) -> usize {
+        let mut execution = ExecutionEngine::new();
+        execution.set_limit_fill_mode(mode);
+        execution.limit_order(Utc::now(), "ES".to_string(), 1, OrderSide::Buy, 3990.0);
+        execution
+            .process_bar(&bar(4000.0, 4010.0, 3990.0, 4005.0))
+            .len()
+    }
+
+    #[test]
+    fn touch_fills_a_limit_at_the_exact_low() {
+        assert_eq!(limit_fills_on_exact_low(LimitFillMode::Touch), 1);
+    }
+
+    #[test]
+    fn through_needs_the_bar_to_trade_past_the_limit() {
+        assert_eq!(limit_fills_on_exact_low(LimitFillMode::Through), 0);
+
+        let mut execution = ExecutionEngine::new();
+        execution.set_limit_fill_mode(LimitFillMode::Through);
+        execution.limit_order(Utc::now(), "ES".to_string(), 1, OrderSide::Buy, 3990.0);
+        let fills = execution.process_bar(&bar(4000.0, 4010.0, 3989.75, 4005.0));
+        assert_eq!(fills[0].fill_price, 3990.0);
+    }
+
+    fn limit(execution: &mut ExecutionEngine, price: f64) -> u64 {
+        execution.limit_order(Utc::now(), "ES".to_string(), 1, OrderSide::Buy, price)
+    }
+
+    #[test]
+    fn order_stats_count_each_outcome() {
+        let mut execution = ExecutionEngine::new();
+        market(&mut execution, OrderSide::Buy, 1);
+        let resting = limit(&mut execution, 3900.0);
+        let pulled = limit(&mut execution, 3950.0);
+        limit(&mut execution, 3800.0);
+        assert!(execution.cancel_order(pulled));
+
+        //the market order fills; both remaining limits rest below the low for two bars
+        for _ in 0..2 {
+            execution.process_bar(&bar(4000.0, 4010.0, 3990.0, 4005.0));
+        }
+        assert!(execution.cancel_order(resting));
+        execution.expire_all_orders();
+
+        let stats = execution.order_stats();
+        assert_eq!(stats.submitted, 4);
+        assert_eq!(stats.filled, 1);
+        assert_eq!(stats.cancelled, 2);
+        assert_eq!(stats.expired, 1);
+        assert_eq!(stats.unfilled_order_bars, 4);
+        assert_eq!(stats.fill_rate(), 0.25);
+    }
+
+    //(fill price, qty) of a 4-lot long's bracket exit on a bar that reaches both legs
+    fn straddled_bracket(policy: BracketFillPolicy) -> Vec<(f64, u32)> {
+        let mut execution = ExecutionEngine::new();
+        execution.set_bracket_fill_policy(policy);
+        execution.bracket_order(
+            Utc::now(),
+            "ES".to_string(),
+            4,
+            OrderSide::Sell,
+            4020.0,
+            3990.0,
+        );
+
+        let fills = execution.process_bar(&bar(4000.0, 4025.0, 3985.0, 4005.0));
+        assert_eq!(execution.pending_order_count(), 0);
+        fills
+            .iter()
+            .map(|fill| (fill.fill_price, fill.qty.unsigned_abs()))
+            .collect()
+    }
+
+    #[test]
+    fn pessimistic_bracket_fills_the_stop() {
+        assert_eq!(
+            straddled_bracket(BracketFillPolicy::Pessimistic),
+            vec![(3990.0, 4)]
+        );
+    }
+
+    #[test]
+    fn optimistic_bracket_fills_the_take_profit() {
+        assert_eq!(
+            straddled_bracket(BracketFillPolicy::Optimistic),
+            vec![(4020.0, 4)]
+        );
+    }
+
+    //the stop is 10 points from the open and the target 20, so the stop gets two thirds
+    #[test]
+    fn pro_rata_bracket_splits_toward_the_nearer_leg() {
+        let mut fills = straddled_bracket(BracketFillPolicy::ProRata);
+        fills.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        assert_eq!(fills, vec![(3990.0, 3), (4020.0, 1)]);
+    }
+
+    #[test]
+    fn market_slippage_ticks_move_fills_against_the_order() {
+        let mut execution = ExecutionEngine::new();
+        execution.set_market_slippage_ticks(2, 0.25);
+        market(&mut execution, OrderSide::Buy, 1);
+        market(&mut execution, OrderSide::Sell, 1);
+        limit(&mut execution, 4000.0);
+
+        let fills = execution.process_bar(&bar(4000.0, 4010.0, 3990.0, 4005.0));
+
+        assert_eq!(fills[0].fill_price, 4000.5);
+        assert_eq!(fills[1].fill_price, 3999.5);
+        //limits fill at their price, with no tick offset
+        assert_eq!(fills[2].fill_price, 4000.0);
+    }
+
+    #[test]
+    fn market_slippage_ticks_leave_quote_fills_alone() {
+        let mut execution = ExecutionEngine::with_execution_mode(ExecutionMode::SpreadFill);
+        execution.set_market_slippage_ticks(2, 0.25);
+        market(&mut execution, OrderSide::Buy, 1);
+
+        let quoted = bar(4000.0, 4010.0, 3990.0, 4005.0).with_quotes(Some(3999.75), Some(4000.25));
+        let fills = execution.process_bar(&quoted);
+
+        assert_eq!(fills[0].fill_price, 4000.25);
+    }
+
+    #[test]
+    fn fill_saved_with_lumped_fees_loads_them_as_commission() {
+        let order = Order::market(1, Utc::now(), "ES".to_string(), 1, OrderSide::Buy);
+        let mut saved = serde_json::to_value(Fill::from_order(1, &order, 4000.0, 0.0)).unwrap();
+        let fields = saved.as_object_mut().unwrap();
+        fields.remove("commission");
+        fields.remove("slippage");
+        fields.insert("fees".to_string(), serde_json::json!(3.5));
+
+        let fill: Fill = serde_json::from_value(saved).unwrap();
+        assert_eq!((fill.commission, fill.slippage), (3.5, 0.0));
+        assert_eq!(fill.fees(), 3.5);
+    }
+
+    fn sell_stop_on_close() -> ExecutionEngine {
+        let mut execution = ExecutionEngine::new();
+        execution.set_stop_trigger(StopTrigger::OnClose);
+        execution.stop_order(Utc::now(), "ES".to_string(), 1, OrderSide::Sell, 3990.0);
+        execution
+    }
+
+    #[test]
+    fn on_close_stop_ignores_a_wick_through_the_stop() {
+        let mut execution = sell_stop_on_close();
+        let result = execution.process_bar_detailed(&bar(4000.0, 4005.0, 3980.0, 3995.0));
+
+        assert!(result.fills.is_empty());
+        assert!(matches!(
+            result.still_pending[0].reason,
+            UnfilledReason::StopNotClosedBeyond { close, .. } if close == 3995.0
+        ));
+    }
+
+    #[test]
+    fn on_close_stop_fills_at_a_close_beyond_the_stop() {
+        let mut execution = sell_stop_on_close();
+        let fills = execution.process_bar(&bar(4000.0, 4005.0, 3980.0, 3985.0));
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].fill_price, 3985.0);
+    }
+
+    #[test]
+    fn intrabar_stop_fills_on_the_wick_at_the_stop() {
+        let mut execution = ExecutionEngine::new();
+        execution.stop_order(Utc::now(), "ES".to_string(), 1, OrderSide::Sell, 3990.0);
+        let fills = execution.process_bar(&bar(4000.0, 4005.0, 3980.0, 3995.0));
+
+        assert_eq!(fills.len(), 1);
+        assert_eq!(fills[0].fill_price, 3990.0);
+    }
+
+    //a 1% participation cap lets 10 contracts a bar fill against the helper bar's 1000 volume
+    fn capped_market_order(market_tif: MarketTif) -> ExecutionEngine {
+        let mut execution = ExecutionEngine::new();
+        execution.set_max_participation(Some(0.01));
+        execution.set_market_tif(market_tif);
+        market(&mut execution, OrderSide::Buy, 25);
+        execution
+    }
+
+    #[test]
+    fn capped_market_remainder_carries_to_the_next_bars() {
+        let mut execution = capped_market_order(MarketTif::FillOrCarry);
+        let first = execution.process_bar_detailed(&bar(4000.0, 4010.0, 3990.0, 4005.0));
+        assert_eq!(first.fills[0].qty, 10);
+        assert!(matches!(
+            first.still_pending[0].reason,
+            UnfilledReason::VolumeCapped { remaining: 15 }
+        ));
+
+        let later: Vec<i32> = (0..2)
+            .flat_map(|_| execution.process_bar(&bar(4000.0, 4010.0, 3990.0, 4005.0)))
+            .map(|fill| fill.qty)
+            .collect();
+        assert_eq!(later, vec![10, 5]);
+        assert!(execution.pending_orders().is_empty());
+    }
+
+    #[test]
+    fn capped_market_remainder_is_cancelled_with_ioc() {
+        let mut execution = capped_market_order(MarketTif::ImmediateOrCancel);
+        let result = execution.process_bar_detailed(&bar(4000.0, 4010.0, 3990.0, 4005.0));
+
+        assert_eq!(result.fills.len(), 1);
+        assert_eq!(result.fills[0].qty, 10);
+        assert!(matches!(
+            result.skipped[0].reason,
+            UnfilledReason::RemainderCancelled { remaining: 15 }
+        ));
+        assert!(execution.pending_orders().is_empty());
+    }
+}
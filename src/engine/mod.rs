@@ -1,5 +1,11 @@
 pub mod backtest;
 pub mod execution;
 
-pub use backtest::{BacktestConfig, BacktestEngine, BacktestResult};
-pub use execution::{ExecutionEngine, Fill, Order, OrderSide, OrderType};
+pub use backtest::{
+    BacktestConfig, BacktestEngine, BacktestResult, BarState, MarkPrice, SignalFillMode,
+};
+pub use execution::{
+    BracketFillPolicy, ExecutionEngine, ExecutionMode, ExitReason, Fill, LimitFillMode, MarketTif,
+    Order, OrderRejection, OrderSide, OrderStats, OrderType, PlacementCheck, ProcessResult,
+    StopTrigger, UnfilledOrder, UnfilledReason,
+};
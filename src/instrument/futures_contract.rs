@@ -1,5 +1,54 @@
 use serde::{Deserialize, Serialize};
 
+//how pnl relates to price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum ContractKind {
+    //pnl is linear in price: ticks * tick_value * qty
+    #[default]
+    Linear,
+    //pnl is linear in 1/price (inverse crypto, some fx): multiplier * qty * (1/entry - 1/exit)
+    //pnl is in the base unit, eg btc for a usd-quoted btc contract
+    Inverse,
+}
+
+impl ContractKind {
+    //parse contract kind from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "linear" => Some(ContractKind::Linear),
+            "inverse" => Some(ContractKind::Inverse),
+            _ => None,
+        }
+    }
+}
+
+//how a linear contract converts a price move into pnl
+//all three agree when fills land on tick boundaries; they differ for off-tick fills
+//(mid-price or spread fills) and for contracts whose point_value isn't tick_value / tick_size
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum PnlMethod {
+    //price move in (possibly fractional) ticks times tick_value
+    #[default]
+    Ticks,
+    //price move rounded to the nearest whole tick, times tick_value
+    //matches products that settle pnl in whole ticks
+    TickRounded,
+    //price move times point_value, with no reference to the tick grid
+    PointValue,
+}
+
+impl PnlMethod {
+    //parse pnl method from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "ticks" | "tick" => Some(PnlMethod::Ticks),
+            "tick_rounded" | "tick-rounded" | "rounded" => Some(PnlMethod::TickRounded),
+            "point_value" | "point-value" | "points" => Some(PnlMethod::PointValue),
+            _ => None,
+        }
+    }
+}
+
 //represents a futures contract specification
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FuturesContract {
@@ -32,6 +81,14 @@ pub struct FuturesContract {
 
     //maintenance margin per contract
     pub maintenance_margin: f64,
+
+    //linear or inverse pnl
+    #[serde(default)]
+    pub kind: ContractKind,
+
+    //how linear pnl is computed from a price move
+    #[serde(default)]
+    pub pnl_method: PnlMethod,
 }
 
 impl FuturesContract {
@@ -60,21 +117,61 @@ impl FuturesContract {
             multiplier,
             initial_margin,
             maintenance_margin,
+            kind: ContractKind::default(),
+            pnl_method: PnlMethod::default(),
         }
     }
 
+    //sets whether pnl is linear or inverse in price
+    pub fn with_kind(mut self, kind: ContractKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
     //converts a price difference to ticks
     pub fn price_to_ticks(&self, price_diff: f64) -> f64 {
         price_diff / self.tick_size
     }
 
-    //calculates pnl from a price move
+    //rounds a price to the nearest tick
+    pub fn round_to_tick(&self, price: f64) -> f64 {
+        if self.tick_size <= 0.0 {
+            return price;
+        }
+        (price / self.tick_size).round() * self.tick_size
+    }
+
+    //sets how linear pnl is computed from a price move
+    pub fn with_pnl_method(mut self, pnl_method: PnlMethod) -> Self {
+        self.pnl_method = pnl_method;
+        self
+    }
+
+    //calculates pnl from a price move using the contract's pnl method
     //arguments
     //price_diff - the price difference (exit_price - entry_price for long)
     //quantity - number of contracts (positive for long, negative for short)
     pub fn pnl_from_price_move(&self, price_diff: f64, quantity: i32) -> f64 {
-        let ticks = self.price_to_ticks(price_diff);
-        ticks * self.tick_value * quantity as f64
+        let per_contract = match self.pnl_method {
+            PnlMethod::Ticks => self.price_to_ticks(price_diff) * self.tick_value,
+            PnlMethod::TickRounded => self.price_to_ticks(price_diff).round() * self.tick_value,
+            PnlMethod::PointValue => price_diff * self.point_value,
+        };
+        per_contract * quantity as f64
+    }
+
+    //calculates pnl of quantity contracts (positive long, negative short) from entry to exit price
+    //linear contracts use the tick value, inverse contracts the change in 1/price
+    pub fn pnl_between(&self, entry_price: f64, exit_price: f64, quantity: i32) -> f64 {
+        match self.kind {
+            ContractKind::Linear => self.pnl_from_price_move(exit_price - entry_price, quantity),
+            ContractKind::Inverse => {
+                if entry_price <= 0.0 || exit_price <= 0.0 {
+                    return 0.0;
+                }
+                self.multiplier * quantity as f64 * (1.0 / entry_price - 1.0 / exit_price)
+            }
+        }
     }
 
     //calculates the notional value of a position
@@ -124,6 +221,24 @@ impl FuturesContract {
         )
     }
 
+    //returns the preset contract for a symbol (case-insensitive), or none if there isn't one
+    pub fn preset(symbol: &str, contract_month: &str) -> Option<Self> {
+        match symbol.to_uppercase().as_str() {
+            "ES" => Some(FuturesContract::es(contract_month)),
+            "NQ" => Some(FuturesContract::nq(contract_month)),
+            _ => None,
+        }
+    }
+
+    //symbols with a preset contract
+    pub const PRESET_SYMBOLS: &'static [&'static str] = &["ES", "NQ"];
+
+    //overrides the notional multiplier, leaving point_value (used for pnl) unchanged
+    pub fn with_multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier;
+        self
+    }
+
     //helper to create a custom contract from cli parameters
     pub fn from_params(
         symbol: String,
@@ -153,3 +268,103 @@ impl FuturesContract {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notional_uses_the_multiplier_override() {
+        let contract = FuturesContract::es("H24").with_multiplier(10.0);
+
+        assert_eq!(contract.notional_value(4000.0, -2), 80000.0);
+        //pnl still follows the 50-dollar point value
+        assert_eq!(contract.pnl_between(4000.0, 4001.0, 1), 50.0);
+    }
+
+    #[test]
+    fn multiplier_defaults_to_the_point_value() {
+        let contract = FuturesContract::from_params(
+            "ES".to_string(),
+            "H24".to_string(),
+            0.25,
+            12.5,
+            None,
+            None,
+            None,
+        );
+
+        assert_eq!(contract.notional_value(4000.0, 1), 200000.0);
+    }
+
+    fn inverse_btc() -> FuturesContract {
+        FuturesContract::from_params(
+            "BTC".to_string(),
+            "H24".to_string(),
+            0.5,
+            0.5,
+            Some(1.0),
+            None,
+            None,
+        )
+        .with_kind(ContractKind::Inverse)
+        .with_multiplier(100.0)
+    }
+
+    #[test]
+    fn inverse_pnl_matches_a_hand_computed_example() {
+        let contract = inverse_btc();
+
+        //10 contracts of 100 usd face, long from 50000 to 55000:
+        //1000 * (1/50000 - 1/55000) = 1000 / 550000 coins
+        let long = contract.pnl_between(50000.0, 55000.0, 10);
+        assert!((long - 1000.0 / 550000.0).abs() < 1e-15);
+        assert!((contract.pnl_between(50000.0, 55000.0, -10) + long).abs() < 1e-15);
+        //the same dollar move is worth more at a lower price
+        assert!(contract.pnl_between(25000.0, 30000.0, 10) > long);
+    }
+
+    #[test]
+    fn linear_is_the_default_kind() {
+        let contract = FuturesContract::es("H24");
+
+        assert_eq!(contract.kind, ContractKind::Linear);
+        assert_eq!(contract.pnl_between(4000.0, 3990.0, 2), -1000.0);
+    }
+
+    const PNL_METHODS: [PnlMethod; 3] = [
+        PnlMethod::Ticks,
+        PnlMethod::TickRounded,
+        PnlMethod::PointValue,
+    ];
+
+    #[test]
+    fn pnl_methods_agree_on_tick_aligned_fills() {
+        for (entry, exit, qty) in [
+            (4000.0, 4012.75, 3),
+            (4000.25, 3987.5, -2),
+            (4100.0, 4100.0, 1),
+        ] {
+            let expected = (exit - entry) * 50.0 * qty as f64;
+            for method in PNL_METHODS {
+                let contract = FuturesContract::es("H24").with_pnl_method(method);
+                let pnl = contract.pnl_between(entry, exit, qty);
+                assert!((pnl - expected).abs() < 1e-9, "{:?}: {}", method, pnl);
+            }
+        }
+    }
+
+    #[test]
+    fn pnl_methods_differ_on_off_tick_fills() {
+        //a mid-price fill 0.1 points from entry, less than half a tick
+        let pnl = |method| {
+            FuturesContract::es("H24")
+                .with_pnl_method(method)
+                .pnl_between(4000.0, 4000.1, 1)
+        };
+
+        assert!((pnl(PnlMethod::Ticks) - 5.0).abs() < 1e-9);
+        assert!((pnl(PnlMethod::PointValue) - 5.0).abs() < 1e-9);
+        assert_eq!(pnl(PnlMethod::TickRounded), 0.0);
+    }
+}
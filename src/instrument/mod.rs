@@ -1,3 +1,3 @@
 pub mod futures_contract;
 
-pub use futures_contract::FuturesContract;
+pub use futures_contract::{ContractKind, FuturesContract, PnlMethod};
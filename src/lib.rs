@@ -8,21 +8,278 @@ pub mod metrics;
 pub mod portfolio;
 pub mod strategy;
 
+use anyhow::{anyhow, Context, Result};
+use config::BacktestConfiguration;
+use data::Bar;
+use engine::{BacktestEngine, BacktestResult};
+use instrument::FuturesContract;
+use metrics::{objective_minimized, objective_value, SummaryMetrics};
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use strategy::Strategy;
+
+//runs a complete backtest from a configuration: loads and filters the data, builds the
+//contract and strategy, runs the engine and summarizes the result
+//custom strategies are resolved through the process-wide registry (register_strategy)
+pub fn run(config: BacktestConfiguration) -> Result<BacktestResult> {
+    let (mut engine, mut strategy) = config.build_engine()?;
+    Ok(engine.run(&mut strategy))
+}
+
+//runs one strategy configuration over each symbol of a multi-symbol bar set, independently
+//and in parallel, and ranks the symbols best first by the objective (see objective_value)
+//each symbol trades its preset contract when there is one, else the configured contract
+//renamed to it; the configuration's own data path and symbol are ignored
+pub fn screen(
+    config: &BacktestConfiguration,
+    bars: &[Bar],
+    symbols: &[&str],
+    objective: &str,
+) -> Result<Vec<(String, SummaryMetrics)>> {
+    config.validate_strategy()?;
+    objective_value(&SummaryMetrics::default(), objective)
+        .ok_or_else(|| anyhow!("Unknown objective: {}", objective))?;
+
+    let mut results = symbols
+        .par_iter()
+        .enumerate()
+        .map(|(index, &symbol)| {
+            let (mut engine, mut strategy) = screen_engine(config, bars, index, symbol)?;
+            Ok((symbol.to_string(), engine.run(&mut strategy).summary))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    //nan objectives (eg a ratio with no trades) rank last
+    let minimized = objective_minimized(objective);
+    let key = |metrics: &SummaryMetrics| {
+        objective_value(metrics, objective)
+            .filter(|value| !value.is_nan())
+            .map(|value| if minimized { -value } else { value })
+    };
+    results.sort_by(|(_, a), (_, b)| key(b).partial_cmp(&key(a)).unwrap_or(Ordering::Equal));
+    Ok(results)
+}
+
+//builds the engine and strategy for the index-th symbol of a screen
+//the index is the engine's id namespace, so fill ids stay unique across the symbols
+fn screen_engine(
+    config: &BacktestConfiguration,
+    bars: &[Bar],
+    index: usize,
+    symbol: &str,
+) -> Result<(BacktestEngine, Box<dyn Strategy>)> {
+    let contract =
+        FuturesContract::preset(symbol, &config.contract.contract_month).unwrap_or_else(|| {
+            let mut contract = config.contract.to_futures_contract();
+            contract.symbol = symbol.to_string();
+            contract
+        });
+    //caller bars may be unsorted, so the engine keeps its order check
+    let mut engine_config = config.to_engine_config();
+    engine_config.id_namespace = index as u32;
+    let engine = config.engine_for(bars, symbol, contract, engine_config)?;
+    let strategy = config
+        .strategy_params
+        .build_strategy(symbol)
+        .context(format!("Failed to build the strategy for {}", symbol))?;
+    Ok((engine, strategy))
+}
+
 //prelude module for convenient imports
 pub mod prelude {
     pub use crate::config::{
-        BacktestConfiguration, ContractConfig, RsiParams, SmaParams, StrategyParams, StrategyType,
+        BacktestConfiguration, ContractConfig, CustomParams, KeltnerParams, RsiParams, SmaParams,
+        StrategyParams, StrategyType,
+    };
+    pub use crate::data::{
+        clean_missing_prices, clear_bar_cache, filter_by_date_range, filter_by_symbol,
+        filter_by_symbols, load_csv, load_csv_cached, load_csv_reader,
+        load_csv_reader_with_options, load_csv_with_options, set_bar_cache_capacity, Bar, BarCache,
+        LoaderOptions, MissingPricePolicy,
     };
-    pub use crate::data::{filter_by_symbol, load_csv, Bar};
     pub use crate::engine::{
-        BacktestConfig, BacktestEngine, BacktestResult, ExecutionEngine, Fill, Order, OrderSide,
-        OrderType,
+        BacktestConfig, BacktestEngine, BacktestResult, BarState, BracketFillPolicy,
+        ExecutionEngine, ExecutionMode, ExitReason, Fill, LimitFillMode, MarkPrice, MarketTif,
+        Order, OrderRejection, OrderSide, OrderStats, OrderType, PlacementCheck, ProcessResult,
+        SignalFillMode, StopTrigger, UnfilledOrder, UnfilledReason,
+    };
+    pub use crate::instrument::{ContractKind, FuturesContract, PnlMethod};
+    #[cfg(feature = "html-report")]
+    pub use crate::metrics::tear_sheet_html;
+    pub use crate::metrics::{
+        aggregate_metrics, calculate_equity_curve, calculate_equity_curve_intrabar,
+        calculate_equity_curve_with_mode, drawdown_periods, heatmap_csv, infer_periods_per_year,
+        monthly_returns, objective_minimized, objective_value, r_distribution, r_multiple_stats,
+        regression_stats, seasonality, slippage_distribution, slippage_stats, tear_sheet,
+        worst_drawdown, write_drawdown_csv, AggregateMetrics, BucketStats, CurrencyFormat,
+        DirectionStats, DrawdownMode, DrawdownPeriod, EquityPoint, Heatmap, MetricStats,
+        MetricsError, RMultipleStats, RegressionStats, Seasonality, SlippageStats, SummaryMetrics,
+        TradingCalendar,
+    };
+    pub use crate::portfolio::{
+        round_trips, write_round_trips_csv, Account, AccountError, AccountMode, AccountSnapshot,
+        CostTiming, FeeSettlement, Lot, Position, RoundTrip, TradeLog,
     };
-    pub use crate::instrument::FuturesContract;
-    pub use crate::metrics::{calculate_equity_curve, EquityPoint, SummaryMetrics};
-    pub use crate::portfolio::{Account, Position};
     pub use crate::strategy::{
-        rsi_reversion::RsiReversionStrategy, sma_crossover::SmaCrossoverStrategy, Strategy,
-        StrategyContext,
+        keltner::{KeltnerMode, KeltnerStrategy},
+        protective::ProtectiveExits,
+        registry::{register_strategy, ParamMap, StrategyFactory, StrategyRegistry},
+        rsi_reversion::RsiReversionStrategy,
+        sizing::{PositionSizing, VolTarget},
+        sma_crossover::SmaCrossoverStrategy,
+        AsAny, Strategy, StrategyContext,
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SmaParams, StrategyParams};
+    use crate::engine::ExecutionEngine;
+    use chrono::TimeZone;
+
+    //an oscillating series per symbol with its own drift, so the crossovers trade
+    fn universe() -> Vec<Bar> {
+        let start = chrono::Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let mut bars = Vec::new();
+        for (symbol, drift) in [("AAA", 2.0), ("BBB", -2.0)] {
+            for i in 0..120 {
+                let close = 1000.0 + 20.0 * (i as f64 / 5.0).sin() + drift * i as f64;
+                bars.push(
+                    Bar::new(
+                        start + chrono::Duration::days(i),
+                        close,
+                        close + 1.0,
+                        close - 1.0,
+                        close,
+                        1000.0,
+                        None,
+                        symbol.to_string(),
+                    )
+                    .unwrap(),
+                );
+            }
+        }
+        bars
+    }
+
+    fn config() -> BacktestConfiguration {
+        BacktestConfiguration {
+            strategy_params: StrategyParams::Sma(SmaParams {
+                fast_window: 3,
+                slow_window: 8,
+                ..SmaParams::default()
+            }),
+            ..BacktestConfiguration::default()
+        }
+    }
+
+    #[test]
+    fn screen_ranks_symbols_by_objective() {
+        let results = screen(&config(), &universe(), &["AAA", "BBB"], "total_return").unwrap();
+
+        assert_eq!(results.len(), 2);
+        assert!(results[0].1.num_trades > 0);
+        assert!(results[0].1.total_return >= results[1].1.total_return);
+    }
+
+    #[test]
+    fn screen_sorts_unsorted_caller_bars() {
+        let sorted = screen(&config(), &universe(), &["AAA"], "sharpe").unwrap();
+
+        let mut shuffled = universe();
+        shuffled.reverse();
+        let unsorted = screen(&config(), &shuffled, &["AAA"], "sharpe").unwrap();
+
+        assert_eq!(sorted[0].1.total_return, unsorted[0].1.total_return);
+        assert_eq!(sorted[0].1.num_trades, unsorted[0].1.num_trades);
+    }
+
+    #[test]
+    fn screened_symbols_get_unique_fill_ids() {
+        let bars = universe();
+        let mut ids = Vec::new();
+        for (index, symbol) in ["AAA", "BBB"].into_iter().enumerate() {
+            let (mut engine, mut strategy) =
+                screen_engine(&config(), &bars, index, symbol).unwrap();
+            let result = engine.run(&mut strategy);
+            assert!(!result.trades.fills().is_empty());
+            for fill in result.trades.fills() {
+                assert_eq!(ExecutionEngine::id_namespace(fill.id), index as u32);
+                ids.push(fill.id);
+            }
+        }
+
+        let count = ids.len();
+        ids.sort_unstable();
+        ids.dedup();
+        assert_eq!(ids.len(), count);
+    }
+
+    #[test]
+    fn screen_rejects_unknown_objective_before_running() {
+        //the symbol has no bars, so only an up-front check reports the objective
+        let err = screen(&config(), &universe(), &["ZZZ"], "luck").unwrap_err();
+        assert!(err.to_string().contains("Unknown objective"));
+    }
+
+    //writes daily es bars oscillating around 4000 to a csv file in dir
+    fn write_csv(dir: &std::path::Path, days: i64) -> std::path::PathBuf {
+        let start = chrono::Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let mut csv = String::from("timestamp,open,high,low,close,volume,symbol\n");
+        for i in 0..days {
+            let close = 4000.0 + 20.0 * (i as f64 / 5.0).sin();
+            csv.push_str(&format!(
+                "{},{},{},{},{},1000,ES\n",
+                (start + chrono::Duration::days(i)).to_rfc3339(),
+                close,
+                close + 1.0,
+                close - 1.0,
+                close
+            ));
+        }
+        let path = dir.join("es.csv");
+        std::fs::write(&path, csv).unwrap();
+        path
+    }
+
+    //the whole pipeline from a saved config file and a csv: load, filter, build, run, summarize
+    #[test]
+    fn run_drives_a_backtest_from_a_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.json");
+        BacktestConfiguration {
+            data_path: write_csv(dir.path(), 90),
+            start_date: chrono::NaiveDate::from_ymd_opt(2024, 1, 12),
+            ..config()
+        }
+        .to_json_file(&config_path)
+        .unwrap();
+
+        let config = BacktestConfiguration::from_json_file(&config_path).unwrap();
+        let result = run(config.clone()).unwrap();
+
+        //the first ten days fall before the start date
+        assert_eq!(result.equity_curve.len(), 80);
+        assert!(result.summary.num_trades > 0);
+        assert!(result.trades.fills().iter().all(|fill| fill.symbol == "ES"));
+        assert_eq!(
+            result.summary.final_balance,
+            metrics::round_to_cents(result.equity_curve.last().unwrap().equity)
+        );
+
+        let rerun = run(config).unwrap();
+        assert_eq!(rerun.summary.final_balance, result.summary.final_balance);
+    }
+
+    #[test]
+    fn run_fails_on_missing_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = BacktestConfiguration {
+            data_path: dir.path().join("missing.csv"),
+            ..config()
+        };
+
+        assert!(run(config).is_err());
+    }
+}
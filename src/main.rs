@@ -1,6 +1,8 @@
-use anyhow::{Context, Result};
-use clap::{Parser, Subcommand};
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
 use menudo::prelude::*;
+use menudo::strategy::registry::is_registered;
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -14,310 +16,1073 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     //run a backtest
-    Run {
-        //path to csv data file
-        #[arg(long)]
-        data: PathBuf,
-
-        //symbol to trade (eg es, nq)
-        #[arg(long)]
-        symbol: String,
-
-        //strategy type (sma, rsi)
-        #[arg(long)]
-        strategy: String,
-
-        //contract month (eg 2025-03)
-        #[arg(long, default_value = "2025-03")]
-        contract_month: String,
-
-        //tick size
-        #[arg(long)]
-        tick_size: f64,
-
-        //tick value (dollar value of one tick)
-        #[arg(long)]
-        tick_value: f64,
-
-        //point value (optional, defaults to tick_value/tick_size)
-        #[arg(long)]
-        point_value: Option<f64>,
-
-        //initial margin per contract (optional)
-        #[arg(long)]
-        initial_margin: Option<f64>,
-
-        //maintenance margin per contract (optional)
-        #[arg(long)]
-        maintenance_margin: Option<f64>,
-
-        //initial account balance
-        #[arg(long, default_value = "100000")]
-        initial_balance: f64,
-
-        //commission per contract per side
-        #[arg(long, default_value = "2.5")]
-        commission: f64,
-
-        //slippage per contract per side
-        #[arg(long, default_value = "1.0")]
-        slippage: f64,
-
-        //sma strategy parameters
-        //fast sma window (for sma strategy)
-        #[arg(long)]
-        fast: Option<usize>,
-
-        //slow sma window (for sma strategy)
-        #[arg(long)]
-        slow: Option<usize>,
-
-        //rsi strategy parameters
-        //rsi lookback period (for rsi strategy)
-        #[arg(long)]
-        rsi_lookback: Option<usize>,
-
-        //rsi lower threshold (for rsi strategy)
-        #[arg(long)]
-        rsi_lower: Option<f64>,
-
-        //rsi upper threshold (for rsi strategy)
-        #[arg(long)]
-        rsi_upper: Option<f64>,
-
-        //common strategy parameter
-        //number of contracts to trade
-        #[arg(long, default_value = "1")]
-        qty: u32,
-
-        //output options
-        //output path for equity curve csv
-        #[arg(long)]
-        output_equity_csv: Option<PathBuf>,
-
-        //output path for trades csv
-        #[arg(long)]
-        output_trades_csv: Option<PathBuf>,
-    },
+    Run(Box<RunArgs>),
+
+    //validate data and parameters without running a backtest
+    Validate(Box<ValidateArgs>),
+
+    //print the specification of a preset contract
+    Contract(ContractArgs),
 }
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+//arguments for the contract command
+#[derive(Args)]
+struct ContractArgs {
+    //contract symbol (eg es, nq)
+    symbol: String,
+}
 
-    match cli.command {
-        Commands::Run {
-            data,
-            symbol,
-            strategy,
-            contract_month,
-            tick_size,
-            tick_value,
-            point_value,
-            initial_margin,
-            maintenance_margin,
-            initial_balance,
-            commission,
-            slippage,
-            fast,
-            slow,
-            rsi_lookback,
-            rsi_lower,
-            rsi_upper,
-            qty,
-            output_equity_csv,
-            output_trades_csv,
-        } => {
-            run_backtest(
-                data,
-                symbol,
-                strategy,
-                contract_month,
-                tick_size,
-                tick_value,
-                point_value,
-                initial_margin,
-                maintenance_margin,
-                initial_balance,
-                commission,
-                slippage,
-                fast,
-                slow,
-                rsi_lookback,
-                rsi_lower,
-                rsi_upper,
-                qty,
-                output_equity_csv,
-                output_trades_csv,
-            )?;
-        }
-    }
+//arguments for the validate command
+#[derive(Args)]
+struct ValidateArgs {
+    //path to csv data file
+    #[arg(long)]
+    data: PathBuf,
 
-    Ok(())
+    //csv header mapping
+    #[command(flatten)]
+    loader_args: LoaderArgs,
+
+    //symbol to trade (eg es, nq)
+    #[arg(long)]
+    symbol: String,
+
+    //strategy type (sma, rsi, keltner)
+    #[arg(long)]
+    strategy: String,
+
+    //tick size (optional, checked if given)
+    #[arg(long)]
+    tick_size: Option<f64>,
+
+    //tick value (optional, checked if given)
+    #[arg(long)]
+    tick_value: Option<f64>,
+
+    //strategy parameters
+    #[command(flatten)]
+    strategy_args: StrategyArgs,
 }
 
-#[allow(clippy::too_many_arguments)]
-fn run_backtest(
-    data_path: PathBuf,
+//arguments for the run command
+#[derive(Args)]
+struct RunArgs {
+    //path to csv data file
+    #[arg(long)]
+    data: PathBuf,
+
+    //csv header mapping
+    #[command(flatten)]
+    loader_args: LoaderArgs,
+
+    //symbol to trade (eg es, nq)
+    #[arg(long)]
     symbol: String,
-    strategy_name: String,
+
+    //strategy type (sma, rsi, keltner)
+    #[arg(long)]
+    strategy: String,
+
+    //first date to run over, inclusive (eg 2024-01-01, optional)
+    #[arg(long)]
+    start_date: Option<NaiveDate>,
+
+    //last date to run over, inclusive of the whole day (eg 2024-12-31, optional)
+    #[arg(long)]
+    end_date: Option<NaiveDate>,
+
+    //contract month (eg 2025-03)
+    #[arg(long, default_value = "2025-03")]
     contract_month: String,
+
+    //tick size
+    #[arg(long)]
     tick_size: f64,
+
+    //tick value (dollar value of one tick)
+    #[arg(long)]
     tick_value: f64,
+
+    //point value (optional, defaults to tick_value/tick_size)
+    #[arg(long)]
     point_value: Option<f64>,
+
+    //initial margin per contract (optional)
+    #[arg(long)]
     initial_margin: Option<f64>,
+
+    //maintenance margin per contract (optional)
+    #[arg(long)]
     maintenance_margin: Option<f64>,
+
+    //notional multiplier if it differs from the point value (optional)
+    #[arg(long)]
+    multiplier: Option<f64>,
+
+    //contract pnl model (linear, inverse)
+    #[arg(long, default_value = "linear")]
+    contract_kind: String,
+
+    //linear pnl calculation (ticks, tick_rounded, point_value) - differs only for off-tick fills
+    #[arg(long, default_value = "ticks")]
+    pnl_method: String,
+
+    //initial account balance
+    #[arg(long, default_value = "100000")]
     initial_balance: f64,
+
+    //commission per contract per side
+    #[arg(long, default_value = "2.5")]
     commission: f64,
+
+    //least commission per order side, for broker schedules with an order minimum
+    #[arg(long, default_value = "0")]
+    min_commission: f64,
+
+    //flat ticket fee per order side, charged on top of the per-contract commission
+    #[arg(long, default_value = "0")]
+    order_fee: f64,
+
+    //slippage per contract per side
+    #[arg(long, default_value = "1.0")]
     slippage: f64,
+
+    //when costs are charged (per_fill, entry, exit) - entry/exit charge a round turn once
+    #[arg(long, default_value = "per_fill")]
+    cost_timing: String,
+
+    //when costs leave cash (immediate, daily) - daily charges them at each day's last bar
+    #[arg(long, default_value = "immediate")]
+    fee_settlement: String,
+
+    //position keeping (netting, hedging) - hedging holds opposing fills as separate lots
+    #[arg(long, default_value = "netting")]
+    account_mode: String,
+
+    //execution mode (slippage, spread) - spread fills market orders at bid/ask when present
+    #[arg(long, default_value = "slippage")]
+    execution_mode: String,
+
+    //limit order fill rule (touch, through) - touch is optimistic
+    #[arg(long, default_value = "touch")]
+    limit_fill_mode: String,
+
+    //stop order trigger (intrabar, close) - close ignores wicks through the stop
+    #[arg(long, default_value = "intrabar")]
+    stop_trigger: String,
+
+    //largest fraction of a bar's volume market orders may fill on it (optional, eg 0.1)
+    #[arg(long)]
+    max_participation: Option<f64>,
+
+    //what happens to a market order's remainder over the volume cap (carry, ioc)
+    #[arg(long, default_value = "carry")]
+    market_tif: String,
+
+    //bracket leg assumed to fill when a bar hits both take-profit and stop
+    //(pessimistic, optimistic, prorata)
+    #[arg(long, default_value = "pessimistic")]
+    bracket_fill_policy: String,
+
+    //bars strategy orders wait after the signal before they can fill (latency)
+    #[arg(long, default_value = "0")]
+    signal_delay_bars: u32,
+
+    //ticks market orders fill against you, beyond the open (or signal close)
+    #[arg(long, default_value = "0")]
+    market_slippage_ticks: u32,
+
+    //limit/stop priced on the wrong side of the last close (off, warn, reject)
+    #[arg(long, default_value = "warn")]
+    placement_check: String,
+
+    //maximum same-direction add-on entries while a position is open (optional)
+    #[arg(long)]
+    max_pyramid_entries: Option<usize>,
+
+    //dollar risk per atr of movement; caps the position each bar at risk / (atr x point value)
+    #[arg(long)]
+    vol_target_risk: Option<f64>,
+
+    //atr period for the volatility target
+    #[arg(long, default_value = "14")]
+    vol_target_atr: usize,
+
+    //bars at the start of the run the strategy doesn't trade on (equity is still recorded)
+    #[arg(long, default_value = "0")]
+    warmup_bars: usize,
+
+    //calendar days from the first bar the strategy doesn't trade on (optional)
+    #[arg(long)]
+    warmup_days: Option<u32>,
+
+    //bars to suppress new entries after a fill, counting the fill bar (optional)
+    #[arg(long)]
+    min_bars_between_trades: Option<usize>,
+
+    //halt trading and go flat once drawdown from peak equity exceeds this fraction (eg 0.2)
+    #[arg(long)]
+    max_drawdown_stop: Option<f64>,
+
+    //equity at or below which the account is bankrupt: go flat and halt trading
+    #[arg(long, default_value = "0")]
+    equity_floor: f64,
+
+    //run without an equity floor, so equity may go negative and trading never halts on it
+    #[arg(long, conflicts_with = "equity_floor")]
+    no_equity_floor: bool,
+
+    //roll an open position to the next contract on this date (repeatable, eg 2024-03-14)
+    #[arg(long = "roll-date")]
+    roll_dates: Vec<NaiveDate>,
+
+    //price used to mark equity (close, settlement)
+    #[arg(long, default_value = "close")]
+    mark_price: String,
+
+    //when strategy market orders fill (next_open, signal_close) - signal_close ignores gaps
+    #[arg(long, default_value = "next_open")]
+    signal_fill: String,
+
+    //drawdown definition (percent, dollar, percent-from-start, dollar-from-start)
+    #[arg(long, default_value = "percent")]
+    drawdown_mode: String,
+
+    //measure drawdown at each bar's worst-case price instead of the close
+    #[arg(long)]
+    intrabar_drawdown: bool,
+
+    //return periods per year for sharpe/sortino/volatility (default: inferred from bar spacing)
+    #[arg(long)]
+    periods_per_year: Option<f64>,
+
+    //seed for stochastic components; the same seed, data and options reproduce a run exactly
+    #[arg(long, default_value = "0")]
+    seed: u64,
+
+    //account currency used to format output (usd, eur, gbp, jpy, chf)
+    #[arg(long, default_value = "usd")]
+    currency: String,
+
+    //strategy parameters
+    #[command(flatten)]
+    strategy_args: StrategyArgs,
+
+    //print average pnl by entry weekday and hour
+    #[arg(long)]
+    seasonality: bool,
+
+    //output options
+    //output path for the full result as json (reload with BacktestResult::load)
+    #[arg(long)]
+    output_result_json: Option<PathBuf>,
+
+    //output path for equity curve csv
+    #[arg(long)]
+    output_equity_csv: Option<PathBuf>,
+
+    //output path for trades csv
+    #[arg(long)]
+    output_trades_csv: Option<PathBuf>,
+
+    //output path for a round-trip-per-row trades csv, for importing into trade journals
+    #[arg(long)]
+    output_round_trips_csv: Option<PathBuf>,
+
+    //output path for the underwater (drawdown) curve csv
+    #[arg(long)]
+    output_drawdown_csv: Option<PathBuf>,
+
+    //output path for a tear sheet report (text; .html needs the html-report feature)
+    #[arg(long)]
+    output_tear_sheet: Option<PathBuf>,
+}
+
+//csv header mapping shared by the run and validate commands
+#[derive(Args)]
+struct LoaderArgs {
+    //maps a csv header to the field it holds as header=field (repeatable, eg datetime=timestamp)
+    #[arg(long = "column-map", value_parser = parse_column_map)]
+    column_map: Vec<(String, String)>,
+
+    //recognise common vendor header aliases (datetime, vol, oi, ...)
+    #[cfg(feature = "csv-autodetect")]
+    #[arg(long)]
+    detect_columns: bool,
+
+    //bars with a blank, NaN or zero price: error, skip, ffill (carry the previous close) or keep
+    #[arg(long, default_value = "error", value_parser = parse_missing_prices)]
+    missing_prices: MissingPricePolicy,
+}
+
+impl LoaderArgs {
+    fn to_options(&self) -> LoaderOptions {
+        LoaderOptions {
+            column_map: self.column_map.iter().cloned().collect(),
+            #[cfg(feature = "csv-autodetect")]
+            auto_detect: self.detect_columns,
+            missing_prices: self.missing_prices,
+        }
+    }
+}
+
+fn parse_missing_prices(s: &str) -> std::result::Result<MissingPricePolicy, String> {
+    MissingPricePolicy::parse(s).ok_or_else(|| {
+        format!(
+            "unknown missing price policy '{}' (keep, error, skip, ffill)",
+            s
+        )
+    })
+}
+
+//parses a header=field column mapping
+fn parse_column_map(s: &str) -> std::result::Result<(String, String), String> {
+    let (header, field) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected header=field, got '{}'", s))?;
+    Ok((header.to_string(), field.to_string()))
+}
+
+//strategy parameters shared by the run and validate commands
+#[derive(Args)]
+struct StrategyArgs {
+    //sma strategy parameters
+    //fast sma window (for sma strategy)
+    #[arg(long)]
     fast: Option<usize>,
+
+    //slow sma window (for sma strategy)
+    #[arg(long)]
     slow: Option<usize>,
+
+    //long-term trend filter sma window (optional, for sma strategy)
+    #[arg(long)]
+    trend_filter: Option<usize>,
+
+    //rsi strategy parameters
+    //rsi lookback period (for rsi strategy)
+    #[arg(long)]
     rsi_lookback: Option<usize>,
+
+    //rsi lower threshold (for rsi strategy)
+    #[arg(long)]
     rsi_lower: Option<f64>,
+
+    //rsi upper threshold (for rsi strategy)
+    #[arg(long)]
     rsi_upper: Option<f64>,
+
+    //bars rsi must stay beyond a threshold before entering (for rsi strategy)
+    #[arg(long)]
+    rsi_confirmation_bars: Option<usize>,
+
+    //protective exits (for sma and rsi strategies)
+    //stop-loss distance from the entry price, in ticks
+    #[arg(long)]
+    stop_loss_ticks: Option<u32>,
+
+    //take-profit distance from the entry price, in ticks
+    #[arg(long)]
+    take_profit_ticks: Option<u32>,
+
+    //keltner strategy parameters
+    //ema midline period (for keltner strategy)
+    #[arg(long)]
+    ema_period: Option<usize>,
+
+    //atr period for band width (for keltner strategy)
+    #[arg(long)]
+    atr_period: Option<usize>,
+
+    //atr multiplier for band width (for keltner strategy)
+    #[arg(long)]
+    keltner_multiplier: Option<f64>,
+
+    //trade breakouts or reversions (breakout, reversion) (for keltner strategy)
+    #[arg(long, default_value = "breakout")]
+    keltner_mode: String,
+
+    //custom strategy parameters as key=value (repeatable, for registered strategies)
+    #[arg(long = "param", value_parser = parse_param)]
+    params: Vec<(String, f64)>,
+
+    //common strategy parameter
+    //number of contracts to trade
+    #[arg(long, default_value = "1")]
     qty: u32,
-    output_equity_csv: Option<PathBuf>,
-    output_trades_csv: Option<PathBuf>,
-) -> Result<()> {
-    println!("Menudo Futures Backtesting Engine");
-    println!("==================================\n");
 
-    //load data
+    //position sizing mode (fixed, pct)
+    #[arg(long, default_value = "fixed")]
+    size_mode: String,
+
+    //fraction of equity per entry (for pct sizing, e.g. 0.1)
+    #[arg(long)]
+    size: Option<f64>,
+}
+
+//parses a key=value custom strategy parameter
+fn parse_param(s: &str) -> std::result::Result<(String, f64), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("expected key=value, got '{}'", s))?;
+    let value = value
+        .parse::<f64>()
+        .map_err(|e| format!("invalid value for '{}': {}", key, e))?;
+    Ok((key.to_string(), value))
+}
+
+impl StrategyArgs {
+    //builds strategy parameters, applying defaults where optional
+    //names that aren't built in are looked up among registered custom strategies
+    fn to_params(&self, strategy_name: &str) -> Result<StrategyParams> {
+        let strategy_type = match StrategyType::parse(strategy_name) {
+            Some(strategy_type) => strategy_type,
+            None if is_registered(strategy_name) => StrategyType::Custom,
+            None => anyhow::bail!("Unknown strategy: {}", strategy_name),
+        };
+
+        let size_fraction = match self.size_mode.to_lowercase().as_str() {
+            "fixed" => None,
+            "pct" | "percent" => Some(
+                self.size
+                    .ok_or_else(|| anyhow::anyhow!("--size required for pct sizing"))?,
+            ),
+            _ => anyhow::bail!("Unknown size mode: {}", self.size_mode),
+        };
+
+        let params = match strategy_type {
+            StrategyType::SmaCrossover => StrategyParams::Sma(SmaParams {
+                fast_window: self
+                    .fast
+                    .ok_or_else(|| anyhow::anyhow!("--fast required for SMA strategy"))?,
+                slow_window: self
+                    .slow
+                    .ok_or_else(|| anyhow::anyhow!("--slow required for SMA strategy"))?,
+                trend_window: self.trend_filter,
+                qty: self.qty,
+                size_fraction,
+                stop_loss_ticks: self.stop_loss_ticks,
+                take_profit_ticks: self.take_profit_ticks,
+            }),
+            StrategyType::RsiReversion => StrategyParams::Rsi(RsiParams {
+                lookback: self.rsi_lookback.unwrap_or(14),
+                oversold: self.rsi_lower.unwrap_or(30.0),
+                overbought: self.rsi_upper.unwrap_or(70.0),
+                confirmation_bars: self.rsi_confirmation_bars.unwrap_or(1),
+                qty: self.qty,
+                size_fraction,
+                stop_loss_ticks: self.stop_loss_ticks,
+                take_profit_ticks: self.take_profit_ticks,
+            }),
+            StrategyType::Keltner => StrategyParams::Keltner(KeltnerParams {
+                ema_period: self.ema_period.unwrap_or(20),
+                atr_period: self.atr_period.unwrap_or(10),
+                multiplier: self.keltner_multiplier.unwrap_or(2.0),
+                mode: KeltnerMode::parse(&self.keltner_mode).ok_or_else(|| {
+                    anyhow::anyhow!("Unknown keltner mode: {}", self.keltner_mode)
+                })?,
+                qty: self.qty,
+                size_fraction,
+            }),
+            StrategyType::Custom => StrategyParams::Custom(CustomParams {
+                name: strategy_name.to_string(),
+                qty: self.qty,
+                params: self.params.iter().cloned().collect(),
+            }),
+        };
+
+        Ok(params)
+    }
+}
+
+fn main() -> Result<()> {
+    //library logs go to stderr; set RUST_LOG (eg RUST_LOG=menudo=debug) for more detail
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    let cli = Cli::parse();
+
+    match cli.command {
+        Commands::Run(args) => {
+            run_backtest(*args)?;
+        }
+        Commands::Validate(args) => {
+            validate_inputs(*args)?;
+        }
+        Commands::Contract(args) => {
+            print_contract(args)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn print_contract(args: ContractArgs) -> Result<()> {
+    use prettytable::{Cell, Row, Table};
+
+    let contract = FuturesContract::preset(&args.symbol, "").ok_or_else(|| {
+        anyhow::anyhow!(
+            "Unknown contract symbol: {} (presets: {})",
+            args.symbol,
+            FuturesContract::PRESET_SYMBOLS.join(", ")
+        )
+    })?;
+
+    let mut table = Table::new();
+    let rows = [
+        ("Symbol", contract.symbol.clone()),
+        ("Exchange", contract.exchange.clone()),
+        ("Currency", contract.currency.clone()),
+        ("Tick Size", contract.tick_size.to_string()),
+        ("Tick Value", format!("{:.2}", contract.tick_value)),
+        ("Point Value", format!("{:.2}", contract.point_value)),
+        ("Multiplier", contract.multiplier.to_string()),
+        ("Initial Margin", format!("{:.2}", contract.initial_margin)),
+        (
+            "Maintenance Margin",
+            format!("{:.2}", contract.maintenance_margin),
+        ),
+    ];
+    for (name, value) in rows {
+        table.add_row(Row::new(vec![Cell::new(name), Cell::new(&value)]));
+    }
+    table.printstd();
+
+    Ok(())
+}
+
+fn validate_inputs(args: ValidateArgs) -> Result<()> {
+    let ValidateArgs {
+        data: data_path,
+        loader_args,
+        symbol,
+        strategy: strategy_name,
+        tick_size,
+        tick_value,
+        strategy_args,
+    } = args;
+
+    let mut problems = Vec::new();
+
+    //strategy and parameters
+    match strategy_args
+        .to_params(&strategy_name)
+        .and_then(|params| params.validate().map(|_| params))
+    {
+        Ok(params) => println!("Strategy: {:?}", params),
+        Err(e) => problems.push(format!("Invalid strategy: {}", e)),
+    }
+
+    //contract specification
+    if let Some(tick_size) = tick_size {
+        if tick_size <= 0.0 {
+            problems.push(format!("Tick size must be positive, got {}", tick_size));
+        }
+    }
+    if let Some(tick_value) = tick_value {
+        if tick_value <= 0.0 {
+            problems.push(format!("Tick value must be positive, got {}", tick_value));
+        }
+    }
+
+    //data
     println!("Loading data from {:?}...", data_path);
-    let all_bars =
-        load_csv(&data_path).context(format!("Failed to load data from {:?}", data_path))?;
+    match load_csv_with_options(&data_path, &loader_args.to_options()) {
+        Ok(all_bars) => {
+            let bars = filter_by_symbol(&all_bars, &symbol);
 
-    //filter by symbol
-    let bars = filter_by_symbol(&all_bars, &symbol);
+            match (bars.first(), bars.last()) {
+                (Some(first), Some(last)) => {
+                    println!("Loaded {} bars for {}", bars.len(), symbol);
+                    println!("Date range: {} to {}", first.timestamp, last.timestamp);
 
-    if bars.is_empty() {
-        anyhow::bail!("No data found for symbol {}", symbol);
+                    let duplicates = print_gap_summary(&bars);
+                    if duplicates > 0 {
+                        problems.push(format!("{} duplicate timestamp(s) found", duplicates));
+                    }
+                }
+                _ => problems.push(format!("No data found for symbol {}", symbol)),
+            }
+        }
+        Err(e) => problems.push(format!("Failed to load data: {:#}", e)),
+    }
+
+    if problems.is_empty() {
+        println!("\nValidation passed");
+        return Ok(());
     }
 
-    println!("Loaded {} bars for {}", bars.len(), symbol);
+    println!("\nValidation failed:");
+    for problem in &problems {
+        println!("  - {}", problem);
+    }
+    anyhow::bail!("{} problem(s) found", problems.len())
+}
+
+//prints the typical bar spacing and gaps larger than it
+//returns the number of duplicate timestamps
+fn print_gap_summary(bars: &[Bar]) -> usize {
+    let mut intervals: Vec<chrono::Duration> = bars
+        .windows(2)
+        .map(|pair| pair[1].timestamp - pair[0].timestamp)
+        .collect();
+
+    let duplicates = intervals.iter().filter(|d| d.is_zero()).count();
+
+    if intervals.is_empty() {
+        println!("Gaps: n/a (single bar)");
+        return duplicates;
+    }
+
+    intervals.sort();
+    let typical = intervals[intervals.len() / 2];
+    let largest = intervals[intervals.len() - 1];
+    let gap_count = intervals.iter().filter(|&&d| d > typical).count();
+
     println!(
-        "Date range: {} to {}\n",
-        bars.first().unwrap().timestamp,
-        bars.last().unwrap().timestamp
+        "Typical spacing: {}, gaps larger than typical: {}, largest gap: {}",
+        format_duration(typical),
+        gap_count,
+        format_duration(largest)
     );
 
-    //create contract
-    let contract = FuturesContract::from_params(
-        symbol.clone(),
+    duplicates
+}
+
+//formats a duration as days plus hh:mm:ss
+fn format_duration(duration: chrono::Duration) -> String {
+    let secs = duration.num_seconds();
+    let days = secs / 86400;
+    let rem = secs % 86400;
+    format!(
+        "{}d {:02}:{:02}:{:02}",
+        days,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+fn run_backtest(args: RunArgs) -> Result<()> {
+    let RunArgs {
+        data: data_path,
+        loader_args,
+        symbol,
+        strategy: strategy_name,
+        start_date,
+        end_date,
         contract_month,
         tick_size,
         tick_value,
         point_value,
         initial_margin,
         maintenance_margin,
-    );
+        multiplier,
+        contract_kind,
+        pnl_method,
+        initial_balance,
+        commission,
+        min_commission,
+        order_fee,
+        slippage,
+        cost_timing,
+        fee_settlement,
+        account_mode,
+        execution_mode,
+        limit_fill_mode,
+        stop_trigger,
+        max_participation,
+        market_tif,
+        bracket_fill_policy,
+        signal_delay_bars,
+        market_slippage_ticks,
+        placement_check,
+        max_pyramid_entries,
+        vol_target_risk,
+        vol_target_atr,
+        warmup_bars,
+        warmup_days,
+        min_bars_between_trades,
+        max_drawdown_stop,
+        equity_floor,
+        no_equity_floor,
+        roll_dates,
+        mark_price,
+        signal_fill,
+        drawdown_mode,
+        intrabar_drawdown,
+        periods_per_year,
+        seed,
+        currency,
+        strategy_args,
+        seasonality: print_seasonality,
+        output_result_json,
+        output_equity_csv,
+        output_trades_csv,
+        output_round_trips_csv,
+        output_drawdown_csv,
+        output_tear_sheet,
+    } = args;
+
+    let currency = CurrencyFormat::parse(&currency)
+        .ok_or_else(|| anyhow::anyhow!("Unknown currency: {}", currency))?;
+
+    println!("Menudo Futures Backtesting Engine");
+    println!("==================================\n");
+
+    //contract specification
+    let contract_kind = ContractKind::parse(&contract_kind)
+        .ok_or_else(|| anyhow::anyhow!("Unknown contract kind: {}", contract_kind))?;
+    let pnl_method = PnlMethod::parse(&pnl_method)
+        .ok_or_else(|| anyhow::anyhow!("Unknown pnl method: {}", pnl_method))?;
+    let contract_config = ContractConfig {
+        symbol: symbol.clone(),
+        contract_month,
+        tick_size,
+        tick_value,
+        point_value,
+        initial_margin,
+        maintenance_margin,
+        multiplier,
+        kind: contract_kind,
+        pnl_method,
+    };
+    let contract = contract_config.to_futures_contract();
 
     println!(
-        "Contract: {} (tick: ${}, value: ${})",
-        contract.symbol, contract.tick_size, contract.tick_value
+        "Contract: {} (tick: {}, value: {}, multiplier: {})",
+        contract.symbol,
+        contract.tick_size,
+        currency.format(contract.tick_value),
+        contract.multiplier
+    );
+    println!(
+        "Initial margin: {}\n",
+        currency.format(contract.initial_margin)
     );
-    println!("Initial margin: ${:.2}\n", contract.initial_margin);
 
-    //create strategy
-    let strategy_type = StrategyType::parse(&strategy_name)
-        .ok_or_else(|| anyhow::anyhow!("Unknown strategy: {}", strategy_name))?;
+    //strategy
+    let params = strategy_args.to_params(&strategy_name)?;
+    params.validate()?;
 
-    let mut strategy: Box<dyn Strategy> = match strategy_type {
-        StrategyType::SmaCrossover => {
-            let fast = fast.ok_or_else(|| anyhow::anyhow!("--fast required for SMA strategy"))?;
-            let slow = slow.ok_or_else(|| anyhow::anyhow!("--slow required for SMA strategy"))?;
+    match &params {
+        StrategyParams::Sma(p) => println!(
+            "Strategy: SMA Crossover (fast={}, slow={}, trend filter={:?}, stop={:?}, target={:?})",
+            p.fast_window, p.slow_window, p.trend_window, p.stop_loss_ticks, p.take_profit_ticks
+        ),
+        StrategyParams::Rsi(p) => println!(
+            "Strategy: RSI Reversion (lookback={}, lower={}, upper={}, confirmation={}, stop={:?}, target={:?})",
+            p.lookback,
+            p.oversold,
+            p.overbought,
+            p.confirmation_bars,
+            p.stop_loss_ticks,
+            p.take_profit_ticks
+        ),
+        StrategyParams::Keltner(p) => println!(
+            "Strategy: Keltner Channel (ema={}, atr={}, multiplier={}, mode={:?})",
+            p.ema_period, p.atr_period, p.multiplier, p.mode
+        ),
+        StrategyParams::Custom(p) => println!("Strategy: {} ({:?})", p.name, p.params),
+    }
 
-            println!("Strategy: SMA Crossover (fast={}, slow={})", fast, slow);
-            Box::new(SmaCrossoverStrategy::new(symbol.clone(), fast, slow, qty))
-        }
-        StrategyType::RsiReversion => {
-            let lookback = rsi_lookback.unwrap_or(14);
-            let lower = rsi_lower.unwrap_or(30.0);
-            let upper = rsi_upper.unwrap_or(70.0);
+    let cost_timing = CostTiming::parse(&cost_timing)
+        .ok_or_else(|| anyhow::anyhow!("Unknown cost timing: {}", cost_timing))?;
+    let fee_settlement = FeeSettlement::parse(&fee_settlement)
+        .ok_or_else(|| anyhow::anyhow!("Unknown fee settlement: {}", fee_settlement))?;
+    let account_mode = AccountMode::parse(&account_mode)
+        .ok_or_else(|| anyhow::anyhow!("Unknown account mode: {}", account_mode))?;
+    let execution_mode = ExecutionMode::parse(&execution_mode)
+        .ok_or_else(|| anyhow::anyhow!("Unknown execution mode: {}", execution_mode))?;
+    let limit_fill_mode = LimitFillMode::parse(&limit_fill_mode)
+        .ok_or_else(|| anyhow::anyhow!("Unknown limit fill mode: {}", limit_fill_mode))?;
+    let stop_trigger = StopTrigger::parse(&stop_trigger)
+        .ok_or_else(|| anyhow::anyhow!("Unknown stop trigger: {}", stop_trigger))?;
+    let market_tif = MarketTif::parse(&market_tif)
+        .ok_or_else(|| anyhow::anyhow!("Unknown market time in force: {}", market_tif))?;
+    let bracket_fill_policy = BracketFillPolicy::parse(&bracket_fill_policy)
+        .ok_or_else(|| anyhow::anyhow!("Unknown bracket fill policy: {}", bracket_fill_policy))?;
+    let placement_check = PlacementCheck::parse(&placement_check)
+        .ok_or_else(|| anyhow::anyhow!("Unknown placement check: {}", placement_check))?;
+    let mark_price = MarkPrice::parse(&mark_price)
+        .ok_or_else(|| anyhow::anyhow!("Unknown mark price: {}", mark_price))?;
+    let signal_fill_mode = SignalFillMode::parse(&signal_fill)
+        .ok_or_else(|| anyhow::anyhow!("Unknown signal fill mode: {}", signal_fill))?;
+    let drawdown_mode = DrawdownMode::parse(&drawdown_mode)
+        .ok_or_else(|| anyhow::anyhow!("Unknown drawdown mode: {}", drawdown_mode))?;
 
-            println!(
-                "Strategy: RSI Reversion (lookback={}, lower={}, upper={})",
-                lookback, lower, upper
-            );
-            Box::new(RsiReversionStrategy::new(
-                symbol.clone(),
-                lookback,
-                lower,
-                upper,
-                qty,
-            ))
+    match strategy_args.size {
+        Some(fraction) if strategy_args.size_mode != "fixed" => {
+            println!("Sizing: {:.1}% of equity per entry", fraction * 100.0)
         }
-    };
-
-    println!("Quantity: {} contract(s)", qty);
-    println!("Initial balance: ${:.2}", initial_balance);
-    println!("Commission: ${:.2} per contract", commission);
-    println!("Slippage: ${:.2} per contract\n", slippage);
+        _ => println!("Quantity: {} contract(s)", strategy_args.qty),
+    }
+    println!("Initial balance: {}", currency.format(initial_balance));
+    println!("Commission: {} per contract", currency.format(commission));
+    if min_commission > 0.0 || order_fee > 0.0 {
+        println!(
+            "Order commission: {} minimum, {} ticket fee",
+            currency.format(min_commission),
+            currency.format(order_fee)
+        );
+    }
+    println!("Slippage: {} per contract", currency.format(slippage));
+    println!("Cost timing: {:?}", cost_timing);
+    println!("Fee settlement: {:?}", fee_settlement);
+    println!("Account mode: {:?}", account_mode);
+    println!("Execution mode: {:?}", execution_mode);
+    println!("Limit fill mode: {:?}", limit_fill_mode);
+    let vol_target = vol_target_risk.map(|risk| VolTarget::new(risk, vol_target_atr));
+    if let Some(target) = vol_target {
+        println!(
+            "Volatility target: {} per {}-bar atr",
+            currency.format(target.risk),
+            target.atr_period
+        );
+    }
+    println!("Stop trigger: {:?}", stop_trigger);
+    if let Some(fraction) = max_participation {
+        println!(
+            "Max participation: {:.1}% of bar volume, remainder {:?}",
+            fraction * 100.0,
+            market_tif
+        );
+    }
+    println!("Bracket fill policy: {:?}", bracket_fill_policy);
+    if signal_delay_bars > 0 {
+        println!("Signal delay: {} bar(s)", signal_delay_bars);
+    }
+    if market_slippage_ticks > 0 {
+        println!("Market slippage: {} tick(s)", market_slippage_ticks);
+    }
+    println!("Placement check: {:?}", placement_check);
+    if warmup_bars > 0 {
+        println!("Warmup: {} bar(s)", warmup_bars);
+    }
+    if let Some(days) = warmup_days {
+        println!("Warmup: {} day(s)", days);
+    }
+    if let Some(min_bars) = min_bars_between_trades {
+        println!("Entry cooldown: {} bar(s) after a fill", min_bars);
+    }
+    println!("Mark price: {:?}", mark_price);
+    println!("Signal fill: {:?}", signal_fill_mode);
+    println!("Drawdown mode: {:?}", drawdown_mode);
+    println!("Intrabar drawdown: {}", intrabar_drawdown);
+    println!("Seed: {}", seed);
+    match periods_per_year {
+        Some(periods) => println!("Periods per year: {}\n", periods),
+        None => println!("Periods per year: inferred from bar spacing\n"),
+    }
 
-    //create backtest config
-    let config = BacktestConfig {
+    //create backtest configuration
+    let config = BacktestConfiguration {
+        data_path: data_path.clone(),
+        symbol: symbol.clone(),
+        start_date,
+        end_date,
+        loader: loader_args.to_options(),
+        contract: contract_config,
         initial_balance,
         commission_per_contract: commission,
+        min_commission_per_order: min_commission,
+        commission_per_order: order_fee,
         slippage_per_contract: slippage,
+        cost_timing,
+        fee_settlement,
+        account_mode,
         max_lookback: 500,
+        warmup_bars,
+        warmup_days,
+        execution_mode,
+        limit_fill_mode,
+        stop_trigger,
+        max_participation,
+        market_tif,
+        bracket_fill_policy,
+        signal_delay_bars,
+        market_slippage_ticks,
+        placement_check,
+        max_pyramid_entries,
+        vol_target,
+        min_bars_between_trades,
+        max_drawdown_stop,
+        equity_floor: (!no_equity_floor).then_some(equity_floor),
+        roll_dates,
+        mark_price,
+        signal_fill_mode,
+        drawdown_mode,
+        intrabar_drawdown,
+        currency: currency.clone(),
+        periods_per_year,
+        seed,
+        strategy_type: params.strategy_type(),
+        strategy_params: params,
+        output_equity_csv: output_equity_csv.clone(),
+        output_trades_csv: output_trades_csv.clone(),
     };
 
-    //run backtest
-    println!("Running backtest...\n");
-    let mut engine = BacktestEngine::new(config, bars, contract);
-    let result = engine.run(&mut strategy);
+    //load data and run backtest
+    println!("Loading data from {:?} and running backtest...", data_path);
+    let result = menudo::run(config)?;
+
+    if let (Some(first), Some(last)) = (result.equity_curve.first(), result.equity_curve.last()) {
+        println!("Ran {} bars for {}", result.equity_curve.len(), symbol);
+        println!("Date range: {} to {}\n", first.timestamp, last.timestamp);
+    }
 
     //display results
     println!("Backtest Results");
     println!("================\n");
     result.summary.pretty_print_table();
 
+    let summary = &result.summary;
+    if summary.fees_exceed_gross_profit() {
+        println!(
+            "\nWARNING: fees ({}) exceed gross profit ({}) - the strategy only loses because of costs, check for overtrading",
+            summary.currency.format(summary.total_fees),
+            summary.currency.format(summary.gross_pnl)
+        );
+    }
+
+    if let Some(worst) = worst_drawdown(&result.equity_curve) {
+        println!(
+            "\nWorst drawdown: {:.2}% from {} to a trough on {}, {}",
+            worst.depth * 100.0,
+            worst.start.date_naive(),
+            worst.trough.date_naive(),
+            worst
+                .end
+                .map(|end| format!("recovered {}", end.date_naive()))
+                .unwrap_or_else(|| "not recovered".to_string())
+        );
+    }
+
+    if let Some(halted_at) = result.halted_at {
+        if result.bankrupt {
+            println!(
+                "\nAccount bankrupt at {} - equity reached the floor, trading halted",
+                halted_at
+            );
+        } else {
+            println!("\nDrawdown stop hit at {} - trading halted", halted_at);
+        }
+    }
+
+    let order_stats = result.order_stats;
+    println!(
+        "\nOrders: {} submitted, {} filled ({:.1}%), {} cancelled, {} expired, {} order-bars pending unfilled",
+        order_stats.submitted,
+        order_stats.filled,
+        order_stats.fill_rate() * 100.0,
+        order_stats.cancelled,
+        order_stats.expired,
+        order_stats.unfilled_order_bars
+    );
+
+    if result.rolls > 0 {
+        println!("\nPosition rolled {} time(s)", result.rolls);
+    }
+
+    if print_seasonality {
+        println!("\nAverage net P&L per trade by entry day/hour (UTC), trade count in parentheses");
+        seasonality(&result.round_trips()).pretty_print_table();
+    }
+
     //save outputs if requested
+    if let Some(result_path) = output_result_json {
+        result.save(&result_path)?;
+        println!("\nResult saved to {:?}", result_path);
+    }
+
     if let Some(equity_path) = output_equity_csv {
-        save_equity_csv(&result.equity_curve, &equity_path)?;
+        save_equity_csv(&result.equity_curve, &equity_path, &currency)?;
         println!("\nEquity curve saved to {:?}", equity_path);
     }
 
     if let Some(trades_path) = output_trades_csv {
-        save_trades_csv(&result.trades, &trades_path)?;
+        save_trades_csv(&result.trades, &trades_path, &currency)?;
         println!("Trades saved to {:?}", trades_path);
     }
 
+    if let Some(round_trips_path) = output_round_trips_csv {
+        write_round_trips_csv(&result.round_trips(), &round_trips_path)?;
+        println!("Round trips saved to {:?}", round_trips_path);
+    }
+
+    if let Some(drawdown_path) = output_drawdown_csv {
+        write_drawdown_csv(&result.equity_curve, &drawdown_path)?;
+        println!("Drawdown curve saved to {:?}", drawdown_path);
+    }
+
+    if let Some(tear_sheet_path) = output_tear_sheet {
+        save_tear_sheet(&result, &tear_sheet_path)?;
+        println!("Tear sheet saved to {:?}", tear_sheet_path);
+    }
+
+    Ok(())
+}
+
+//writes the tear sheet as html for a .html path, as text otherwise
+fn save_tear_sheet(result: &BacktestResult, path: &PathBuf) -> Result<()> {
+    let is_html = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("html"));
+
+    let report = if is_html {
+        #[cfg(feature = "html-report")]
+        {
+            tear_sheet_html(result)
+        }
+        #[cfg(not(feature = "html-report"))]
+        {
+            anyhow::bail!("HTML tear sheets need the html-report feature");
+        }
+    } else {
+        tear_sheet(result)
+    };
+
+    std::fs::write(path, report)?;
     Ok(())
 }
 
-fn save_equity_csv(equity_curve: &[EquityPoint], path: &PathBuf) -> Result<()> {
+fn save_equity_csv(
+    equity_curve: &[EquityPoint],
+    path: &PathBuf,
+    currency: &CurrencyFormat,
+) -> Result<()> {
     use std::io::Write;
 
     let mut file = std::fs::File::create(path)?;
-    writeln!(file, "timestamp,equity,drawdown,returns")?;
+    writeln!(file, "timestamp,equity,drawdown,returns,currency")?;
 
     for point in equity_curve {
         writeln!(
             file,
-            "{},{},{},{}",
+            "{},{},{},{},{}",
             point.timestamp.to_rfc3339(),
             point.equity,
             point.drawdown,
-            point.returns
+            point.returns,
+            currency.code
         )?;
     }
 
     Ok(())
 }
 
-fn save_trades_csv(trades: &[Fill], path: &PathBuf) -> Result<()> {
+fn save_trades_csv(trades: &[Fill], path: &PathBuf, currency: &CurrencyFormat) -> Result<()> {
     use std::io::Write;
 
     let mut file = std::fs::File::create(path)?;
     writeln!(
         file,
-        "id,order_id,timestamp,symbol,qty,side,fill_price,fees"
+        "id,order_id,timestamp,symbol,qty,side,fill_price,fees,commission,slippage,currency,exit_reason"
     )?;
 
     for trade in trades {
         writeln!(
             file,
-            "{},{},{},{},{},{:?},{},{}",
+            "{},{},{},{},{},{:?},{},{},{},{},{},{}",
             trade.id,
             trade.order_id,
             trade.timestamp.to_rfc3339(),
@@ -325,7 +1090,14 @@ fn save_trades_csv(trades: &[Fill], path: &PathBuf) -> Result<()> {
             trade.qty,
             trade.side,
             trade.fill_price,
-            trade.fees
+            trade.fees(),
+            trade.commission,
+            trade.slippage,
+            currency.code,
+            trade
+                .exit_reason
+                .map(|reason| format!("{:?}", reason))
+                .unwrap_or_default()
         )?;
     }
 
@@ -0,0 +1,241 @@
+use crate::metrics::summary::{SummaryMetrics, RATIO_CAP};
+use crate::metrics::timeseries::DrawdownMode;
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use statrs::statistics::Statistics;
+
+//distribution of one metric across a batch of backtests
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricStats {
+    pub mean: f64,
+    pub median: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    //runs left out because the ratio was at RATIO_CAP (unbounded, eg no losing trades)
+    #[serde(default)]
+    pub capped: usize,
+}
+
+impl MetricStats {
+    //computes stats over the finite values (nan/infinite values are ignored)
+    pub fn from_values(values: &[f64]) -> Self {
+        let mut finite: Vec<f64> = values.iter().copied().filter(|v| v.is_finite()).collect();
+
+        if finite.is_empty() {
+            return MetricStats::default();
+        }
+
+        finite.sort_by(|a, b| a.total_cmp(b));
+
+        let mid = finite.len() / 2;
+        let median = if finite.len().is_multiple_of(2) {
+            (finite[mid - 1] + finite[mid]) / 2.0
+        } else {
+            finite[mid]
+        };
+
+        //sample std dev is undefined for a single value
+        let std_dev = if finite.len() >= 2 {
+            finite.iter().std_dev()
+        } else {
+            0.0
+        };
+
+        MetricStats {
+            mean: finite.iter().mean(),
+            median,
+            std_dev,
+            min: finite[0],
+            max: finite[finite.len() - 1],
+            capped: 0,
+        }
+    }
+
+    //computes stats over an unbounded ratio, leaving out (and counting) the capped values
+    //so a single run without losses doesn't drag the mean and spread towards the cap
+    pub fn from_ratios(values: &[f64]) -> Self {
+        let bounded: Vec<f64> = values.iter().copied().filter(|&v| v < RATIO_CAP).collect();
+        MetricStats {
+            capped: values.len() - bounded.len(),
+            ..MetricStats::from_values(&bounded)
+        }
+    }
+}
+
+//summary-of-summaries across a batch of backtests (optimization, walk-forward)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AggregateMetrics {
+    pub count: usize,
+    pub total_return_pct: MetricStats,
+    pub cagr: MetricStats,
+    pub max_drawdown: MetricStats,
+    pub sharpe_ratio: MetricStats,
+    pub sortino_ratio: MetricStats,
+    pub win_rate: MetricStats,
+    pub profit_factor: MetricStats,
+    pub num_trades: MetricStats,
+    //drawdown mode of the runs (taken from the first run)
+    #[serde(default)]
+    pub drawdown_mode: DrawdownMode,
+
+    //indices into the input slice of the best and worst runs by sharpe ratio
+    pub best_index: Option<usize>,
+    pub worst_index: Option<usize>,
+}
+
+//aggregates summary metrics from a batch of backtests
+pub fn aggregate_metrics(results: &[SummaryMetrics]) -> AggregateMetrics {
+    let collect = |f: fn(&SummaryMetrics) -> f64| -> MetricStats {
+        let values: Vec<f64> = results.iter().map(f).collect();
+        MetricStats::from_values(&values)
+    };
+    let collect_ratio = |f: fn(&SummaryMetrics) -> f64| -> MetricStats {
+        let values: Vec<f64> = results.iter().map(f).collect();
+        MetricStats::from_ratios(&values)
+    };
+
+    let by_sharpe = |a: &(usize, &SummaryMetrics), b: &(usize, &SummaryMetrics)| {
+        a.1.sharpe_ratio.total_cmp(&b.1.sharpe_ratio)
+    };
+    let best_index = results.iter().enumerate().max_by(by_sharpe).map(|(i, _)| i);
+    let worst_index = results.iter().enumerate().min_by(by_sharpe).map(|(i, _)| i);
+
+    AggregateMetrics {
+        count: results.len(),
+        total_return_pct: collect(|m| m.total_return_pct),
+        cagr: collect(|m| m.cagr),
+        max_drawdown: collect(|m| m.max_drawdown),
+        sharpe_ratio: collect(|m| m.sharpe_ratio),
+        sortino_ratio: collect_ratio(|m| m.sortino_ratio),
+        win_rate: collect(|m| m.win_rate),
+        profit_factor: collect_ratio(|m| m.profit_factor),
+        num_trades: collect(|m| m.num_trades as f64),
+        drawdown_mode: results.first().map(|m| m.drawdown_mode).unwrap_or_default(),
+        best_index,
+        worst_index,
+    }
+}
+
+impl AggregateMetrics {
+    //prints the aggregate in a formatted table
+    pub fn pretty_print_table(&self) {
+        self.to_table().printstd();
+    }
+
+    //builds the metric / mean / median / std / min / max table
+    //followed by the run count and the best and worst runs by sharpe
+    pub fn to_table(&self) -> Table {
+        let mut table = Table::new();
+
+        table.add_row(Row::new(vec![
+            Cell::new("Metric"),
+            Cell::new("Mean"),
+            Cell::new("Median"),
+            Cell::new("Std Dev"),
+            Cell::new("Min"),
+            Cell::new("Max"),
+        ]));
+
+        let drawdown_row = if self.drawdown_mode.is_dollar() {
+            ("Max Drawdown $", &self.max_drawdown, 1.0)
+        } else {
+            ("Max Drawdown %", &self.max_drawdown, 100.0)
+        };
+
+        let rows = [
+            ("Total Return %", &self.total_return_pct, 100.0),
+            ("CAGR %", &self.cagr, 1.0),
+            drawdown_row,
+            ("Sharpe Ratio", &self.sharpe_ratio, 1.0),
+            ("Sortino Ratio", &self.sortino_ratio, 1.0),
+            ("Win Rate %", &self.win_rate, 100.0),
+            ("Profit Factor", &self.profit_factor, 1.0),
+            ("Number of Trades", &self.num_trades, 1.0),
+        ];
+
+        for (name, stats, scale) in rows {
+            let name = if stats.capped > 0 {
+                format!("{} ({} unbounded excluded)", name, stats.capped)
+            } else {
+                name.to_string()
+            };
+            table.add_row(Row::new(vec![
+                Cell::new(&name),
+                Cell::new(&format!("{:.3}", stats.mean * scale)),
+                Cell::new(&format!("{:.3}", stats.median * scale)),
+                Cell::new(&format!("{:.3}", stats.std_dev * scale)),
+                Cell::new(&format!("{:.3}", stats.min * scale)),
+                Cell::new(&format!("{:.3}", stats.max * scale)),
+            ]));
+        }
+
+        table.add_row(Row::new(vec![
+            Cell::new("Runs"),
+            Cell::new(&self.count.to_string()),
+        ]));
+        if let (Some(best), Some(worst)) = (self.best_index, self.worst_index) {
+            table.add_row(Row::new(vec![
+                Cell::new("Best run (by Sharpe)"),
+                Cell::new(&format!("#{}", best)),
+            ]));
+            table.add_row(Row::new(vec![
+                Cell::new("Worst run (by Sharpe)"),
+                Cell::new(&format!("#{}", worst)),
+            ]));
+        }
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(sharpe_ratio: f64, num_trades: usize) -> SummaryMetrics {
+        SummaryMetrics {
+            sharpe_ratio,
+            num_trades,
+            ..SummaryMetrics::default()
+        }
+    }
+
+    #[test]
+    fn stats_across_runs() {
+        let runs = [metrics(2.0, 10), metrics(1.0, 30), metrics(3.0, 20)];
+        let aggregate = aggregate_metrics(&runs);
+
+        assert_eq!(aggregate.count, 3);
+        let sharpe = &aggregate.sharpe_ratio;
+        assert_eq!((sharpe.mean, sharpe.median), (2.0, 2.0));
+        assert_eq!((sharpe.min, sharpe.max), (1.0, 3.0));
+        //sample standard deviation of 1, 2, 3
+        assert!((sharpe.std_dev - 1.0).abs() < 1e-12);
+        assert_eq!(aggregate.num_trades.mean, 20.0);
+        assert_eq!(
+            (aggregate.best_index, aggregate.worst_index),
+            (Some(2), Some(1))
+        );
+    }
+
+    #[test]
+    fn nan_values_are_skipped() {
+        let stats = MetricStats::from_values(&[1.0, f64::NAN, 4.0, f64::INFINITY]);
+        assert_eq!((stats.mean, stats.median), (2.5, 2.5));
+        assert_eq!((stats.min, stats.max), (1.0, 4.0));
+
+        let single = MetricStats::from_values(&[f64::NAN, 5.0]);
+        assert_eq!((single.mean, single.std_dev), (5.0, 0.0));
+    }
+
+    #[test]
+    fn empty_input_gives_zeroed_stats() {
+        let aggregate = aggregate_metrics(&[]);
+        assert_eq!(aggregate.count, 0);
+        assert_eq!(aggregate.sharpe_ratio.mean, 0.0);
+        assert_eq!(aggregate.best_index, None);
+        assert_eq!(aggregate.worst_index, None);
+        assert_eq!(MetricStats::from_values(&[f64::NAN]).max, 0.0);
+    }
+}
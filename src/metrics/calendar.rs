@@ -0,0 +1,200 @@
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+//trading days in a year, used to turn trading-day counts into years
+pub const TRADING_DAYS_PER_YEAR: f64 = 252.0;
+
+//calendar days in a year, for bars spaced a week or more apart
+const CALENDAR_DAYS_PER_YEAR: f64 = 365.25;
+
+//trading calendar that skips weekends and an optional list of holidays
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TradingCalendar {
+    holidays: BTreeSet<NaiveDate>,
+    //return periods per year used to annualize ratios (none = infer from the bar spacing)
+    #[serde(default)]
+    periods_per_year: Option<f64>,
+}
+
+impl TradingCalendar {
+    //creates a calendar that only skips weekends
+    pub fn new() -> Self {
+        TradingCalendar {
+            holidays: BTreeSet::new(),
+            periods_per_year: None,
+        }
+    }
+
+    //creates a calendar that skips weekends and the given holidays
+    pub fn with_holidays<I: IntoIterator<Item = NaiveDate>>(holidays: I) -> Self {
+        TradingCalendar {
+            holidays: holidays.into_iter().collect(),
+            periods_per_year: None,
+        }
+    }
+
+    //annualizes with a fixed number of return periods per year instead of inferring it
+    pub fn with_periods_per_year(mut self, periods_per_year: Option<f64>) -> Self {
+        self.periods_per_year = periods_per_year;
+        self
+    }
+
+    //returns the return periods per year for a series with these timestamps: the
+    //configured count, else the count inferred from the spacing, else daily bars
+    pub fn periods_per_year(&self, timestamps: &[DateTime<Utc>]) -> f64 {
+        self.periods_per_year
+            .or_else(|| infer_periods_per_year(timestamps))
+            .unwrap_or(TRADING_DAYS_PER_YEAR)
+    }
+
+    //returns true if the exchange trades on the date
+    pub fn is_trading_day(&self, date: NaiveDate) -> bool {
+        !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) && !self.holidays.contains(&date)
+    }
+
+    //counts trading days after start up to and including end
+    pub fn trading_days_between(&self, start: NaiveDate, end: NaiveDate) -> usize {
+        start
+            .iter_days()
+            .skip(1)
+            .take_while(|date| *date <= end)
+            .filter(|date| self.is_trading_day(*date))
+            .count()
+    }
+
+    //returns the span between two dates in trading years
+    pub fn years_between(&self, start: NaiveDate, end: NaiveDate) -> f64 {
+        self.trading_days_between(start, end) as f64 / TRADING_DAYS_PER_YEAR
+    }
+}
+
+//infers how many bars a year holds from the median gap between timestamps
+//intraday bars: the median number of bars per trading day times the trading days per year
+//(so session length and overnight gaps don't matter); daily bars up to a week apart count
+//trading days; bars a week or more apart (weekly, monthly) count calendar days
+//returns none for fewer than two distinct timestamps
+pub fn infer_periods_per_year(timestamps: &[DateTime<Utc>]) -> Option<f64> {
+    let mut gaps: Vec<i64> = timestamps
+        .windows(2)
+        .map(|w| (w[1] - w[0]).num_seconds())
+        .filter(|&gap| gap > 0)
+        .collect();
+    if gaps.is_empty() {
+        return None;
+    }
+    gaps.sort_unstable();
+    let median_days = gaps[gaps.len() / 2] as f64 / 86_400.0;
+
+    //shorter than a day, with slack for daylight-saving shifts in daily bars
+    if median_days < 20.0 / 24.0 {
+        let mut bars_per_day: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+        for timestamp in timestamps {
+            *bars_per_day.entry(timestamp.date_naive()).or_default() += 1;
+        }
+        let mut counts: Vec<usize> = bars_per_day.into_values().collect();
+        counts.sort_unstable();
+        Some(counts[counts.len() / 2] as f64 * TRADING_DAYS_PER_YEAR)
+    } else if median_days < 7.0 {
+        Some(TRADING_DAYS_PER_YEAR / median_days.round())
+    } else {
+        Some(CALENDAR_DAYS_PER_YEAR / median_days)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn counts_weekdays_after_start() {
+        let calendar = TradingCalendar::new();
+
+        //fri to mon skips the weekend
+        assert_eq!(
+            calendar.trading_days_between(date(2024, 1, 5), date(2024, 1, 8)),
+            1
+        );
+        assert_eq!(
+            calendar.trading_days_between(date(2024, 1, 1), date(2024, 12, 18)),
+            252
+        );
+        assert_eq!(
+            calendar.years_between(date(2024, 1, 1), date(2024, 12, 18)),
+            1.0
+        );
+        assert_eq!(
+            calendar.trading_days_between(date(2024, 1, 8), date(2024, 1, 8)),
+            0
+        );
+    }
+
+    #[test]
+    fn skips_holidays() {
+        let calendar = TradingCalendar::with_holidays([date(2024, 1, 15)]);
+
+        assert!(!calendar.is_trading_day(date(2024, 1, 15)));
+        assert!(!calendar.is_trading_day(date(2024, 1, 13)));
+        assert_eq!(
+            calendar.trading_days_between(date(2024, 1, 12), date(2024, 1, 19)),
+            4
+        );
+    }
+
+    fn times(start: DateTime<Utc>, step: chrono::Duration, n: i64) -> Vec<DateTime<Utc>> {
+        (0..n).map(|i| start + step * i as i32).collect()
+    }
+
+    fn start() -> DateTime<Utc> {
+        date(2024, 1, 2).and_hms_opt(14, 30, 0).unwrap().and_utc()
+    }
+
+    #[test]
+    fn infers_minute_bars_from_bars_per_session() {
+        //three 390-minute sessions with overnight gaps between them
+        let timestamps: Vec<_> = (0..3)
+            .flat_map(|day| {
+                times(
+                    start() + chrono::Duration::days(day),
+                    chrono::Duration::minutes(1),
+                    390,
+                )
+            })
+            .collect();
+
+        assert_eq!(infer_periods_per_year(&timestamps), Some(390.0 * 252.0));
+    }
+
+    #[test]
+    fn infers_daily_and_weekly_bars() {
+        //weekdays only: the weekend gaps don't move the median
+        let weekdays: Vec<_> = times(start(), chrono::Duration::days(1), 28)
+            .into_iter()
+            .filter(|t| !matches!(t.weekday(), Weekday::Sat | Weekday::Sun))
+            .collect();
+        assert_eq!(infer_periods_per_year(&weekdays), Some(252.0));
+
+        let weekly = times(start(), chrono::Duration::weeks(1), 10);
+        assert_eq!(infer_periods_per_year(&weekly), Some(365.25 / 7.0));
+    }
+
+    #[test]
+    fn configured_periods_override_inference() {
+        let daily = times(start(), chrono::Duration::days(1), 10);
+
+        assert_eq!(TradingCalendar::new().periods_per_year(&daily), 252.0);
+        assert_eq!(
+            TradingCalendar::new()
+                .with_periods_per_year(Some(12.0))
+                .periods_per_year(&daily),
+            12.0
+        );
+        //too few bars to infer from falls back to daily
+        assert_eq!(infer_periods_per_year(&daily[..1]), None);
+        assert_eq!(TradingCalendar::new().periods_per_year(&daily[..1]), 252.0);
+    }
+}
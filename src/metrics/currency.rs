@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+
+//currency and number formatting for monetary output
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CurrencyFormat {
+    //iso currency code, e.g. usd
+    pub code: String,
+    pub symbol: String,
+    //place the symbol after the number (1.234,56 €) instead of before ($1234.56)
+    pub symbol_after: bool,
+    pub thousands_separator: Option<char>,
+    pub decimal_separator: char,
+    pub decimals: usize,
+}
+
+impl Default for CurrencyFormat {
+    fn default() -> Self {
+        CurrencyFormat {
+            code: "USD".to_string(),
+            symbol: "$".to_string(),
+            symbol_after: false,
+            thousands_separator: None,
+            decimal_separator: '.',
+            decimals: 2,
+        }
+    }
+}
+
+impl CurrencyFormat {
+    //parse a currency preset from its code
+    pub fn parse(s: &str) -> Option<Self> {
+        let preset =
+            |code: &str, symbol: &str, after: bool, thousands, decimal, decimals| CurrencyFormat {
+                code: code.to_string(),
+                symbol: symbol.to_string(),
+                symbol_after: after,
+                thousands_separator: thousands,
+                decimal_separator: decimal,
+                decimals,
+            };
+
+        match s.to_lowercase().as_str() {
+            "usd" => Some(CurrencyFormat::default()),
+            "eur" => Some(preset("EUR", "€", true, Some('.'), ',', 2)),
+            "gbp" => Some(preset("GBP", "£", false, Some(','), '.', 2)),
+            "jpy" => Some(preset("JPY", "¥", false, Some(','), '.', 0)),
+            "chf" => Some(preset("CHF", "CHF ", false, Some('\''), '.', 2)),
+            _ => None,
+        }
+    }
+
+    //formats an amount with the currency symbol and separators
+    //rounds first, so residuals like -0.001 print as zero rather than -0.00
+    pub fn format(&self, amount: f64) -> String {
+        let amount = round_to(amount, self.decimals);
+        let sign = if amount < 0.0 { "-" } else { "" };
+        let fixed = format!("{:.*}", self.decimals, amount.abs());
+
+        let (int_part, frac_part) = match fixed.split_once('.') {
+            Some((int_part, frac_part)) => (int_part, Some(frac_part)),
+            None => (fixed.as_str(), None),
+        };
+
+        let mut number = match self.thousands_separator {
+            Some(separator) => group_digits(int_part, separator),
+            None => int_part.to_string(),
+        };
+        if let Some(frac_part) = frac_part {
+            number.push(self.decimal_separator);
+            number.push_str(frac_part);
+        }
+
+        if self.symbol_after {
+            format!("{}{} {}", sign, number, self.symbol)
+        } else {
+            format!("{}{}{}", sign, self.symbol, number)
+        }
+    }
+}
+
+//rounds a value to the given number of decimals, normalizing -0.0 to 0.0
+pub fn round_to(value: f64, decimals: usize) -> f64 {
+    let scale = 10f64.powi(decimals as i32);
+    let rounded = (value * scale).round() / scale;
+    if rounded == 0.0 {
+        0.0
+    } else {
+        rounded
+    }
+}
+
+//rounds a money amount to cents, clearing floating-point residue from summed fills
+pub fn round_to_cents(amount: f64) -> f64 {
+    round_to(amount, 2)
+}
+
+//inserts a separator between every group of three digits
+fn group_digits(digits: &str, separator: char) -> String {
+    let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            grouped.push(separator);
+        }
+        grouped.push(c);
+    }
+
+    grouped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn residuals_round_to_an_unsigned_zero() {
+        assert_eq!(round_to_cents(-0.004).to_bits(), 0.0f64.to_bits());
+        assert_eq!(round_to_cents(0.1 + 0.2), 0.3);
+        assert_eq!(CurrencyFormat::default().format(-0.001), "$0.00");
+        assert_eq!(CurrencyFormat::default().format(-1234.567), "-$1234.57");
+    }
+}
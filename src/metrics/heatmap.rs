@@ -0,0 +1,217 @@
+use crate::metrics::summary::SummaryMetrics;
+use crate::strategy::registry::ParamMap;
+use anyhow::{anyhow, bail, Result};
+use std::io::Write;
+use std::path::Path;
+
+//returns the named summary metric used as a sweep objective
+pub fn objective_value(metrics: &SummaryMetrics, objective: &str) -> Option<f64> {
+    let value = match objective.to_lowercase().as_str() {
+        "sharpe" | "sharpe_ratio" => metrics.sharpe_ratio,
+        "sortino" | "sortino_ratio" => metrics.sortino_ratio,
+        "cagr" => metrics.cagr,
+        "total_return" => metrics.total_return,
+        "total_return_pct" | "return" => metrics.total_return_pct,
+        "max_drawdown" | "drawdown" => metrics.max_drawdown,
+        "annual_volatility" | "volatility" => metrics.annual_volatility,
+        "profit_factor" => metrics.profit_factor,
+        "win_rate" => metrics.win_rate,
+        "num_trades" | "trades" => metrics.num_trades as f64,
+        "median_trade" => metrics.median_trade,
+        "max_concurrent_contracts" | "max_contracts" => metrics.max_concurrent_contracts as f64,
+        _ => return None,
+    };
+    Some(value)
+}
+
+//whether a smaller value of the objective is better (drawdown and volatility)
+pub fn objective_minimized(objective: &str) -> bool {
+    matches!(
+        objective.to_lowercase().as_str(),
+        "max_drawdown" | "drawdown" | "annual_volatility" | "volatility"
+    )
+}
+
+//objective values of a two-parameter sweep laid out on a grid
+//cells[row][col] is the value at (y_values[row], x_values[col]), none where the sweep skipped it
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    pub x_param: String,
+    pub y_param: String,
+    pub objective: String,
+    pub x_values: Vec<f64>,
+    pub y_values: Vec<f64>,
+    pub cells: Vec<Vec<Option<f64>>>,
+}
+
+impl Heatmap {
+    //builds the grid from sweep results (parameters and metrics of each run)
+    //runs that share an (x, y) point because other parameters varied are averaged
+    pub fn from_grid(
+        grid_results: &[(ParamMap, SummaryMetrics)],
+        x_param: &str,
+        y_param: &str,
+        objective: &str,
+    ) -> Result<Self> {
+        let mut points = Vec::with_capacity(grid_results.len());
+        for (params, metrics) in grid_results {
+            let x = *params
+                .get(x_param)
+                .ok_or_else(|| anyhow!("Sweep result is missing parameter {}", x_param))?;
+            let y = *params
+                .get(y_param)
+                .ok_or_else(|| anyhow!("Sweep result is missing parameter {}", y_param))?;
+            let value = objective_value(metrics, objective)
+                .ok_or_else(|| anyhow!("Unknown objective: {}", objective))?;
+            points.push((x, y, value));
+        }
+
+        if points.is_empty() {
+            bail!("No sweep results to build a heatmap from");
+        }
+
+        let x_values = distinct_sorted(points.iter().map(|p| p.0));
+        let y_values = distinct_sorted(points.iter().map(|p| p.1));
+
+        let mut sums = vec![vec![(0.0, 0usize); x_values.len()]; y_values.len()];
+        for (x, y, value) in points {
+            let col = position_of(&x_values, x);
+            let row = position_of(&y_values, y);
+            sums[row][col].0 += value;
+            sums[row][col].1 += 1;
+        }
+
+        let cells = sums
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .map(|(sum, n)| (n > 0).then(|| sum / n as f64))
+                    .collect()
+            })
+            .collect();
+
+        Ok(Heatmap {
+            x_param: x_param.to_string(),
+            y_param: y_param.to_string(),
+            objective: objective.to_string(),
+            x_values,
+            y_values,
+            cells,
+        })
+    }
+
+    //writes the grid as a csv matrix: x values across the header, one row per y value
+    //missing cells are left empty
+    pub fn write_csv(&self, path: &Path) -> Result<()> {
+        let mut file = std::fs::File::create(path)?;
+
+        let header: Vec<String> = self.x_values.iter().map(|x| x.to_string()).collect();
+        writeln!(
+            file,
+            "{}\\{},{}",
+            self.y_param,
+            self.x_param,
+            header.join(",")
+        )?;
+
+        for (y, row) in self.y_values.iter().zip(&self.cells) {
+            let cells: Vec<String> = row
+                .iter()
+                .map(|cell| cell.map(|v| v.to_string()).unwrap_or_default())
+                .collect();
+            writeln!(file, "{},{}", y, cells.join(","))?;
+        }
+
+        Ok(())
+    }
+}
+
+//exports a two-parameter sweep's objective as a csv matrix for heatmap plotting
+pub fn heatmap_csv(
+    grid_results: &[(ParamMap, SummaryMetrics)],
+    x_param: &str,
+    y_param: &str,
+    objective: &str,
+    path: &Path,
+) -> Result<()> {
+    Heatmap::from_grid(grid_results, x_param, y_param, objective)?.write_csv(path)
+}
+
+fn distinct_sorted(values: impl Iterator<Item = f64>) -> Vec<f64> {
+    let mut values: Vec<f64> = values.collect();
+    values.sort_by(|a, b| a.total_cmp(b));
+    values.dedup_by(|a, b| a.total_cmp(b).is_eq());
+    values
+}
+
+//index of a value in its sorted distinct list; every point's value is in the list it was built from
+fn position_of(values: &[f64], value: f64) -> usize {
+    values.partition_point(|probe| probe.total_cmp(&value).is_lt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(x: f64, y: f64, other: f64, sharpe: f64) -> (ParamMap, SummaryMetrics) {
+        let params = ParamMap::from([
+            ("fast".to_string(), x),
+            ("slow".to_string(), y),
+            ("stop".to_string(), other),
+        ]);
+        let metrics = SummaryMetrics {
+            sharpe_ratio: sharpe,
+            ..SummaryMetrics::default()
+        };
+        (params, metrics)
+    }
+
+    fn sweep() -> Vec<(ParamMap, SummaryMetrics)> {
+        vec![
+            run(10.0, 50.0, 1.0, 0.5),
+            run(5.0, 20.0, 1.0, 1.0),
+            //same (x, y) point as the run above with another stop
+            run(5.0, 20.0, 2.0, 2.0),
+            run(10.0, 20.0, 1.0, -0.5),
+        ]
+    }
+
+    #[test]
+    fn grid_is_sorted_and_shared_points_are_averaged() {
+        let heatmap = Heatmap::from_grid(&sweep(), "fast", "slow", "sharpe").unwrap();
+
+        assert_eq!(heatmap.x_values, vec![5.0, 10.0]);
+        assert_eq!(heatmap.y_values, vec![20.0, 50.0]);
+        assert_eq!(
+            heatmap.cells,
+            vec![vec![Some(1.5), Some(-0.5)], vec![None, Some(0.5)]]
+        );
+    }
+
+    #[test]
+    fn csv_has_x_across_and_a_row_per_y() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("heatmap.csv");
+        heatmap_csv(&sweep(), "fast", "slow", "sharpe", &path).unwrap();
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(csv, "slow\\fast,5,10\n20,1.5,-0.5\n50,,0.5\n");
+    }
+
+    #[test]
+    fn missing_parameter_or_objective_is_an_error() {
+        assert!(Heatmap::from_grid(&sweep(), "fast", "lookback", "sharpe").is_err());
+        assert!(Heatmap::from_grid(&sweep(), "fast", "slow", "luck").is_err());
+        assert!(Heatmap::from_grid(&[], "fast", "slow", "sharpe").is_err());
+    }
+
+    #[test]
+    fn position_of_finds_every_listed_value() {
+        let values = distinct_sorted([3.0, -1.0, 2.5, 3.0, 0.0].into_iter());
+
+        assert_eq!(values, vec![-1.0, 0.0, 2.5, 3.0]);
+        for (i, &value) in values.iter().enumerate() {
+            assert_eq!(position_of(&values, value), i);
+        }
+    }
+}
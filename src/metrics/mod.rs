@@ -1,5 +1,31 @@
+pub mod aggregate;
+pub mod calendar;
+pub mod currency;
+pub mod heatmap;
+pub mod r_multiple;
+pub mod regression;
+pub mod seasonality;
+pub mod slippage;
 pub mod summary;
+pub mod tearsheet;
 pub mod timeseries;
 
-pub use summary::SummaryMetrics;
-pub use timeseries::{calculate_equity_curve, EquityPoint};
+pub use aggregate::{aggregate_metrics, AggregateMetrics, MetricStats};
+pub use calendar::{infer_periods_per_year, TradingCalendar, TRADING_DAYS_PER_YEAR};
+pub use currency::{round_to, round_to_cents, CurrencyFormat};
+pub use heatmap::{heatmap_csv, objective_minimized, objective_value, Heatmap};
+pub use r_multiple::{r_distribution, r_multiple_stats, RMultipleStats};
+pub use regression::{regression_stats, RegressionStats};
+pub use seasonality::{seasonality, BucketStats, Seasonality};
+pub use slippage::{slippage_distribution, slippage_stats, SlippageStats};
+pub use summary::{DirectionStats, MetricsError, SummaryMetrics, RATIO_CAP};
+#[cfg(feature = "html-report")]
+pub use tearsheet::tear_sheet_html;
+pub use tearsheet::{
+    drawdown_periods, monthly_returns, tear_sheet, worst_drawdown, write_drawdown_csv,
+    DrawdownPeriod,
+};
+pub use timeseries::{
+    calculate_equity_curve, calculate_equity_curve_intrabar, calculate_equity_curve_with_mode,
+    calculate_flow_adjusted_returns, time_weighted_return, DrawdownMode, EquityPoint,
+};
@@ -0,0 +1,126 @@
+use crate::portfolio::trade_log::RoundTrip;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+//round trip results in units of initial risk, over the trips that had a stop
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct RMultipleStats {
+    //round trips with a known initial risk
+    pub num_trades: usize,
+    //average r per trade, the r expectancy
+    pub expectancy: f64,
+    pub avg_win_r: f64,
+    pub avg_loss_r: f64,
+    pub best_r: f64,
+    pub worst_r: f64,
+}
+
+//summarizes the r-multiples of the round trips; trips without an initial stop are left out
+pub fn r_multiple_stats(trips: &[RoundTrip]) -> RMultipleStats {
+    let r_multiples: Vec<f64> = trips.iter().filter_map(|t| t.r_multiple()).collect();
+    if r_multiples.is_empty() {
+        return RMultipleStats::default();
+    }
+
+    let mean = |values: &[f64]| {
+        if values.is_empty() {
+            0.0
+        } else {
+            values.iter().sum::<f64>() / values.len() as f64
+        }
+    };
+    let wins: Vec<f64> = r_multiples.iter().copied().filter(|&r| r > 0.0).collect();
+    let losses: Vec<f64> = r_multiples.iter().copied().filter(|&r| r < 0.0).collect();
+
+    RMultipleStats {
+        num_trades: r_multiples.len(),
+        expectancy: mean(&r_multiples),
+        avg_win_r: mean(&wins),
+        avg_loss_r: mean(&losses),
+        best_r: r_multiples
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max),
+        worst_r: r_multiples.iter().copied().fold(f64::INFINITY, f64::min),
+    }
+}
+
+//counts round trips per whole-r bucket: bucket n holds r-multiples in [n, n + 1)
+//so -1 holds losses of up to one r and 0 small winners
+pub fn r_distribution(trips: &[RoundTrip]) -> BTreeMap<i64, usize> {
+    let mut buckets = BTreeMap::new();
+    for r in trips.iter().filter_map(|t| t.r_multiple()) {
+        *buckets.entry(r.floor() as i64).or_default() += 1;
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::execution::OrderSide;
+    use chrono::Utc;
+
+    fn trip(pnl: f64, fees: f64, initial_risk: Option<f64>) -> RoundTrip {
+        let now = Utc::now();
+        RoundTrip {
+            symbol: "ES".to_string(),
+            side: OrderSide::Buy,
+            qty: 1,
+            entry_time: now,
+            exit_time: now,
+            entry_price: 4000.0,
+            exit_price: 4000.0 + pnl / 50.0,
+            pnl,
+            fees,
+            exit_reason: None,
+            initial_risk,
+        }
+    }
+
+    #[test]
+    fn r_is_net_pnl_over_initial_risk() {
+        //a winner of two r and a loser of one r, both after fees
+        let winner = trip(1010.0, 10.0, Some(500.0));
+        let loser = trip(-495.0, 5.0, Some(500.0));
+
+        assert_eq!(winner.r_multiple(), Some(2.0));
+        assert_eq!(loser.r_multiple(), Some(-1.0));
+
+        let stats = r_multiple_stats(&[winner, loser]);
+        assert_eq!(stats.num_trades, 2);
+        assert_eq!(stats.avg_win_r, 2.0);
+        assert_eq!(stats.avg_loss_r, -1.0);
+        assert_eq!(stats.best_r, 2.0);
+        assert_eq!(stats.worst_r, -1.0);
+    }
+
+    #[test]
+    fn trips_without_a_stop_are_left_out() {
+        let trips = [
+            trip(1010.0, 10.0, Some(500.0)),
+            trip(-495.0, 5.0, Some(500.0)),
+            trip(-245.0, 5.0, Some(500.0)),
+            //a big stopless winner would skew every figure if it were counted
+            trip(5000.0, 10.0, None),
+            trip(5000.0, 10.0, Some(0.0)),
+        ];
+
+        let stats = r_multiple_stats(&trips);
+        assert_eq!(stats.num_trades, 3);
+        //(2 - 1 - 0.5) / 3
+        assert!((stats.expectancy - 0.5 / 3.0).abs() < 1e-12);
+        assert_eq!(stats.avg_loss_r, -0.75);
+        assert_eq!(stats.best_r, 2.0);
+
+        assert_eq!(r_distribution(&trips), BTreeMap::from([(-1, 2), (2, 1)]));
+    }
+
+    #[test]
+    fn no_stopped_trips_give_empty_stats() {
+        let trips = [trip(1000.0, 0.0, None)];
+
+        assert_eq!(r_multiple_stats(&trips), RMultipleStats::default());
+        assert!(r_distribution(&trips).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+//least-squares fit of strategy returns against a benchmark's returns
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct RegressionStats {
+    //sensitivity of the strategy to the benchmark (slope)
+    pub beta: f64,
+    //strategy return per period not explained by the benchmark (intercept)
+    pub alpha: f64,
+    //pearson correlation of the two series
+    pub correlation: f64,
+    //number of aligned return periods the fit used
+    pub observations: usize,
+}
+
+//regresses strategy returns on benchmark returns
+//series of different lengths are aligned on their most recent periods, using the shorter length,
+//so both should end on the same bar
+//returns all zeros with fewer than two periods, and zero beta/correlation on a flat benchmark
+pub fn regression_stats(strategy_returns: &[f64], benchmark_returns: &[f64]) -> RegressionStats {
+    let n = strategy_returns.len().min(benchmark_returns.len());
+    if n < 2 {
+        return RegressionStats {
+            observations: n,
+            ..RegressionStats::default()
+        };
+    }
+
+    let strategy = &strategy_returns[strategy_returns.len() - n..];
+    let benchmark = &benchmark_returns[benchmark_returns.len() - n..];
+
+    let mean_strategy = strategy.iter().sum::<f64>() / n as f64;
+    let mean_benchmark = benchmark.iter().sum::<f64>() / n as f64;
+
+    let mut covariance = 0.0;
+    let mut variance_strategy = 0.0;
+    let mut variance_benchmark = 0.0;
+    for (s, b) in strategy.iter().zip(benchmark) {
+        let ds = s - mean_strategy;
+        let db = b - mean_benchmark;
+        covariance += ds * db;
+        variance_strategy += ds * ds;
+        variance_benchmark += db * db;
+    }
+
+    let beta = if variance_benchmark > 0.0 {
+        covariance / variance_benchmark
+    } else {
+        0.0
+    };
+
+    let correlation = if variance_strategy > 0.0 && variance_benchmark > 0.0 {
+        covariance / (variance_strategy * variance_benchmark).sqrt()
+    } else {
+        0.0
+    };
+
+    RegressionStats {
+        beta,
+        alpha: mean_strategy - beta * mean_benchmark,
+        correlation,
+        observations: n,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BENCHMARK: [f64; 5] = [0.01, -0.02, 0.005, 0.015, -0.01];
+
+    //strategy returns of exactly 0.001 + 1.5 x benchmark
+    fn levered() -> Vec<f64> {
+        BENCHMARK.iter().map(|b| 0.001 + 1.5 * b).collect()
+    }
+
+    #[test]
+    fn exact_linear_relation_recovers_beta_and_alpha() {
+        let stats = regression_stats(&levered(), &BENCHMARK);
+
+        assert!((stats.beta - 1.5).abs() < 1e-12);
+        assert!((stats.alpha - 0.001).abs() < 1e-12);
+        assert!((stats.correlation - 1.0).abs() < 1e-12);
+        assert_eq!(stats.observations, 5);
+    }
+
+    #[test]
+    fn longer_series_align_on_their_latest_periods() {
+        let mut strategy = vec![0.5, -0.5];
+        strategy.extend(levered());
+
+        let stats = regression_stats(&strategy, &BENCHMARK);
+        assert_eq!(stats.observations, 5);
+        assert!((stats.beta - 1.5).abs() < 1e-12);
+    }
+
+    #[test]
+    fn flat_benchmark_and_short_series_give_zeros() {
+        let flat = regression_stats(&levered(), &[0.01; 5]);
+        assert_eq!((flat.beta, flat.correlation), (0.0, 0.0));
+
+        assert_eq!(
+            regression_stats(&[0.01], &BENCHMARK),
+            RegressionStats {
+                observations: 1,
+                ..RegressionStats::default()
+            }
+        );
+    }
+}
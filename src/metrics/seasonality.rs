@@ -0,0 +1,206 @@
+use crate::portfolio::RoundTrip;
+use chrono::{Datelike, Timelike};
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+//pnl of the trades falling into one time bucket
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct BucketStats {
+    pub count: usize,
+    pub wins: usize,
+    pub total_pnl: f64,
+}
+
+impl BucketStats {
+    fn add(&mut self, pnl: f64) {
+        self.count += 1;
+        self.total_pnl += pnl;
+        if pnl > 0.0 {
+            self.wins += 1;
+        }
+    }
+
+    //average pnl per trade (0 for an empty bucket)
+    pub fn avg_pnl(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.total_pnl / self.count as f64
+        }
+    }
+
+    //fraction of winning trades (0 for an empty bucket)
+    pub fn win_rate(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.wins as f64 / self.count as f64
+        }
+    }
+}
+
+//trade pnl bucketed by day-of-week and hour-of-day of the entry (utc)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Seasonality {
+    //[weekday from monday][hour]
+    pub grid: Vec<[BucketStats; 24]>,
+    pub by_weekday: [BucketStats; 7],
+    pub by_hour: [BucketStats; 24],
+}
+
+//buckets round trips by the weekday and hour they were entered, using net pnl
+pub fn seasonality(trips: &[RoundTrip]) -> Seasonality {
+    let mut grid = vec![[BucketStats::default(); 24]; 7];
+    let mut by_weekday = [BucketStats::default(); 7];
+    let mut by_hour = [BucketStats::default(); 24];
+
+    for trip in trips {
+        let weekday = trip.entry_time.weekday().num_days_from_monday() as usize;
+        let hour = trip.entry_time.hour() as usize;
+        let pnl = trip.net_pnl();
+
+        grid[weekday][hour].add(pnl);
+        by_weekday[weekday].add(pnl);
+        by_hour[hour].add(pnl);
+    }
+
+    Seasonality {
+        grid,
+        by_weekday,
+        by_hour,
+    }
+}
+
+impl Seasonality {
+    //stats across all buckets
+    pub fn total(&self) -> BucketStats {
+        self.by_weekday
+            .iter()
+            .fold(BucketStats::default(), |total, stats| BucketStats {
+                count: total.count + stats.count,
+                wins: total.wins + stats.wins,
+                total_pnl: total.total_pnl + stats.total_pnl,
+            })
+    }
+
+    //prints the weekday by hour grid
+    pub fn pretty_print_table(&self) {
+        self.to_table().printstd();
+    }
+
+    //builds average pnl per trade as a weekday by hour grid
+    //only hours with trades get a column
+    pub fn to_table(&self) -> Table {
+        let hours: Vec<usize> = (0..24).filter(|&h| self.by_hour[h].count > 0).collect();
+
+        let mut table = Table::new();
+
+        let mut header = vec![Cell::new("Day")];
+        header.extend(hours.iter().map(|h| Cell::new(&format!("{:02}h", h))));
+        header.push(Cell::new("All"));
+        table.add_row(Row::new(header));
+
+        let bucket_cell = |stats: &BucketStats| {
+            if stats.count == 0 {
+                Cell::new("-")
+            } else {
+                Cell::new(&format!("{:.2} ({})", stats.avg_pnl(), stats.count))
+            }
+        };
+
+        for (day, name) in WEEKDAYS.iter().enumerate() {
+            if self.by_weekday[day].count == 0 {
+                continue;
+            }
+            let mut row = vec![Cell::new(name)];
+            row.extend(hours.iter().map(|&h| bucket_cell(&self.grid[day][h])));
+            row.push(bucket_cell(&self.by_weekday[day]));
+            table.add_row(Row::new(row));
+        }
+
+        let mut totals = vec![Cell::new("All")];
+        totals.extend(hours.iter().map(|&h| bucket_cell(&self.by_hour[h])));
+        totals.push(bucket_cell(&self.total()));
+        table.add_row(Row::new(totals));
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::execution::OrderSide;
+    use chrono::{TimeZone, Utc};
+
+    //2024-01-01 is a monday
+    fn trip(day: u32, hour: u32, pnl: f64) -> RoundTrip {
+        let entry_time = Utc.with_ymd_and_hms(2024, 1, day, hour, 30, 0).unwrap();
+        RoundTrip {
+            symbol: "ES".to_string(),
+            side: OrderSide::Buy,
+            qty: 1,
+            entry_time,
+            exit_time: entry_time + chrono::Duration::minutes(45),
+            entry_price: 4000.0,
+            exit_price: 4000.0 + pnl / 50.0,
+            pnl,
+            fees: 5.0,
+            exit_reason: None,
+            initial_risk: None,
+        }
+    }
+
+    fn trips() -> Vec<RoundTrip> {
+        vec![
+            //two mondays at 14h, one on each side of zero
+            trip(1, 14, 105.0),
+            trip(8, 14, -45.0),
+            trip(1, 15, 205.0),
+            //wednesday at 14h
+            trip(3, 14, 5.0),
+            trip(5, 20, -95.0),
+        ]
+    }
+
+    #[test]
+    fn trades_are_bucketed_by_entry_weekday() {
+        let stats = seasonality(&trips());
+        let monday = stats.by_weekday[0];
+
+        assert_eq!(monday.count, 3);
+        assert_eq!(monday.wins, 2);
+        assert_eq!(monday.total_pnl, 250.0);
+        assert!((monday.avg_pnl() - 250.0 / 3.0).abs() < 1e-9);
+
+        //a trade that only breaks even after fees is not a win
+        assert_eq!(stats.by_weekday[2].count, 1);
+        assert_eq!(stats.by_weekday[2].wins, 0);
+        assert_eq!(stats.by_weekday[4].avg_pnl(), -100.0);
+        assert_eq!(stats.by_weekday[1].count, 0);
+        assert_eq!(stats.by_weekday[1].avg_pnl(), 0.0);
+    }
+
+    #[test]
+    fn trades_are_bucketed_by_entry_hour_and_grid_cell() {
+        let stats = seasonality(&trips());
+
+        assert_eq!(stats.by_hour[14].count, 3);
+        assert_eq!(stats.by_hour[14].total_pnl, 50.0);
+        assert_eq!(stats.by_hour[15].avg_pnl(), 200.0);
+        assert_eq!(stats.by_hour[20].win_rate(), 0.0);
+
+        let monday_14h = stats.grid[0][14];
+        assert_eq!(monday_14h.count, 2);
+        assert_eq!(monday_14h.avg_pnl(), 25.0);
+        assert_eq!(monday_14h.win_rate(), 0.5);
+        assert_eq!(stats.grid[2][14].count, 1);
+
+        let total = stats.total();
+        assert_eq!(total.count, 5);
+        assert_eq!(total.wins, 2);
+        assert_eq!(total.total_pnl, 150.0);
+    }
+}
@@ -0,0 +1,90 @@
+use crate::engine::execution::Fill;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+//slippage charged per contract, in ticks of the traded contract
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SlippageStats {
+    //contracts filled
+    pub contracts: u64,
+    //total slippage over total contracts, so larger fills weigh more
+    pub avg_ticks: f64,
+    //highest per-contract slippage of any fill
+    pub worst_ticks: f64,
+    //contracts per whole tick of slippage (see slippage_distribution)
+    pub distribution: BTreeMap<i64, u64>,
+}
+
+//per-contract slippage of a fill in ticks
+fn fill_ticks(fill: &Fill, tick_value: f64) -> f64 {
+    fill.slippage / tick_value / fill.qty.unsigned_abs() as f64
+}
+
+//summarizes the slippage part of the fills' fees
+//round-turn cost timing charges both sides on one fill, which shows as twice the slippage there
+pub fn slippage_stats(fills: &[Fill], tick_value: f64) -> SlippageStats {
+    let filled = fills.iter().filter(|f| f.qty != 0);
+    let contracts: u64 = filled.clone().map(|f| f.qty.unsigned_abs() as u64).sum();
+    if contracts == 0 || tick_value <= 0.0 {
+        return SlippageStats::default();
+    }
+
+    let total: f64 = filled.clone().map(|f| f.slippage).sum();
+    SlippageStats {
+        contracts,
+        avg_ticks: total / tick_value / contracts as f64,
+        worst_ticks: filled
+            .map(|f| fill_ticks(f, tick_value))
+            .fold(0.0, f64::max),
+        distribution: slippage_distribution(fills, tick_value),
+    }
+}
+
+//counts contracts per per-contract slippage, rounded to whole ticks
+pub fn slippage_distribution(fills: &[Fill], tick_value: f64) -> BTreeMap<i64, u64> {
+    let mut buckets = BTreeMap::new();
+    if tick_value <= 0.0 {
+        return buckets;
+    }
+    for fill in fills.iter().filter(|f| f.qty != 0) {
+        *buckets
+            .entry(fill_ticks(fill, tick_value).round() as i64)
+            .or_default() += fill.qty.unsigned_abs() as u64;
+    }
+    buckets
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::execution::{Order, OrderSide};
+    use chrono::Utc;
+
+    fn fill(qty: u32, slippage: f64) -> Fill {
+        let order = Order::market(1, Utc::now(), "ES".to_string(), qty, OrderSide::Buy);
+        let mut fill = Fill::from_order(1, &order, 4000.0, 0.0);
+        fill.slippage = slippage;
+        fill
+    }
+
+    #[test]
+    fn average_weighs_fills_by_contracts() {
+        //es ticks are worth 12.5: three contracts at one tick each, one at three ticks
+        let fills = [fill(3, 37.5), fill(1, 37.5)];
+        let stats = slippage_stats(&fills, 12.5);
+
+        assert_eq!(stats.contracts, 4);
+        assert_eq!(stats.avg_ticks, 1.5);
+        assert_eq!(stats.worst_ticks, 3.0);
+        assert_eq!(stats.distribution, BTreeMap::from([(1, 3), (3, 1)]));
+    }
+
+    #[test]
+    fn no_fills_give_empty_stats() {
+        assert_eq!(slippage_stats(&[], 12.5), SlippageStats::default());
+        assert_eq!(
+            slippage_stats(&[fill(1, 12.5)], 0.0),
+            SlippageStats::default()
+        );
+    }
+}
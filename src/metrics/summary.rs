@@ -1,20 +1,100 @@
 use crate::engine::execution::Fill;
-use crate::metrics::timeseries::{calculate_returns, max_drawdown, EquityPoint};
+use crate::engine::BacktestResult;
+use crate::metrics::calendar::TradingCalendar;
+use crate::metrics::currency::{round_to, round_to_cents, CurrencyFormat};
+use crate::metrics::r_multiple::{r_multiple_stats, RMultipleStats};
+use crate::metrics::slippage::SlippageStats;
+use crate::metrics::timeseries::{
+    calculate_equity_curve_with_mode, calculate_returns, max_drawdown, time_weighted_return,
+    DrawdownMode, EquityPoint,
+};
+use crate::portfolio::trade_log::{round_trips, RoundTrip};
+use chrono::{DateTime, Utc};
 use prettytable::{Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use statrs::statistics::Statistics;
+use std::collections::HashMap;
+use thiserror::Error;
+
+//finite stand-in for ratios that are unbounded (no losing trades, no downside returns)
+//keeps metrics json-serializable and sortable, where infinity would become null
+pub const RATIO_CAP: f64 = 1000.0;
+
+//inputs summary metrics can't be computed from
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum MetricsError {
+    #[error("Initial balance must be positive, got {0}")]
+    NonPositiveInitialBalance(f64),
+    #[error("Equity curve has a non-finite value ({equity}) at {timestamp}")]
+    NonFiniteEquity {
+        timestamp: DateTime<Utc>,
+        equity: f64,
+    },
+}
+
+//trade statistics for the round trips of one direction
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct DirectionStats {
+    pub num_trades: usize,
+    pub win_rate: f64,
+    pub profit_factor: f64,
+    //realized pnl after fees
+    pub net_pnl: f64,
+}
+
+impl DirectionStats {
+    //win rate and profit factor are measured on pnl before fees, like the overall figures
+    fn from_round_trips<'a>(trips: impl Iterator<Item = &'a RoundTrip>) -> Self {
+        let mut stats = DirectionStats::default();
+        let (mut wins, mut total_wins, mut total_losses) = (0, 0.0, 0.0);
+        for trip in trips {
+            stats.num_trades += 1;
+            stats.net_pnl += trip.net_pnl();
+            if trip.pnl > 0.0 {
+                wins += 1;
+                total_wins += trip.pnl;
+            } else {
+                total_losses -= trip.pnl;
+            }
+        }
+
+        if stats.num_trades > 0 {
+            stats.win_rate = wins as f64 / stats.num_trades as f64;
+        }
+        stats.profit_factor = profit_factor(total_wins, total_losses);
+        stats.net_pnl = round_to_cents(stats.net_pnl);
+        stats
+    }
+
+    //formats the stats as trades / win rate / profit factor / net pnl
+    fn format(&self, currency: &CurrencyFormat) -> String {
+        format!(
+            "{} / {:.2}% / {} / {}",
+            self.num_trades,
+            round_to(self.win_rate * 100.0, 2),
+            format_ratio(self.profit_factor),
+            currency.format(self.net_pnl)
+        )
+    }
+}
 
 //summary metrics for a backtest
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SummaryMetrics {
     pub initial_balance: f64,
     pub final_balance: f64,
     pub total_return: f64,
     pub total_return_pct: f64,
+    //compounded period returns net of external cash flows (matches total_return_pct without flows)
+    #[serde(default)]
+    pub time_weighted_return: f64,
     pub cagr: f64,
     pub max_drawdown: f64,
     pub sharpe_ratio: f64,
     pub sortino_ratio: f64,
+    //standard deviation of period returns, annualized like sharpe
+    #[serde(default)]
+    pub annual_volatility: f64,
     pub win_rate: f64,
     pub avg_win: f64,
     pub avg_loss: f64,
@@ -24,15 +104,122 @@ pub struct SummaryMetrics {
     pub num_losing_trades: usize,
     pub largest_win: f64,
     pub largest_loss: f64,
+    //round-trip pnl percentiles (linear interpolation between sorted trades)
+    #[serde(default)]
+    pub median_trade: f64,
+    #[serde(default)]
+    pub trade_p25: f64,
+    #[serde(default)]
+    pub trade_p75: f64,
+    //worst and best deciles: the 10th and 90th percentile round trip
+    #[serde(default)]
+    pub trade_p10: f64,
+    #[serde(default)]
+    pub trade_p90: f64,
+    //the trade statistics split by the direction of each round trip
+    #[serde(default)]
+    pub long_stats: DirectionStats,
+    #[serde(default)]
+    pub short_stats: DirectionStats,
+    //trade results in units of initial risk, for trips that had a protective stop
+    #[serde(default)]
+    pub r_stats: RMultipleStats,
     pub exposure: f64,
+    //peak gross contracts held at once (set by the engine from its positions)
+    #[serde(default)]
+    pub max_concurrent_contracts: u32,
+    //commissions and slippage paid across all fills
+    #[serde(default)]
+    pub total_fees: f64,
+    //the two parts of total_fees
+    #[serde(default)]
+    pub total_commission: f64,
+    #[serde(default)]
+    pub total_slippage: f64,
+    //slippage per contract in ticks (set by the engine, which knows the tick value)
+    #[serde(default)]
+    pub slippage: SlippageStats,
+    //pnl before fees (total_return + total_fees)
+    #[serde(default)]
+    pub gross_pnl: f64,
+    //total fees as a fraction of gross pnl (0 when gross pnl isn't positive)
+    #[serde(default)]
+    pub fees_pct_of_gross: f64,
+    //fees per year as a fraction of average equity, the return fees cost annually
+    #[serde(default)]
+    pub annual_fee_drag: f64,
+    //how max_drawdown is measured (fraction or dollars)
+    #[serde(default)]
+    pub drawdown_mode: DrawdownMode,
+    //currency used to format monetary values
+    #[serde(default)]
+    pub currency: CurrencyFormat,
 }
 
 impl SummaryMetrics {
     //calculate summary metrics from equity curve and trade log
+    //annualizes with a weekends-only trading calendar
     pub fn from_backtest(
         equity_curve: &[EquityPoint],
         trades: &[Fill],
         initial_balance: f64,
+    ) -> Self {
+        Self::from_backtest_with_calendar(
+            equity_curve,
+            trades,
+            initial_balance,
+            &TradingCalendar::default(),
+        )
+    }
+
+    //like from_backtest, but rejects inputs the metrics can't be computed from instead of
+    //reporting zeros: a non-positive initial balance or a non-finite equity value
+    //an empty equity curve (a run with no bars) is valid and yields zero metrics
+    pub fn try_from_backtest(
+        equity_curve: &[EquityPoint],
+        trades: &[Fill],
+        initial_balance: f64,
+    ) -> Result<Self, MetricsError> {
+        Self::try_from_backtest_with_calendar(
+            equity_curve,
+            trades,
+            initial_balance,
+            &TradingCalendar::default(),
+        )
+    }
+
+    //like from_backtest_with_calendar, but rejects unusable inputs (see try_from_backtest)
+    pub fn try_from_backtest_with_calendar(
+        equity_curve: &[EquityPoint],
+        trades: &[Fill],
+        initial_balance: f64,
+        calendar: &TradingCalendar,
+    ) -> Result<Self, MetricsError> {
+        if initial_balance <= 0.0 || !initial_balance.is_finite() {
+            return Err(MetricsError::NonPositiveInitialBalance(initial_balance));
+        }
+        if let Some(point) = equity_curve.iter().find(|p| !p.equity.is_finite()) {
+            return Err(MetricsError::NonFiniteEquity {
+                timestamp: point.timestamp,
+                equity: point.equity,
+            });
+        }
+
+        Ok(Self::from_backtest_with_calendar(
+            equity_curve,
+            trades,
+            initial_balance,
+            calendar,
+        ))
+    }
+
+    //calculate summary metrics, annualizing cagr and sharpe/sortino with the same
+    //trading-day count from the calendar
+    pub fn from_backtest_with_calendar(
+        equity_curve: &[EquityPoint],
+        trades: &[Fill],
+        initial_balance: f64,
+        calendar: &TradingCalendar,
     ) -> Self {
         let final_balance = equity_curve
             .last()
@@ -40,20 +227,23 @@ impl SummaryMetrics {
             .unwrap_or(initial_balance);
 
         let total_return = final_balance - initial_balance;
-        let total_return_pct = total_return / initial_balance;
-
-        //calculate cagr
-        let cagr = if equity_curve.len() >= 2 {
-            let start_time = equity_curve.first().unwrap().timestamp;
-            let end_time = equity_curve.last().unwrap().timestamp;
-            let duration_days = (end_time - start_time).num_days() as f64;
-            let years = duration_days / 365.25;
+        let total_return_pct = if initial_balance > 0.0 {
+            total_return / initial_balance
+        } else {
+            0.0
+        };
 
-            if years > 0.0 {
-                ((final_balance / initial_balance).powf(1.0 / years) - 1.0) * 100.0
-            } else {
-                0.0
+        //span of the run in trading years
+        let years = match (equity_curve.first(), equity_curve.last()) {
+            (Some(start), Some(end)) => {
+                calendar.years_between(start.timestamp.date_naive(), end.timestamp.date_naive())
             }
+            _ => 0.0,
+        };
+
+        //calculate cagr (needs a positive duration)
+        let cagr = if years > 0.0 && initial_balance > 0.0 && final_balance > 0.0 {
+            ((final_balance / initial_balance).powf(1.0 / years) - 1.0) * 100.0
         } else {
             0.0
         };
@@ -65,89 +255,247 @@ impl SummaryMetrics {
         let equity_values: Vec<f64> = equity_curve.iter().map(|p| p.equity).collect();
         let returns = calculate_returns(&equity_values);
 
+        //measured from the initial balance; the engine has no deposits or withdrawals to take out
+        let mut twr_values = Vec::with_capacity(equity_values.len() + 1);
+        twr_values.push(initial_balance);
+        twr_values.extend_from_slice(&equity_values);
+        let twr = time_weighted_return(&twr_values, &[]);
+
+        //return periods per year from the bar spacing (or the calendar's fixed count), so
+        //minute and daily bars annualize on the same trading-year basis as the cagr span
+        let timestamps: Vec<_> = equity_curve.iter().map(|p| p.timestamp).collect();
+        let periods_per_year = calendar.periods_per_year(&timestamps);
+
         let sharpe = if !returns.is_empty() {
-            calculate_sharpe_ratio(&returns)
+            calculate_sharpe_ratio(&returns, periods_per_year)
         } else {
             0.0
         };
 
         let sortino = if !returns.is_empty() {
-            calculate_sortino_ratio(&returns)
+            calculate_sortino_ratio(&returns, periods_per_year)
         } else {
             0.0
         };
 
+        let annual_volatility = calculate_annual_volatility(&returns, periods_per_year);
+
         //trade statistics
-        let trade_stats = calculate_trade_statistics(trades);
+        let trips = round_trips(trades);
+        let trade_stats = calculate_trade_statistics(&trips);
+        let long_stats = DirectionStats::from_round_trips(trips.iter().filter(|t| t.is_long()));
+        let short_stats = DirectionStats::from_round_trips(trips.iter().filter(|t| !t.is_long()));
+
+        //fee aggregation, gross pnl adds back what costs took out of the net return
+        let total_fees: f64 = trades.iter().map(|t| t.fees()).sum();
+        let gross_pnl = total_return + total_fees;
+        let fees_pct_of_gross = if gross_pnl > 0.0 {
+            total_fees / gross_pnl
+        } else {
+            0.0
+        };
+        let avg_equity = if equity_values.is_empty() {
+            0.0
+        } else {
+            equity_values.iter().sum::<f64>() / equity_values.len() as f64
+        };
+        let annual_fee_drag = if years > 0.0 && avg_equity > 0.0 {
+            total_fees / years / avg_equity
+        } else {
+            0.0
+        };
 
         //exposure calculation (simplified - percentage of time in market)
         let exposure = calculate_exposure(equity_curve, trades);
 
+        //monetary fields are rounded to cents so summed floating-point residue isn't reported
         SummaryMetrics {
             initial_balance,
-            final_balance,
-            total_return,
+            final_balance: round_to_cents(final_balance),
+            total_return: round_to_cents(total_return),
             total_return_pct,
+            time_weighted_return: twr,
             cagr,
             max_drawdown: max_dd,
             sharpe_ratio: sharpe,
             sortino_ratio: sortino,
+            annual_volatility,
             win_rate: trade_stats.win_rate,
-            avg_win: trade_stats.avg_win,
-            avg_loss: trade_stats.avg_loss,
+            avg_win: round_to_cents(trade_stats.avg_win),
+            avg_loss: round_to_cents(trade_stats.avg_loss),
             profit_factor: trade_stats.profit_factor,
             num_trades: trade_stats.num_trades,
             num_winning_trades: trade_stats.num_winning_trades,
             num_losing_trades: trade_stats.num_losing_trades,
-            largest_win: trade_stats.largest_win,
-            largest_loss: trade_stats.largest_loss,
+            largest_win: round_to_cents(trade_stats.largest_win),
+            largest_loss: round_to_cents(trade_stats.largest_loss),
+            median_trade: round_to_cents(trade_stats.median),
+            trade_p25: round_to_cents(trade_stats.p25),
+            trade_p75: round_to_cents(trade_stats.p75),
+            trade_p10: round_to_cents(trade_stats.p10),
+            trade_p90: round_to_cents(trade_stats.p90),
+            long_stats,
+            short_stats,
+            r_stats: r_multiple_stats(&trips),
             exposure,
+            max_concurrent_contracts: 0,
+            total_fees: round_to_cents(total_fees),
+            total_commission: round_to_cents(trades.iter().map(|t| t.commission).sum()),
+            total_slippage: round_to_cents(trades.iter().map(|t| t.slippage).sum()),
+            slippage: SlippageStats::default(),
+            gross_pnl: round_to_cents(gross_pnl),
+            fees_pct_of_gross,
+            annual_fee_drag,
+            drawdown_mode: DrawdownMode::default(),
+            currency: CurrencyFormat::default(),
+        }
+    }
+
+    //summary metrics for each symbol traded in a result, from that symbol's fills
+    //with one symbol the portfolio equity curve is that symbol's; with several, each symbol's
+    //equity is the initial balance plus its own realized pnl net of fees, stepped at each
+    //equity point (open positions aren't attributed, as the result carries no per-symbol marks)
+    pub fn per_symbol(result: &BacktestResult) -> HashMap<String, SummaryMetrics> {
+        let initial_balance = result.summary.initial_balance;
+        let mut symbols: Vec<&str> = result.trades.iter().map(|f| f.symbol.as_str()).collect();
+        symbols.sort();
+        symbols.dedup();
+
+        let mut per_symbol = HashMap::new();
+        for symbol in &symbols {
+            let trades = result.trades.by_symbol(symbol);
+
+            let metrics = if symbols.len() == 1 {
+                Self::from_backtest(&result.equity_curve, &trades, initial_balance)
+            } else {
+                let equity_curve = realized_equity_curve(
+                    &result.equity_curve,
+                    &trades,
+                    initial_balance,
+                    result.summary.drawdown_mode,
+                );
+                Self::from_backtest(&equity_curve, &trades, initial_balance)
+            };
+
+            per_symbol.insert(
+                symbol.to_string(),
+                metrics
+                    .with_drawdown_mode(result.summary.drawdown_mode)
+                    .with_currency(result.summary.currency.clone())
+                    .with_max_concurrent_contracts(
+                        result
+                            .max_contracts_by_symbol
+                            .get(*symbol)
+                            .copied()
+                            .unwrap_or(0),
+                    ),
+            );
+        }
+        per_symbol
+    }
+
+    //sets the drawdown mode the equity curve was built with
+    pub fn with_drawdown_mode(mut self, mode: DrawdownMode) -> Self {
+        self.drawdown_mode = mode;
+        self
+    }
+
+    //sets the currency used to format monetary values
+    pub fn with_currency(mut self, currency: CurrencyFormat) -> Self {
+        self.currency = currency;
+        self
+    }
+
+    //sets the peak gross contracts held, which the trades alone can't give for hedged lots
+    pub fn with_max_concurrent_contracts(mut self, max_concurrent_contracts: u32) -> Self {
+        self.max_concurrent_contracts = max_concurrent_contracts;
+        self
+    }
+
+    //sets the per-contract slippage in ticks, which needs the contract's tick value
+    pub fn with_slippage_stats(mut self, slippage: SlippageStats) -> Self {
+        self.slippage = slippage;
+        self
+    }
+
+    //returns true if a gross profit was entirely eaten by fees (a sign of overtrading)
+    pub fn fees_exceed_gross_profit(&self) -> bool {
+        self.gross_pnl > 0.0 && self.total_fees > self.gross_pnl
+    }
+
+    //formats max drawdown in dollars or percent depending on the mode
+    fn format_drawdown(&self) -> String {
+        if self.drawdown_mode.is_dollar() {
+            self.currency.format(self.max_drawdown)
+        } else {
+            format!("{:.2}%", round_to(self.max_drawdown * 100.0, 2))
         }
     }
 
     //prints metrics in a formatted table
     pub fn pretty_print_table(&self) {
+        self.to_table().printstd();
+    }
+
+    //builds the metric / value table
+    pub fn to_table(&self) -> Table {
         let mut table = Table::new();
 
         table.add_row(Row::new(vec![Cell::new("Metric"), Cell::new("Value")]));
 
         table.add_row(Row::new(vec![
             Cell::new("Initial Balance"),
-            Cell::new(&format!("${:.2}", self.initial_balance)),
+            Cell::new(&self.currency.format(self.initial_balance)),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Final Balance"),
-            Cell::new(&format!("${:.2}", self.final_balance)),
+            Cell::new(&self.currency.format(self.final_balance)),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Total Return"),
             Cell::new(&format!(
-                "${:.2} ({:.2}%)",
-                self.total_return,
-                self.total_return_pct * 100.0
+                "{} ({:.2}%)",
+                self.currency.format(self.total_return),
+                round_to(self.total_return_pct * 100.0, 2)
+            )),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Time-Weighted Return"),
+            Cell::new(&format!(
+                "{:.2}%",
+                round_to(self.time_weighted_return * 100.0, 2)
             )),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("CAGR"),
-            Cell::new(&format!("{:.2}%", self.cagr)),
+            Cell::new(&format!("{:.2}%", round_to(self.cagr, 2))),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Max Drawdown"),
-            Cell::new(&format!("{:.2}%", self.max_drawdown * 100.0)),
+            Cell::new(&self.format_drawdown()),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Sharpe Ratio"),
-            Cell::new(&format!("{:.3}", self.sharpe_ratio)),
+            Cell::new(&format!("{:.3}", round_to(self.sharpe_ratio, 3))),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Sortino Ratio"),
-            Cell::new(&format!("{:.3}", self.sortino_ratio)),
+            Cell::new(&format_ratio(self.sortino_ratio)),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Annual Volatility"),
+            Cell::new(&format!(
+                "{:.2}%",
+                round_to(self.annual_volatility * 100.0, 2)
+            )),
         ]));
 
         table.add_row(Row::new(vec![
@@ -157,40 +505,186 @@ impl SummaryMetrics {
 
         table.add_row(Row::new(vec![
             Cell::new("Win Rate"),
-            Cell::new(&format!("{:.2}%", self.win_rate * 100.0)),
+            Cell::new(&format!("{:.2}%", round_to(self.win_rate * 100.0, 2))),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Avg Win"),
-            Cell::new(&format!("${:.2}", self.avg_win)),
+            Cell::new(&self.currency.format(self.avg_win)),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Avg Loss"),
-            Cell::new(&format!("${:.2}", self.avg_loss)),
+            Cell::new(&self.currency.format(self.avg_loss)),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Largest Win"),
-            Cell::new(&format!("${:.2}", self.largest_win)),
+            Cell::new(&self.currency.format(self.largest_win)),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Largest Loss"),
-            Cell::new(&format!("${:.2}", self.largest_loss)),
+            Cell::new(&self.currency.format(self.largest_loss)),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Median Trade"),
+            Cell::new(&self.currency.format(self.median_trade)),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Trade P25 / P75"),
+            Cell::new(&format!(
+                "{} / {}",
+                self.currency.format(self.trade_p25),
+                self.currency.format(self.trade_p75)
+            )),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Worst / Best Decile"),
+            Cell::new(&format!(
+                "{} / {}",
+                self.currency.format(self.trade_p10),
+                self.currency.format(self.trade_p90)
+            )),
         ]));
 
         table.add_row(Row::new(vec![
             Cell::new("Profit Factor"),
-            Cell::new(&format!("{:.3}", self.profit_factor)),
+            Cell::new(&format_ratio(self.profit_factor)),
         ]));
 
+        table.add_row(Row::new(vec![
+            Cell::new("Long: Trades / Win / PF / Net"),
+            Cell::new(&self.long_stats.format(&self.currency)),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Short: Trades / Win / PF / Net"),
+            Cell::new(&self.short_stats.format(&self.currency)),
+        ]));
+
+        if self.r_stats.num_trades > 0 {
+            table.add_row(Row::new(vec![
+                Cell::new("R Expectancy"),
+                Cell::new(&format!(
+                    "{:.2}R ({} trades)",
+                    round_to(self.r_stats.expectancy, 2),
+                    self.r_stats.num_trades
+                )),
+            ]));
+
+            table.add_row(Row::new(vec![
+                Cell::new("Avg Win / Loss R"),
+                Cell::new(&format!(
+                    "{:.2}R / {:.2}R",
+                    round_to(self.r_stats.avg_win_r, 2),
+                    round_to(self.r_stats.avg_loss_r, 2)
+                )),
+            ]));
+
+            table.add_row(Row::new(vec![
+                Cell::new("Best / Worst R"),
+                Cell::new(&format!(
+                    "{:.2}R / {:.2}R",
+                    round_to(self.r_stats.best_r, 2),
+                    round_to(self.r_stats.worst_r, 2)
+                )),
+            ]));
+        }
+
         table.add_row(Row::new(vec![
             Cell::new("Exposure"),
-            Cell::new(&format!("{:.2}%", self.exposure * 100.0)),
+            Cell::new(&format!("{:.2}%", round_to(self.exposure * 100.0, 2))),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Max Contracts Held"),
+            Cell::new(&self.max_concurrent_contracts.to_string()),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Gross P&L"),
+            Cell::new(&self.currency.format(self.gross_pnl)),
         ]));
 
-        table.printstd();
+        table.add_row(Row::new(vec![
+            Cell::new("Total Fees"),
+            Cell::new(&self.currency.format(self.total_fees)),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Commission / Slippage"),
+            Cell::new(&format!(
+                "{} / {}",
+                self.currency.format(self.total_commission),
+                self.currency.format(self.total_slippage)
+            )),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Fees % of Gross P&L"),
+            Cell::new(&if self.gross_pnl > 0.0 {
+                format!("{:.2}%", round_to(self.fees_pct_of_gross * 100.0, 2))
+            } else {
+                "-".to_string()
+            }),
+        ]));
+
+        table.add_row(Row::new(vec![
+            Cell::new("Annual Fee Drag"),
+            Cell::new(&format!(
+                "{:.2}% of avg equity",
+                round_to(self.annual_fee_drag * 100.0, 2)
+            )),
+        ]));
+
+        if self.slippage.contracts > 0 {
+            table.add_row(Row::new(vec![
+                Cell::new("Avg / Worst Slippage"),
+                Cell::new(&format!(
+                    "{:.2} / {:.2} ticks",
+                    round_to(self.slippage.avg_ticks, 2),
+                    round_to(self.slippage.worst_ticks, 2)
+                )),
+            ]));
+        }
+
+        table
+    }
+}
+
+//equity from realized pnl net of fees, sampled at the timestamps of the portfolio curve
+fn realized_equity_curve(
+    portfolio_curve: &[EquityPoint],
+    trades: &[Fill],
+    initial_balance: f64,
+    mode: DrawdownMode,
+) -> Vec<EquityPoint> {
+    let timestamps: Vec<_> = portfolio_curve.iter().map(|p| p.timestamp).collect();
+
+    let mut equity_values = Vec::with_capacity(timestamps.len());
+    let mut equity = initial_balance;
+    let mut trade_idx = 0;
+    for timestamp in &timestamps {
+        while trade_idx < trades.len() && trades[trade_idx].timestamp <= *timestamp {
+            equity += trades[trade_idx].realized_pnl - trades[trade_idx].fees();
+            trade_idx += 1;
+        }
+        equity_values.push(equity);
+    }
+
+    calculate_equity_curve_with_mode(&timestamps, &equity_values, initial_balance, mode)
+}
+
+//formats a ratio, marking values at the cap as unbounded
+fn format_ratio(value: f64) -> String {
+    if value >= RATIO_CAP {
+        format!(">= {:.0} (unbounded)", RATIO_CAP)
+    } else {
+        format!("{:.3}", round_to(value, 3))
     }
 }
 
@@ -204,67 +698,41 @@ struct TradeStats {
     profit_factor: f64,
     largest_win: f64,
     largest_loss: f64,
+    median: f64,
+    p25: f64,
+    p75: f64,
+    p10: f64,
+    p90: f64,
 }
 
-fn calculate_trade_statistics(trades: &[Fill]) -> TradeStats {
-    if trades.is_empty() {
-        return TradeStats {
-            num_trades: 0,
-            num_winning_trades: 0,
-            num_losing_trades: 0,
-            win_rate: 0.0,
-            avg_win: 0.0,
-            avg_loss: 0.0,
-            profit_factor: 0.0,
-            largest_win: 0.0,
-            largest_loss: 0.0,
-        };
+//returns the p-th percentile (0..=1) of sorted values, interpolating between neighbours
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
     }
 
-    //group trades into round trips (open + close)
-    let mut round_trips = Vec::new();
-    let mut open_trades: Vec<&Fill> = Vec::new();
-
-    for trade in trades {
-        if open_trades.is_empty() {
-            open_trades.push(trade);
-        } else {
-            let last = open_trades.last().unwrap();
-            let same_direction = (last.qty > 0 && trade.qty > 0) || (last.qty < 0 && trade.qty < 0);
+    let rank = p.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (rank - lower as f64)
+}
 
-            if same_direction {
-                open_trades.push(trade);
-            } else {
-                //closing trade - calculate pnl
-                let total_qty = open_trades.iter().map(|t| t.qty.abs()).sum::<i32>();
-                let avg_entry = open_trades
-                    .iter()
-                    .map(|t| t.fill_price * t.qty.abs() as f64)
-                    .sum::<f64>()
-                    / total_qty as f64;
-
-                let profit_loss = if open_trades[0].qty > 0 {
-                    //long trade
-                    (trade.fill_price - avg_entry) * total_qty.min(trade.qty.abs()) as f64
-                } else {
-                    //short trade
-                    (avg_entry - trade.fill_price) * total_qty.min(trade.qty.abs()) as f64
-                };
-
-                round_trips.push(profit_loss);
-
-                //if trade closes more than open position, it opens a new one
-                if trade.qty.abs() > total_qty {
-                    open_trades.clear();
-                    open_trades.push(trade);
-                } else {
-                    open_trades.clear();
-                }
-            }
-        }
+//gross wins over gross losses, capped when there are no losses
+fn profit_factor(total_wins: f64, total_losses: f64) -> f64 {
+    if total_losses > 0.0 {
+        (total_wins / total_losses).min(RATIO_CAP)
+    } else if total_wins > 0.0 {
+        RATIO_CAP
+    } else {
+        0.0
     }
+}
+
+fn calculate_trade_statistics(trips: &[RoundTrip]) -> TradeStats {
+    //take each round trip's realized pnl
+    let trip_pnls: Vec<f64> = trips.iter().map(|t| t.pnl).collect();
 
-    if round_trips.is_empty() {
+    if trip_pnls.is_empty() {
         return TradeStats {
             num_trades: 0,
             num_winning_trades: 0,
@@ -275,15 +743,23 @@ fn calculate_trade_statistics(trades: &[Fill]) -> TradeStats {
             profit_factor: 0.0,
             largest_win: 0.0,
             largest_loss: 0.0,
+            median: 0.0,
+            p25: 0.0,
+            p75: 0.0,
+            p10: 0.0,
+            p90: 0.0,
         };
     }
 
-    let winning_trades: Vec<f64> = round_trips
+    let mut sorted_pnls = trip_pnls.clone();
+    sorted_pnls.sort_by(|a, b| a.total_cmp(b));
+
+    let winning_trades: Vec<f64> = trip_pnls
         .iter()
         .filter(|&&profit_loss| profit_loss > 0.0)
         .copied()
         .collect();
-    let losing_trades: Vec<f64> = round_trips
+    let losing_trades: Vec<f64> = trip_pnls
         .iter()
         .filter(|&&profit_loss| profit_loss < 0.0)
         .copied()
@@ -291,7 +767,7 @@ fn calculate_trade_statistics(trades: &[Fill]) -> TradeStats {
 
     let num_winning = winning_trades.len();
     let num_losing = losing_trades.len();
-    let total = round_trips.len();
+    let total = trip_pnls.len();
 
     let win_rate = num_winning as f64 / total as f64;
 
@@ -310,13 +786,7 @@ fn calculate_trade_statistics(trades: &[Fill]) -> TradeStats {
     let total_wins: f64 = winning_trades.iter().sum();
     let total_losses: f64 = losing_trades.iter().sum::<f64>().abs();
 
-    let profit_factor = if total_losses > 0.0 {
-        total_wins / total_losses
-    } else if total_wins > 0.0 {
-        f64::INFINITY
-    } else {
-        0.0
-    };
+    let profit_factor = profit_factor(total_wins, total_losses);
 
     let largest_win = winning_trades.iter().fold(0.0f64, |a, &b| a.max(b));
     let largest_loss = losing_trades.iter().fold(0.0f64, |a, &b| a.min(b));
@@ -331,28 +801,48 @@ fn calculate_trade_statistics(trades: &[Fill]) -> TradeStats {
         profit_factor,
         largest_win,
         largest_loss,
+        median: percentile(&sorted_pnls, 0.5),
+        p25: percentile(&sorted_pnls, 0.25),
+        p75: percentile(&sorted_pnls, 0.75),
+        p10: percentile(&sorted_pnls, 0.1),
+        p90: percentile(&sorted_pnls, 0.9),
     }
 }
 
-fn calculate_sharpe_ratio(returns: &[f64]) -> f64 {
-    if returns.is_empty() {
+fn calculate_sharpe_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
+    //sample std dev is undefined for fewer than two returns
+    if returns.len() < 2 {
         return 0.0;
     }
 
     let mean = returns.mean();
     let std_dev = returns.std_dev();
 
-    if std_dev == 0.0 {
+    if std_dev == 0.0 || std_dev.is_nan() {
+        return 0.0;
+    }
+
+    //annualize by the number of return periods per year
+    //sharpe = (mean_return * n) / (std_dev * sqrt(n))
+    //simplified sharpe = mean / std_dev * sqrt(n)
+    (mean / std_dev) * periods_per_year.sqrt()
+}
+
+fn calculate_annual_volatility(returns: &[f64], periods_per_year: f64) -> f64 {
+    //sample std dev is undefined for fewer than two returns
+    if returns.len() < 2 {
         return 0.0;
     }
 
-    //annualize assuming daily returns
-    //sharpe = (mean_return * 252) / (std_dev * sqrt(252))
-    //simplified sharpe = mean / std_dev * sqrt(252)
-    (mean / std_dev) * (252.0_f64).sqrt()
+    let std_dev = returns.std_dev();
+    if std_dev.is_nan() {
+        return 0.0;
+    }
+
+    std_dev * periods_per_year.sqrt()
 }
 
-fn calculate_sortino_ratio(returns: &[f64]) -> f64 {
+fn calculate_sortino_ratio(returns: &[f64], periods_per_year: f64) -> f64 {
     if returns.is_empty() {
         return 0.0;
     }
@@ -363,17 +853,21 @@ fn calculate_sortino_ratio(returns: &[f64]) -> f64 {
     let negative_returns: Vec<f64> = returns.iter().filter(|&&r| r < 0.0).copied().collect();
 
     if negative_returns.is_empty() {
-        return if mean > 0.0 { f64::INFINITY } else { 0.0 };
+        return if mean > 0.0 { RATIO_CAP } else { 0.0 };
+    }
+
+    if negative_returns.len() < 2 {
+        return 0.0;
     }
 
     let downside_dev = negative_returns.std_dev();
 
-    if downside_dev == 0.0 {
+    if downside_dev == 0.0 || downside_dev.is_nan() {
         return 0.0;
     }
 
     //annualize
-    (mean / downside_dev) * (252.0_f64).sqrt()
+    ((mean / downside_dev) * periods_per_year.sqrt()).min(RATIO_CAP)
 }
 
 fn calculate_exposure(equity_curve: &[EquityPoint], trades: &[Fill]) -> f64 {
@@ -401,3 +895,248 @@ fn calculate_exposure(equity_curve: &[EquityPoint], trades: &[Fill]) -> f64 {
 
     in_market_count as f64 / equity_curve.len() as f64
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::{aggregate_metrics, objective_value};
+    use chrono::{TimeZone, Utc};
+
+    fn assert_all_finite(metrics: &SummaryMetrics) {
+        for value in [
+            metrics.total_return,
+            metrics.total_return_pct,
+            metrics.cagr,
+            metrics.max_drawdown,
+            metrics.sharpe_ratio,
+            metrics.sortino_ratio,
+            metrics.annual_volatility,
+            metrics.win_rate,
+            metrics.profit_factor,
+            metrics.exposure,
+        ] {
+            assert!(value.is_finite());
+        }
+    }
+
+    #[test]
+    fn zero_bar_backtest_summarizes_to_zeros() {
+        let metrics = SummaryMetrics::from_backtest(&[], &[], 100000.0);
+
+        assert_all_finite(&metrics);
+        assert_eq!(metrics.final_balance, 100000.0);
+        assert_eq!(metrics.total_return, 0.0);
+        assert_eq!(metrics.cagr, 0.0);
+        assert_eq!(metrics.num_trades, 0);
+    }
+
+    #[test]
+    fn one_bar_backtest_summarizes_to_zeros() {
+        let curve = [EquityPoint {
+            timestamp: Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            equity: 100000.0,
+            drawdown: 0.0,
+            returns: 0.0,
+        }];
+        let metrics = SummaryMetrics::from_backtest(&curve, &[], 100000.0);
+
+        assert_all_finite(&metrics);
+        assert_eq!(metrics.cagr, 0.0);
+        assert_eq!(metrics.sharpe_ratio, 0.0);
+        assert_eq!(metrics.max_drawdown, 0.0);
+    }
+
+    fn point(year: i32, month: u32, day: u32, equity: f64) -> EquityPoint {
+        EquityPoint {
+            timestamp: Utc.with_ymd_and_hms(year, month, day, 0, 0, 0).unwrap(),
+            equity,
+            drawdown: 0.0,
+            returns: 0.0,
+        }
+    }
+
+    #[test]
+    fn cagr_over_two_trading_years() {
+        //mon 2024-01-01 to fri 2025-12-05 spans 504 weekdays, two trading years
+        let calendar = TradingCalendar::new();
+        let curve = [point(2024, 1, 1, 100000.0), point(2025, 12, 5, 121000.0)];
+        let metrics = SummaryMetrics::from_backtest_with_calendar(&curve, &[], 100000.0, &calendar);
+
+        assert!((metrics.cagr - 10.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn holidays_shorten_the_cagr_span() {
+        //one trading year without holidays; a holiday leaves 251 days, so the same
+        //return annualizes to a slightly higher cagr
+        let holiday = chrono::NaiveDate::from_ymd_opt(2024, 7, 4).unwrap();
+        let curve = [point(2024, 1, 1, 100000.0), point(2024, 12, 18, 110000.0)];
+        let plain = SummaryMetrics::from_backtest_with_calendar(
+            &curve,
+            &[],
+            100000.0,
+            &TradingCalendar::new(),
+        );
+        let with_holiday = SummaryMetrics::from_backtest_with_calendar(
+            &curve,
+            &[],
+            100000.0,
+            &TradingCalendar::with_holidays([holiday]),
+        );
+
+        assert!((plain.cagr - 10.0).abs() < 1e-9);
+        let expected = (1.1f64.powf(252.0 / 251.0) - 1.0) * 100.0;
+        assert!((with_holiday.cagr - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn annual_volatility_of_a_known_series() {
+        //alternating +-1%: sample std dev sqrt(4e-4 / 3)
+        let returns = [0.01, -0.01, 0.01, -0.01];
+        let expected = (4e-4f64 / 3.0).sqrt() * 252f64.sqrt();
+
+        assert!((calculate_annual_volatility(&returns, 252.0) - expected).abs() < 1e-12);
+        assert_eq!(calculate_annual_volatility(&returns[..1], 252.0), 0.0);
+    }
+
+    #[test]
+    fn annual_volatility_uses_the_sharpe_annualization() {
+        //daily equity alternating +2% and -1%
+        let mut equity = 100000.0;
+        let curve: Vec<EquityPoint> = (0..20)
+            .map(|i| {
+                if i > 0 {
+                    equity *= if i % 2 == 1 { 1.02 } else { 0.99 };
+                }
+                point(2024, 1, 2 + i, equity)
+            })
+            .collect();
+        let calendar = TradingCalendar::new().with_periods_per_year(Some(252.0));
+        let metrics = SummaryMetrics::from_backtest_with_calendar(&curve, &[], 100000.0, &calendar);
+
+        let returns = calculate_returns(&curve.iter().map(|p| p.equity).collect::<Vec<_>>());
+        let mean = returns.iter().sum::<f64>() / returns.len() as f64;
+        //with one annualization factor, sharpe x volatility is the mean return x periods per year
+        assert!(
+            (metrics.annual_volatility - calculate_annual_volatility(&returns, 252.0)).abs()
+                < 1e-12
+        );
+        assert!(metrics.annual_volatility > 0.0);
+        assert!((metrics.sharpe_ratio * metrics.annual_volatility - mean * 252.0).abs() < 1e-2);
+    }
+
+    //es makes 10 points long on day 2; nq loses 50 points short from day 2 to day 3
+    fn two_symbol_result() -> BacktestResult {
+        use crate::engine::execution::{Order, OrderSide};
+        use crate::instrument::FuturesContract;
+        use crate::portfolio::Account;
+
+        let (es, nq) = (FuturesContract::es("H24"), FuturesContract::nq("H24"));
+        let mut account = Account::new(100000.0, 0.0, 0.0);
+        let day = |d| Utc.with_ymd_and_hms(2024, 1, d, 0, 0, 0).unwrap();
+        let fills = [
+            (1, 2, "ES", OrderSide::Buy, 4000.0),
+            (2, 3, "ES", OrderSide::Sell, 4010.0),
+            (3, 3, "NQ", OrderSide::Sell, 17000.0),
+            (4, 4, "NQ", OrderSide::Buy, 17050.0),
+        ];
+        for (id, d, symbol, side, price) in fills {
+            let order = Order::market(id, day(d), symbol.to_string(), 1, side);
+            let contract = if symbol == "ES" { &es } else { &nq };
+            account
+                .process_fill(Fill::from_order(id, &order, price, 0.0), contract)
+                .unwrap();
+        }
+
+        let equity_curve: Vec<EquityPoint> = [100000.0, 100500.0, 100500.0, 99500.0]
+            .iter()
+            .enumerate()
+            .map(|(i, &equity)| point(2024, 1, 2 + i as u32, equity))
+            .collect();
+        let trades = account.trade_log.clone();
+        BacktestResult {
+            summary: SummaryMetrics::from_backtest(&equity_curve, &trades, 100000.0),
+            equity_curve,
+            trades,
+            halted_at: None,
+            rolls: 0,
+            bankrupt: false,
+            max_concurrent_contracts: 1,
+            max_contracts_by_symbol: HashMap::new(),
+            order_stats: Default::default(),
+            seed: 0,
+        }
+    }
+
+    #[test]
+    fn per_symbol_attributes_each_symbols_pnl() {
+        let result = two_symbol_result();
+        let per_symbol = SummaryMetrics::per_symbol(&result);
+
+        assert_eq!(per_symbol.len(), 2);
+        let (es, nq) = (&per_symbol["ES"], &per_symbol["NQ"]);
+        assert_eq!(es.final_balance, 100500.0);
+        assert_eq!(nq.final_balance, 99000.0);
+        assert_eq!((es.num_trades, nq.num_trades), (1, 1));
+        assert_eq!(
+            es.total_return + nq.total_return,
+            result.summary.total_return
+        );
+        assert!(nq.max_drawdown > 0.0);
+    }
+
+    #[test]
+    fn try_from_backtest_rejects_a_non_positive_balance() {
+        let curve = [point(2024, 1, 2, 0.0)];
+
+        assert_eq!(
+            SummaryMetrics::try_from_backtest(&curve, &[], 0.0).unwrap_err(),
+            MetricsError::NonPositiveInitialBalance(0.0)
+        );
+        assert!(SummaryMetrics::try_from_backtest(&[], &[], -1.0).is_err());
+    }
+
+    #[test]
+    fn try_from_backtest_accepts_an_empty_curve_as_zeros() {
+        let metrics = SummaryMetrics::try_from_backtest(&[], &[], 100000.0).unwrap();
+
+        assert_eq!(metrics.final_balance, 100000.0);
+        assert_eq!(metrics.total_return, 0.0);
+        assert_eq!(metrics.sharpe_ratio, 0.0);
+    }
+
+    #[test]
+    fn try_from_backtest_rejects_non_finite_equity() {
+        let curve = [point(2024, 1, 2, 100000.0), point(2024, 1, 3, f64::NAN)];
+
+        assert!(matches!(
+            SummaryMetrics::try_from_backtest(&curve, &[], 100000.0),
+            Err(MetricsError::NonFiniteEquity { timestamp, .. }) if timestamp == curve[1].timestamp
+        ));
+    }
+
+    //es never loses: its profit factor is capped, survives json and ranks first by profit factor
+    #[test]
+    fn no_loss_run_serializes_and_ranks_sanely() {
+        let per_symbol = SummaryMetrics::per_symbol(&two_symbol_result());
+        let (es, nq) = (&per_symbol["ES"], &per_symbol["NQ"]);
+        assert_eq!(es.profit_factor, RATIO_CAP);
+
+        let json = serde_json::to_value(es).unwrap();
+        assert_eq!(json["profit_factor"], RATIO_CAP);
+        let loaded: SummaryMetrics = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded.profit_factor, RATIO_CAP);
+
+        let mut ranked = [nq, es];
+        ranked.sort_by(|a, b| {
+            let key = |m: &SummaryMetrics| objective_value(m, "profit_factor").unwrap();
+            key(b).total_cmp(&key(a))
+        });
+        assert_eq!(ranked[0].final_balance, es.final_balance);
+
+        //the capped run is counted, not averaged into the profit factor stats
+        let aggregate = aggregate_metrics(&[es.clone(), nq.clone()]);
+        assert_eq!(aggregate.profit_factor.capped, 1);
+        assert_eq!(aggregate.profit_factor.max, nq.profit_factor);
+    }
+}
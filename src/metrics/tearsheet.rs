@@ -0,0 +1,492 @@
+use crate::engine::BacktestResult;
+use crate::metrics::currency::round_to;
+use crate::metrics::r_multiple::r_distribution;
+use crate::metrics::timeseries::EquityPoint;
+use chrono::{DateTime, Datelike, Utc};
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+//drawdown periods listed in the tear sheet
+const TOP_DRAWDOWNS: usize = 5;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+//a stretch of the equity curve below its previous high-water mark
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DrawdownPeriod {
+    //time of the peak the drawdown is measured from
+    pub start: DateTime<Utc>,
+    //time of the lowest equity in the period
+    pub trough: DateTime<Utc>,
+    //first time equity regained the peak (none if it never did)
+    pub end: Option<DateTime<Utc>>,
+    //fraction below the peak at the trough
+    pub depth: f64,
+}
+
+//splits the equity curve into drawdown periods, in chronological order
+//depth is always measured as a fraction of the peak, whatever the result's drawdown mode
+pub fn drawdown_periods(equity_curve: &[EquityPoint]) -> Vec<DrawdownPeriod> {
+    let mut periods = Vec::new();
+    let mut current: Option<DrawdownPeriod> = None;
+    let (mut peak, mut peak_time) = match equity_curve.first() {
+        Some(point) => (point.equity, point.timestamp),
+        None => return periods,
+    };
+
+    for point in equity_curve {
+        if point.equity >= peak {
+            if let Some(mut period) = current.take() {
+                period.end = Some(point.timestamp);
+                periods.push(period);
+            }
+            peak = point.equity;
+            peak_time = point.timestamp;
+            continue;
+        }
+
+        let depth = if peak > 0.0 {
+            (peak - point.equity) / peak
+        } else {
+            0.0
+        };
+        let period = current.get_or_insert(DrawdownPeriod {
+            start: peak_time,
+            trough: point.timestamp,
+            end: None,
+            depth,
+        });
+        if depth > period.depth {
+            period.depth = depth;
+            period.trough = point.timestamp;
+        }
+    }
+
+    periods.extend(current);
+    periods
+}
+
+//the deepest drawdown period of the curve, none if equity never fell below a peak
+pub fn worst_drawdown(equity_curve: &[EquityPoint]) -> Option<DrawdownPeriod> {
+    drawdown_periods(equity_curve)
+        .into_iter()
+        .max_by(|a, b| a.depth.total_cmp(&b.depth))
+}
+
+//writes the underwater curve for plotting: per point the running peak, the drawdown as the
+//result measured it (fraction or dollars) and the percent below the peak as a value <= 0
+pub fn write_drawdown_csv(equity_curve: &[EquityPoint], path: &Path) -> anyhow::Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    writeln!(file, "timestamp,equity,peak,drawdown,underwater_pct")?;
+
+    let mut peak = f64::MIN;
+    for point in equity_curve {
+        peak = peak.max(point.equity);
+        let underwater = if peak > 0.0 {
+            (point.equity - peak) / peak
+        } else {
+            0.0
+        };
+        writeln!(
+            file,
+            "{},{},{},{},{}",
+            point.timestamp.to_rfc3339(),
+            point.equity,
+            peak,
+            point.drawdown,
+            round_to(underwater * 100.0, 4)
+        )?;
+    }
+
+    Ok(())
+}
+
+//returns the return of each calendar month, keyed by (year, month)
+//each month runs from the previous month's last equity (the initial balance for the first)
+//to its own last equity
+pub fn monthly_returns(
+    equity_curve: &[EquityPoint],
+    initial_balance: f64,
+) -> BTreeMap<(i32, u32), f64> {
+    let mut month_end: BTreeMap<(i32, u32), f64> = BTreeMap::new();
+    for point in equity_curve {
+        let date = point.timestamp.date_naive();
+        month_end.insert((date.year(), date.month()), point.equity);
+    }
+
+    let mut returns = BTreeMap::new();
+    let mut start = initial_balance;
+    for (month, end) in month_end {
+        let ret = if start > 0.0 { end / start - 1.0 } else { 0.0 };
+        returns.insert(month, ret);
+        start = end;
+    }
+    returns
+}
+
+//builds the titled tables of the tear sheet: summary, monthly returns, drawdowns, trades and,
+//when there are any, the slippage and r-multiple distributions
+fn sections(result: &BacktestResult) -> Vec<(&'static str, Table)> {
+    let mut sections = vec![
+        ("Summary", result.summary.to_table()),
+        (
+            "Monthly Returns",
+            monthly_table(&monthly_returns(
+                &result.equity_curve,
+                result.summary.initial_balance,
+            )),
+        ),
+        (
+            "Worst Drawdowns",
+            drawdown_table(&drawdown_periods(&result.equity_curve)),
+        ),
+        ("Trades", trade_table(result)),
+    ];
+
+    if result.summary.total_slippage > 0.0 {
+        sections.push((
+            "Slippage Distribution",
+            slippage_table(&result.summary.slippage.distribution),
+        ));
+    }
+
+    let distribution = r_distribution(&result.round_trips());
+    if !distribution.is_empty() {
+        sections.push(("R Distribution", r_table(&distribution)));
+    }
+    sections
+}
+
+fn pct(value: f64) -> String {
+    format!("{:.2}%", round_to(value * 100.0, 2))
+}
+
+fn text_row(cells: &[String]) -> Row {
+    Row::new(cells.iter().map(|cell| Cell::new(cell)).collect())
+}
+
+//one row per year with a column per month and the compounded year return
+fn monthly_table(returns: &BTreeMap<(i32, u32), f64>) -> Table {
+    let mut table = Table::new();
+
+    let mut header = vec!["Year".to_string()];
+    header.extend(MONTHS.iter().map(|m| m.to_string()));
+    header.push("Year".to_string());
+    table.add_row(text_row(&header));
+
+    let mut years: BTreeMap<i32, [Option<f64>; 12]> = BTreeMap::new();
+    for (&(year, month), &ret) in returns {
+        years.entry(year).or_default()[month as usize - 1] = Some(ret);
+    }
+
+    for (year, months) in years {
+        let compounded = months.iter().flatten().fold(1.0, |acc, r| acc * (1.0 + r)) - 1.0;
+        let mut row = vec![year.to_string()];
+        row.extend(months.iter().map(|ret| ret.map(pct).unwrap_or_default()));
+        row.push(pct(compounded));
+        table.add_row(text_row(&row));
+    }
+    table
+}
+
+//the deepest drawdown periods, deepest first
+fn drawdown_table(periods: &[DrawdownPeriod]) -> Table {
+    let mut table = Table::new();
+    table.add_row(text_row(&[
+        "Start".to_string(),
+        "Trough".to_string(),
+        "Recovered".to_string(),
+        "Depth".to_string(),
+        "Days".to_string(),
+    ]));
+
+    let mut deepest: Vec<&DrawdownPeriod> = periods.iter().collect();
+    deepest.sort_by(|a, b| b.depth.total_cmp(&a.depth));
+
+    for period in deepest.into_iter().take(TOP_DRAWDOWNS) {
+        let days = period
+            .end
+            .map(|end| (end - period.start).num_days().to_string())
+            .unwrap_or_else(|| "-".to_string());
+        table.add_row(text_row(&[
+            period.start.date_naive().to_string(),
+            period.trough.date_naive().to_string(),
+            period
+                .end
+                .map(|end| end.date_naive().to_string())
+                .unwrap_or_else(|| "not recovered".to_string()),
+            pct(period.depth),
+            days,
+        ]));
+    }
+    table
+}
+
+//contracts per whole tick of slippage
+fn slippage_table(distribution: &BTreeMap<i64, u64>) -> Table {
+    let mut table = Table::new();
+    table.add_row(text_row(&["Ticks".to_string(), "Contracts".to_string()]));
+    for (ticks, contracts) in distribution {
+        table.add_row(text_row(&[ticks.to_string(), contracts.to_string()]));
+    }
+    table
+}
+
+//round trips per whole-r bucket
+fn r_table(distribution: &BTreeMap<i64, usize>) -> Table {
+    let mut table = Table::new();
+    table.add_row(text_row(&["R".to_string(), "Trades".to_string()]));
+    for (bucket, count) in distribution {
+        table.add_row(text_row(&[
+            format!("{}R to {}R", bucket, bucket + 1),
+            count.to_string(),
+        ]));
+    }
+    table
+}
+
+//round-trip statistics beyond the summary: direction split, holding time and exit reasons
+fn trade_table(result: &BacktestResult) -> Table {
+    let trips = result.round_trips();
+
+    let mut table = Table::new();
+    let mut add = |metric: &str, value: String| {
+        table.add_row(text_row(&[metric.to_string(), value]));
+    };
+
+    add("Round Trips", trips.len().to_string());
+    let summary = &result.summary;
+    for (label, stats) in [("Long", summary.long_stats), ("Short", summary.short_stats)] {
+        add(
+            &format!("{} Trades (Win Rate)", label),
+            format!("{} ({})", stats.num_trades, pct(stats.win_rate)),
+        );
+        add(
+            &format!("{} Net P&L", label),
+            summary.currency.format(stats.net_pnl),
+        );
+    }
+
+    if !trips.is_empty() {
+        let held_hours: f64 = trips
+            .iter()
+            .map(|t| (t.exit_time - t.entry_time).num_minutes() as f64 / 60.0)
+            .sum::<f64>()
+            / trips.len() as f64;
+        add("Avg Holding Time", format!("{:.1}h", held_hours));
+    }
+
+    let mut reasons: BTreeMap<String, usize> = BTreeMap::new();
+    for trip in &trips {
+        let reason = trip
+            .exit_reason
+            .map(|reason| format!("{:?}", reason))
+            .unwrap_or_else(|| "Untagged".to_string());
+        *reasons.entry(reason).or_default() += 1;
+    }
+    for (reason, count) in reasons {
+        add(&format!("Exits: {}", reason), count.to_string());
+    }
+    table
+}
+
+//renders a plain-text tear sheet: summary stats, monthly returns grid, worst drawdowns and
+//trade statistics
+pub fn tear_sheet(result: &BacktestResult) -> String {
+    let mut report = String::new();
+
+    if let (Some(first), Some(last)) = (result.equity_curve.first(), result.equity_curve.last()) {
+        report.push_str(&format!(
+            "Tear sheet: {} to {} ({} bars)\n",
+            first.timestamp.date_naive(),
+            last.timestamp.date_naive(),
+            result.equity_curve.len()
+        ));
+    }
+
+    for (title, table) in sections(result) {
+        report.push_str(&format!("\n{}\n{}", title, table));
+    }
+    report
+}
+
+//renders the tear sheet as a standalone html page, with an equity and underwater chart
+#[cfg(feature = "html-report")]
+pub fn tear_sheet_html(result: &BacktestResult) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Tear Sheet</title>\n\
+         <style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse;margin-bottom:1.5em}\
+         td{border:1px solid #ccc;padding:4px 8px;text-align:right}tr:first-child td{font-weight:bold}</style>\n\
+         </head>\n<body>\n<h1>Tear Sheet</h1>\n",
+    );
+
+    html.push_str("<h2>Equity</h2>\n");
+    html.push_str(&svg_chart(&result.equity_curve, |p| p.equity, "#1f77b4"));
+    html.push_str("<h2>Underwater</h2>\n");
+    let underwater = underwater_curve(&result.equity_curve);
+    html.push_str(&svg_chart(&underwater, |p| -p.drawdown, "#d62728"));
+
+    for (title, table) in sections(result) {
+        html.push_str(&format!("<h2>{}</h2>\n<table>\n", title));
+        for row in table.row_iter() {
+            html.push_str("<tr>");
+            for cell in row.iter() {
+                html.push_str(&format!("<td>{}</td>", escape_html(&cell.get_content())));
+            }
+            html.push_str("</tr>\n");
+        }
+        html.push_str("</table>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+//the curve with drawdown as a fraction of the running peak, whatever the result's mode
+#[cfg(feature = "html-report")]
+fn underwater_curve(equity_curve: &[EquityPoint]) -> Vec<EquityPoint> {
+    let mut peak = f64::MIN;
+    equity_curve
+        .iter()
+        .map(|point| {
+            peak = peak.max(point.equity);
+            let drawdown = if peak > 0.0 {
+                (peak - point.equity) / peak
+            } else {
+                0.0
+            };
+            EquityPoint::new(point.timestamp, point.equity, drawdown, point.returns)
+        })
+        .collect()
+}
+
+//draws one series of the curve as an inline svg line chart
+#[cfg(feature = "html-report")]
+fn svg_chart(curve: &[EquityPoint], value: impl Fn(&EquityPoint) -> f64, color: &str) -> String {
+    const WIDTH: f64 = 800.0;
+    const HEIGHT: f64 = 200.0;
+
+    let values: Vec<f64> = curve.iter().map(value).collect();
+    if values.len() < 2 {
+        return String::new();
+    }
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    let range = if max > min { max - min } else { 1.0 };
+
+    let points: Vec<String> = values
+        .iter()
+        .enumerate()
+        .map(|(i, v)| {
+            let x = i as f64 / (values.len() - 1) as f64 * WIDTH;
+            let y = HEIGHT - (v - min) / range * HEIGHT;
+            format!("{:.1},{:.1}", x, y)
+        })
+        .collect();
+
+    format!(
+        "<svg width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <polyline fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\" points=\"{points}\"/></svg>\n",
+        w = WIDTH,
+        h = HEIGHT,
+        color = color,
+        points = points.join(" ")
+    )
+}
+
+#[cfg(feature = "html-report")]
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::timeseries::calculate_equity_curve;
+    use chrono::TimeZone;
+
+    fn daily_curve(equity: &[f64]) -> Vec<EquityPoint> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let timestamps: Vec<_> = (0..equity.len())
+            .map(|i| start + chrono::Duration::days(i as i64))
+            .collect();
+        calculate_equity_curve(&timestamps, equity, equity[0])
+    }
+
+    //a 25% drawdown recovered on the fifth point, then a 20% one still open at the end
+    #[test]
+    fn drawdown_periods_find_troughs_and_recoveries() {
+        let curve = daily_curve(&[100.0, 120.0, 90.0, 100.0, 120.0, 130.0, 110.0, 104.0, 115.0]);
+        let periods = drawdown_periods(&curve);
+        assert_eq!(periods.len(), 2);
+
+        let recovered = &periods[0];
+        assert_eq!(recovered.start, curve[1].timestamp);
+        assert_eq!(recovered.trough, curve[2].timestamp);
+        assert_eq!(recovered.end, Some(curve[4].timestamp));
+        assert!((recovered.depth - 0.25).abs() < 1e-12);
+
+        let open = &periods[1];
+        assert_eq!(open.start, curve[5].timestamp);
+        assert_eq!(open.trough, curve[7].timestamp);
+        assert_eq!(open.end, None);
+        assert!((open.depth - 0.2).abs() < 1e-12);
+
+        assert_eq!(worst_drawdown(&curve).unwrap().trough, curve[2].timestamp);
+    }
+
+    #[test]
+    fn curve_that_never_falls_has_no_drawdown_periods() {
+        assert!(drawdown_periods(&daily_curve(&[100.0, 100.0, 110.0])).is_empty());
+        assert!(drawdown_periods(&[]).is_empty());
+    }
+
+    #[test]
+    fn monthly_returns_chain_month_end_equity() {
+        let point = |month, day, equity| EquityPoint {
+            timestamp: Utc.with_ymd_and_hms(2024, month, day, 0, 0, 0).unwrap(),
+            equity,
+            drawdown: 0.0,
+            returns: 0.0,
+        };
+        let curve = [
+            point(1, 30, 100.0),
+            point(1, 31, 110.0),
+            point(2, 29, 99.0),
+            point(3, 1, 108.9),
+        ];
+        let returns = monthly_returns(&curve, 100.0);
+
+        let months: Vec<_> = returns.keys().copied().collect();
+        assert_eq!(months, vec![(2024, 1), (2024, 2), (2024, 3)]);
+        for (ret, expected) in returns.values().zip([0.1, -0.1, 0.1]) {
+            assert!((ret - expected).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn drawdown_csv_has_a_header_and_a_row_per_point() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("drawdown.csv");
+        write_drawdown_csv(&daily_curve(&[100.0, 120.0, 90.0]), &path).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<_> = contents.lines().collect();
+        assert_eq!(
+            lines,
+            vec![
+                "timestamp,equity,peak,drawdown,underwater_pct",
+                "2024-01-02T00:00:00+00:00,100,100,0,0",
+                "2024-01-03T00:00:00+00:00,120,120,0,0",
+                "2024-01-04T00:00:00+00:00,90,120,0.25,-25",
+            ]
+        );
+    }
+}
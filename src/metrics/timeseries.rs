@@ -21,31 +21,118 @@ impl EquityPoint {
     }
 }
 
-//calculates the equity curve with drawdowns
+//how drawdown is measured on the equity curve
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum DrawdownMode {
+    //fraction below the high-water mark
+    #[default]
+    PercentFromPeak,
+    //dollars below the high-water mark
+    DollarFromPeak,
+    //fraction below the initial balance (zero while above it)
+    PercentFromStart,
+    //dollars below the initial balance (zero while above it)
+    DollarFromStart,
+}
+
+impl DrawdownMode {
+    //parse drawdown mode from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "percent" | "pct" | "percent-from-peak" => Some(DrawdownMode::PercentFromPeak),
+            "dollar" | "dollar-from-peak" => Some(DrawdownMode::DollarFromPeak),
+            "percent-from-start" | "inception" => Some(DrawdownMode::PercentFromStart),
+            "dollar-from-start" => Some(DrawdownMode::DollarFromStart),
+            _ => None,
+        }
+    }
+
+    //returns true if drawdowns are in dollars rather than a fraction
+    pub fn is_dollar(&self) -> bool {
+        matches!(
+            self,
+            DrawdownMode::DollarFromPeak | DrawdownMode::DollarFromStart
+        )
+    }
+
+    //drawdown of equity given the running peak and the initial balance
+    fn drawdown(&self, equity: f64, peak: f64, initial_balance: f64) -> f64 {
+        let reference = match self {
+            DrawdownMode::PercentFromPeak | DrawdownMode::DollarFromPeak => peak,
+            DrawdownMode::PercentFromStart | DrawdownMode::DollarFromStart => initial_balance,
+        };
+        let below = (reference - equity).max(0.0);
+
+        if self.is_dollar() {
+            below
+        } else if reference > 0.0 {
+            below / reference
+        } else {
+            0.0
+        }
+    }
+}
+
+//calculates the equity curve with percent-from-peak drawdowns
 pub fn calculate_equity_curve(
     timestamps: &[DateTime<Utc>],
     equity_values: &[f64],
     initial_balance: f64,
+) -> Vec<EquityPoint> {
+    calculate_equity_curve_with_mode(
+        timestamps,
+        equity_values,
+        initial_balance,
+        DrawdownMode::default(),
+    )
+}
+
+//calculates the equity curve with drawdowns measured by mode
+pub fn calculate_equity_curve_with_mode(
+    timestamps: &[DateTime<Utc>],
+    equity_values: &[f64],
+    initial_balance: f64,
+    mode: DrawdownMode,
+) -> Vec<EquityPoint> {
+    calculate_equity_curve_intrabar(
+        timestamps,
+        equity_values,
+        equity_values,
+        initial_balance,
+        mode,
+    )
+}
+
+//calculates the equity curve, measuring drawdown at each bar's worst-case equity
+//the peak still tracks marked equity, so intrabar dips deepen drawdown but spikes don't raise the peak
+pub fn calculate_equity_curve_intrabar(
+    timestamps: &[DateTime<Utc>],
+    equity_values: &[f64],
+    worst_equity_values: &[f64],
+    initial_balance: f64,
+    mode: DrawdownMode,
 ) -> Vec<EquityPoint> {
     let mut curve = Vec::with_capacity(timestamps.len());
     let mut peak = initial_balance;
     let mut prev_equity = initial_balance;
 
     for (i, (&timestamp, &equity)) in timestamps.iter().zip(equity_values.iter()).enumerate() {
+        let worst_equity = worst_equity_values
+            .get(i)
+            .copied()
+            .unwrap_or(equity)
+            .min(equity);
+
         //update peak
         if equity > peak {
             peak = equity;
         }
 
         //calculate drawdown
-        let drawdown = if peak > 0.0 {
-            (peak - equity) / peak
-        } else {
-            0.0
-        };
+        let drawdown = mode.drawdown(worst_equity, peak, initial_balance);
 
         //calculate returns
-        let returns = if i == 0 {
+        let returns = if i == 0 || prev_equity <= 0.0 {
             0.0
         } else {
             (equity - prev_equity) / prev_equity
@@ -74,8 +161,147 @@ pub fn calculate_returns(equity_values: &[f64]) -> Vec<f64> {
 
     let mut returns = Vec::with_capacity(equity_values.len() - 1);
     for i in 1..equity_values.len() {
-        let ret = (equity_values[i] - equity_values[i - 1]) / equity_values[i - 1];
+        //a zero or negative prior equity has no meaningful return
+        let ret = if equity_values[i - 1] <= 0.0 {
+            0.0
+        } else {
+            (equity_values[i] - equity_values[i - 1]) / equity_values[i - 1]
+        };
         returns.push(ret);
     }
     returns
 }
+
+//calculates period returns with external cash flows taken out
+//a flow (index, amount) is a deposit (positive) or withdrawal (negative) already included
+//in equity_values[index], so it isn't counted as a gain for the period ending there
+pub fn calculate_flow_adjusted_returns(equity_values: &[f64], flows: &[(usize, f64)]) -> Vec<f64> {
+    if equity_values.len() < 2 {
+        return vec![];
+    }
+
+    let mut returns = Vec::with_capacity(equity_values.len() - 1);
+    for i in 1..equity_values.len() {
+        let flow: f64 = flows
+            .iter()
+            .filter(|(index, _)| *index == i)
+            .map(|(_, amount)| amount)
+            .sum();
+
+        let ret = if equity_values[i - 1] == 0.0 {
+            0.0
+        } else {
+            (equity_values[i] - flow - equity_values[i - 1]) / equity_values[i - 1]
+        };
+        returns.push(ret);
+    }
+    returns
+}
+
+//calculates the time-weighted return: period returns, net of cash flows, compounded
+//unlike (final - initial) / initial, deposits and withdrawals don't count as performance
+pub fn time_weighted_return(equity_values: &[f64], flows: &[(usize, f64)]) -> f64 {
+    calculate_flow_adjusted_returns(equity_values, flows)
+        .iter()
+        .fold(1.0, |growth, ret| growth * (1.0 + ret))
+        - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn empty_equity_gives_an_empty_curve() {
+        assert!(calculate_equity_curve(&[], &[], 100000.0).is_empty());
+    }
+
+    #[test]
+    fn single_point_curve_has_no_return_or_drawdown() {
+        let timestamp = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let curve = calculate_equity_curve(&[timestamp], &[100000.0], 100000.0);
+
+        assert_eq!(curve.len(), 1);
+        assert_eq!(curve[0].returns, 0.0);
+        assert_eq!(curve[0].drawdown, 0.0);
+    }
+
+    //+10%, a 100 deposit, then +10% on the larger balance
+    #[test]
+    fn deposit_inflates_total_return_but_not_twr() {
+        let equity = [100.0, 110.0, 210.0, 231.0];
+        let flows = [(2, 100.0)];
+
+        let total_return = (equity[3] - equity[0]) / equity[0];
+        let twr = time_weighted_return(&equity, &flows);
+
+        assert!((total_return - 1.31).abs() < 1e-12);
+        assert!((twr - 0.21).abs() < 1e-12);
+        let returns = calculate_flow_adjusted_returns(&equity, &flows);
+        assert!(returns[1].abs() < 1e-12);
+    }
+
+    #[test]
+    fn twr_without_flows_is_the_total_return() {
+        let equity = [100.0, 90.0, 120.0];
+
+        assert!((time_weighted_return(&equity, &[]) - 0.2).abs() < 1e-12);
+    }
+
+    //from 1000 up to a 1200 peak, down to 900, back to 1100, then 950
+    fn curve(mode: DrawdownMode) -> Vec<EquityPoint> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let timestamps: Vec<_> = (0..5).map(|i| start + chrono::Duration::days(i)).collect();
+        let equity = [1000.0, 1200.0, 900.0, 1100.0, 950.0];
+        calculate_equity_curve_with_mode(&timestamps, &equity, 1000.0, mode)
+    }
+
+    fn drawdowns(mode: DrawdownMode) -> Vec<f64> {
+        curve(mode).iter().map(|point| point.drawdown).collect()
+    }
+
+    fn assert_all_close(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(
+                (a - e).abs() < 1e-12,
+                "expected {:?}, got {:?}",
+                expected,
+                actual
+            );
+        }
+    }
+
+    #[test]
+    fn dollar_from_peak_drawdowns() {
+        let curve = drawdowns(DrawdownMode::DollarFromPeak);
+        assert_eq!(curve, vec![0.0, 0.0, 300.0, 100.0, 250.0]);
+    }
+
+    #[test]
+    fn percent_from_start_drawdowns_are_zero_above_the_start() {
+        let curve = drawdowns(DrawdownMode::PercentFromStart);
+        assert_all_close(&curve, &[0.0, 0.0, 0.1, 0.0, 0.05]);
+    }
+
+    #[test]
+    fn dollar_from_start_drawdowns_are_zero_above_the_start() {
+        let curve = drawdowns(DrawdownMode::DollarFromStart);
+        assert_eq!(curve, vec![0.0, 0.0, 100.0, 0.0, 50.0]);
+    }
+
+    #[test]
+    fn max_drawdown_depends_on_the_mode() {
+        let max = |mode| max_drawdown(&curve(mode));
+        assert_all_close(
+            &[
+                max(DrawdownMode::PercentFromPeak),
+                max(DrawdownMode::DollarFromPeak),
+                max(DrawdownMode::PercentFromStart),
+                max(DrawdownMode::DollarFromStart),
+            ],
+            &[0.25, 300.0, 0.1, 100.0],
+        );
+    }
+}
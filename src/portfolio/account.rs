@@ -1,7 +1,103 @@
-use crate::engine::execution::Fill;
+use crate::engine::execution::{Fill, OrderSide};
 use crate::instrument::FuturesContract;
 use crate::portfolio::position::Position;
+use crate::portfolio::trade_log::TradeLog;
+use log::debug;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use thiserror::Error;
+
+//reason a fill couldn't be booked
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum AccountError {
+    #[error("Fill for {fill_symbol} can't be booked against the {contract_symbol} contract")]
+    ContractMismatch {
+        fill_symbol: String,
+        contract_symbol: String,
+    },
+}
+
+//when per-contract costs (commission + slippage) are charged
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CostTiming {
+    //charge one side on every fill
+    #[default]
+    PerFill,
+    //charge both sides when a fill opens contracts, nothing on exits
+    RoundTurnOnEntry,
+    //charge both sides when a fill closes contracts, nothing on entries
+    RoundTurnOnExit,
+}
+
+impl CostTiming {
+    //parse cost timing from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "per_fill" | "per-fill" | "fill" => Some(CostTiming::PerFill),
+            "entry" | "round_turn_on_entry" => Some(CostTiming::RoundTurnOnEntry),
+            "exit" | "round_turn_on_exit" => Some(CostTiming::RoundTurnOnExit),
+            _ => None,
+        }
+    }
+}
+
+//when charged costs come out of cash
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum FeeSettlement {
+    //deduct costs from cash as each fill is booked
+    #[default]
+    Immediate,
+    //accrue costs and deduct them once per trading day, at the day's last bar
+    Daily,
+}
+
+impl FeeSettlement {
+    //parse fee settlement from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "immediate" | "fill" => Some(FeeSettlement::Immediate),
+            "daily" | "day" => Some(FeeSettlement::Daily),
+            _ => None,
+        }
+    }
+}
+
+//whether opposing fills net against each other or are held as separate lots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AccountMode {
+    //one net position per symbol
+    #[default]
+    Netting,
+    //independent long and short lots per symbol
+    //only fills of orders tagged with an exit reason (or reduce-only) close lots; others open
+    //new ones
+    Hedging,
+}
+
+impl AccountMode {
+    //parse account mode from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "netting" | "net" => Some(AccountMode::Netting),
+            "hedging" | "hedge" => Some(AccountMode::Hedging),
+            _ => None,
+        }
+    }
+}
+
+//point-in-time view of an account, for reporting and per-bar logging
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountSnapshot {
+    pub cash: f64,
+    //cash plus unrealized pnl at the snapshot prices
+    pub equity: f64,
+    pub margin_used: f64,
+    pub buying_power: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    //open (non-flat) positions, ordered by symbol
+    pub positions: Vec<Position>,
+}
 
 //represents a trading account with positions and cash
 #[derive(Debug, Clone)]
@@ -22,13 +118,31 @@ pub struct Account {
     pub open_positions: HashMap<String, Position>,
 
     //complete trade log
-    pub trade_log: Vec<Fill>,
+    pub trade_log: TradeLog,
 
     //commission per contract per side
     pub commission_per_contract: f64,
 
+    //least per-contract commission charged for one order side (0 for no minimum)
+    pub min_commission_per_order: f64,
+
+    //flat ticket fee per order side, whatever its size
+    pub commission_per_order: f64,
+
     //slippage per contract per side
     pub slippage_per_contract: f64,
+
+    //when costs are charged (per fill or as a round turn)
+    pub cost_timing: CostTiming,
+
+    //netting or hedging positions
+    pub mode: AccountMode,
+
+    //when costs are deducted from cash
+    pub fee_settlement: FeeSettlement,
+
+    //costs booked but not yet deducted from cash (daily settlement)
+    pub accrued_fees: f64,
 }
 
 impl Account {
@@ -44,20 +158,104 @@ impl Account {
             equity: initial_balance,
             margin_used: 0.0,
             open_positions: HashMap::new(),
-            trade_log: Vec::new(),
+            trade_log: TradeLog::new(),
             commission_per_contract,
+            min_commission_per_order: 0.0,
+            commission_per_order: 0.0,
             slippage_per_contract,
+            cost_timing: CostTiming::default(),
+            mode: AccountMode::default(),
+            fee_settlement: FeeSettlement::default(),
+            accrued_fees: 0.0,
         }
     }
 
+    //sets whether positions net or are held as separate lots
+    pub fn set_mode(&mut self, mode: AccountMode) {
+        self.mode = mode;
+    }
+
+    //sets when costs are deducted from cash
+    pub fn set_fee_settlement(&mut self, fee_settlement: FeeSettlement) {
+        self.fee_settlement = fee_settlement;
+    }
+
+    //deducts accrued costs from cash (the daily settlement)
+    pub fn settle_fees(&mut self) {
+        if self.accrued_fees != 0.0 {
+            debug!("settling {:.2} of accrued fees", self.accrued_fees);
+            self.cash -= self.accrued_fees;
+            self.accrued_fees = 0.0;
+        }
+    }
+
+    //sets the per-order parts of the commission: a minimum for the per-contract commission
+    //of each order side and a flat ticket fee on top of it
+    pub fn set_order_commission(&mut self, min_per_order: f64, per_order_fee: f64) {
+        self.min_commission_per_order = min_per_order;
+        self.commission_per_order = per_order_fee;
+    }
+
+    //sets when costs are charged
+    pub fn set_cost_timing(&mut self, cost_timing: CostTiming) {
+        self.cost_timing = cost_timing;
+    }
+
     //processes a fill and updates the account
-    pub fn process_fill(&mut self, fill: Fill, contract: &FuturesContract) {
-        //calculate total costs (commission + slippage)
-        let total_cost =
-            (self.commission_per_contract + self.slippage_per_contract) * fill.qty.abs() as f64;
+    //the contract must be the fill's own; another symbol's tick math would corrupt the pnl
+    pub fn process_fill(
+        &mut self,
+        mut fill: Fill,
+        contract: &FuturesContract,
+    ) -> Result<(), AccountError> {
+        if fill.symbol != contract.symbol {
+            return Err(AccountError::ContractMismatch {
+                fill_symbol: fill.symbol,
+                contract_symbol: contract.symbol.clone(),
+            });
+        }
+
+        //a reduce-only fill can close at most the opposite side held; with nothing to close
+        //it's dropped rather than booked as an entry
+        if fill.reduce_only {
+            let opposite = self
+                .open_positions
+                .get(&fill.symbol)
+                .map(|p| match fill.side {
+                    OrderSide::Buy => p.short_qty(),
+                    OrderSide::Sell => p.long_qty(),
+                })
+                .unwrap_or(0) as i32;
+            let qty = fill.qty.abs().min(opposite);
+            if qty == 0 {
+                debug!(
+                    "reduce-only fill {} dropped: no position to reduce",
+                    fill.id
+                );
+                return Ok(());
+            }
+            fill.qty = fill.qty.signum() * qty;
+        }
 
-        //deduct costs from cash
-        self.cash -= total_cost;
+        //calculate total costs (commission + slippage)
+        //fills priced off bid/ask already paid the spread, so skip slippage
+        let slippage = if fill.filled_at_quote {
+            0.0
+        } else {
+            self.slippage_per_contract
+        };
+        let (sides, side_qty) = self.charged_sides(&fill);
+        let commission = sides * self.side_commission(side_qty);
+        let slippage = slippage * sides * side_qty;
+        let total_cost = commission + slippage;
+
+        //deduct (or accrue) costs and record them on the fill
+        match self.fee_settlement {
+            FeeSettlement::Immediate => self.cash -= total_cost,
+            FeeSettlement::Daily => self.accrued_fees += total_cost,
+        }
+        fill.commission = commission;
+        fill.slippage = slippage;
 
         //get or create position
         let position = self
@@ -65,17 +263,100 @@ impl Account {
             .entry(fill.symbol.clone())
             .or_insert_with(|| Position::new(fill.symbol.clone()));
 
+        //a fill reducing a position with a stop carries the position's initial risk per contract
+        let reducing = position.net_qty != 0 && position.net_qty.signum() != fill.qty.signum();
+        if reducing {
+            fill.risk_per_contract = position.initial_stop.map(|stop| {
+                contract
+                    .pnl_between(position.avg_entry_price, stop, 1)
+                    .abs()
+            });
+        }
+
         //update position and get realized pnl
-        let realized_pnl = position.update_with_fill(fill.qty, fill.fill_price, contract);
+        let realized_pnl = match self.mode {
+            AccountMode::Netting => position.update_with_fill(fill.qty, fill.fill_price, contract),
+            AccountMode::Hedging => position.update_lots_with_fill(
+                fill.qty,
+                fill.fill_price,
+                fill.exit_reason.is_some() || fill.reduce_only,
+                contract,
+            ),
+        };
 
         //add realized pnl to cash
         self.cash += realized_pnl;
+        fill.realized_pnl = realized_pnl;
+        debug!(
+            "fill {} booked: {} {} @ {}, fees {:.2}, realized pnl {:.2}, cash {:.2}",
+            fill.id,
+            fill.qty,
+            fill.symbol,
+            fill.fill_price,
+            fill.fees(),
+            realized_pnl,
+            self.cash
+        );
 
         //update margin used
         self.update_margin_used(contract);
 
         //log the fill
         self.trade_log.push(fill);
+        Ok(())
+    }
+
+    //returns the order sides a fill is charged for under the cost timing and the contracts
+    //in each; a reversing fill both closes and opens contracts
+    fn charged_sides(&self, fill: &Fill) -> (f64, f64) {
+        let closing = self.closing_qty(fill);
+        let opening = fill.qty.abs() - closing;
+
+        match self.cost_timing {
+            CostTiming::PerFill => (1.0, fill.qty.abs() as f64),
+            CostTiming::RoundTurnOnEntry => (2.0, opening as f64),
+            CostTiming::RoundTurnOnExit => (2.0, closing as f64),
+        }
+    }
+
+    //returns the contracts a fill closes: against the opposite net position when netting, and
+    //against the opposite lots when hedging, where only exit and reduce-only fills close
+    fn closing_qty(&self, fill: &Fill) -> i32 {
+        let position = match self.open_positions.get(&fill.symbol) {
+            Some(position) => position,
+            None => return 0,
+        };
+
+        match self.mode {
+            AccountMode::Netting => {
+                let held = position.net_qty;
+                if held != 0 && held.signum() != fill.qty.signum() {
+                    fill.qty.abs().min(held.abs())
+                } else {
+                    0
+                }
+            }
+            AccountMode::Hedging => {
+                if fill.exit_reason.is_none() && !fill.reduce_only {
+                    return 0;
+                }
+                let opposite = match fill.side {
+                    OrderSide::Buy => position.short_qty(),
+                    OrderSide::Sell => position.long_qty(),
+                };
+                fill.qty.abs().min(opposite as i32)
+            }
+        }
+    }
+
+    //commission for one order side of qty contracts: the per-contract rate, raised to the
+    //order minimum, plus the ticket fee
+    fn side_commission(&self, qty: f64) -> f64 {
+        if qty == 0.0 {
+            return 0.0;
+        }
+        (self.commission_per_contract * qty).max(self.min_commission_per_order)
+            + self.commission_per_order
     }
 
     //updates total equity based on current market prices
@@ -96,13 +377,28 @@ impl Account {
     }
 
     //updates margin used based on current positions
+    //margin is held on every open contract, so offsetting hedged lots both count
     fn update_margin_used(&mut self, contract: &FuturesContract) {
         self.margin_used = 0.0;
 
         for position in self.open_positions.values() {
             if !position.is_flat() {
-                self.margin_used += contract.initial_margin_requirement(position.net_qty);
+                let gross = (position.long_qty() + position.short_qty()) as i32;
+                self.margin_used += contract.initial_margin_requirement(gross);
+            }
+        }
+    }
+
+    //records the protective stop an open position was entered with, which sets its initial
+    //risk for r-multiples; later stops (trailed or re-placed) don't change it
+    //returns false if the symbol is flat or already has an initial stop
+    pub fn set_initial_stop(&mut self, symbol: &str, stop_price: f64) -> bool {
+        match self.open_positions.get_mut(symbol) {
+            Some(position) if !position.is_flat() && position.initial_stop.is_none() => {
+                position.initial_stop = Some(stop_price);
+                true
             }
+            _ => false,
         }
     }
 
@@ -168,8 +464,329 @@ impl Account {
         self.total_realized_pnl() + self.total_unrealized_pnl(prices, contracts)
     }
 
+    //returns a snapshot of the account marked at the given prices
+    pub fn snapshot(
+        &self,
+        prices: &HashMap<String, f64>,
+        contracts: &HashMap<String, FuturesContract>,
+    ) -> AccountSnapshot {
+        let unrealized_pnl = self.total_unrealized_pnl(prices, contracts);
+
+        let mut positions: Vec<Position> = self
+            .open_positions
+            .values()
+            .filter(|p| !p.is_flat())
+            .cloned()
+            .collect();
+        positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+
+        AccountSnapshot {
+            cash: self.cash,
+            equity: self.cash + unrealized_pnl,
+            margin_used: self.margin_used,
+            buying_power: self.buying_power(),
+            realized_pnl: self.total_realized_pnl(),
+            unrealized_pnl,
+            positions,
+        }
+    }
+
     //returns the total return as a percentage
     pub fn total_return(&self) -> f64 {
         (self.equity - self.initial_balance) / self.initial_balance
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::execution::{ExitReason, Order};
+    use chrono::Utc;
+
+    fn fill(id: u64, side: OrderSide, qty: u32, exit: bool) -> Fill {
+        let mut order = Order::market(id, Utc::now(), "ES".to_string(), qty, side);
+        if exit {
+            order.exit_reason = Some(ExitReason::SignalFlip);
+        }
+        Fill::from_order(id, &order, 4000.0, 0.0)
+    }
+
+    fn hedging_account() -> Account {
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        account.set_mode(AccountMode::Hedging);
+        account
+    }
+
+    #[test]
+    fn hedging_buy_then_sell_keeps_two_lots() {
+        let contract = FuturesContract::es("H24");
+        let mut account = hedging_account();
+        account
+            .process_fill(fill(1, OrderSide::Buy, 1, false), &contract)
+            .unwrap();
+        account
+            .process_fill(fill(2, OrderSide::Sell, 1, false), &contract)
+            .unwrap();
+
+        let position = account.get_position("ES").unwrap();
+        assert_eq!(position.net_qty, 0);
+        assert!(!position.is_flat());
+        assert_eq!(position.lots.len(), 2);
+        assert_eq!((position.long_qty(), position.short_qty()), (1, 1));
+    }
+
+    #[test]
+    fn hedged_lots_both_hold_margin() {
+        let contract = FuturesContract::es("H24");
+        let mut account = hedging_account();
+        account
+            .process_fill(fill(1, OrderSide::Buy, 2, false), &contract)
+            .unwrap();
+        account
+            .process_fill(fill(2, OrderSide::Sell, 1, false), &contract)
+            .unwrap();
+
+        assert_eq!(account.margin_used, contract.initial_margin_requirement(3));
+    }
+
+    #[test]
+    fn hedged_opening_sell_is_not_charged_as_an_exit() {
+        let contract = FuturesContract::es("H24");
+        let mut account = hedging_account();
+        account.set_cost_timing(CostTiming::RoundTurnOnExit);
+        account
+            .process_fill(fill(1, OrderSide::Buy, 1, false), &contract)
+            .unwrap();
+
+        //opens a short lot next to the long one: an entry, so nothing is charged
+        account
+            .process_fill(fill(2, OrderSide::Sell, 1, false), &contract)
+            .unwrap();
+        assert_eq!(account.trade_log.fills()[1].commission, 0.0);
+
+        //closes the long lot: both sides are charged on the exit
+        account
+            .process_fill(fill(3, OrderSide::Sell, 1, true), &contract)
+            .unwrap();
+        assert_eq!(account.trade_log.fills()[2].commission, 5.0);
+    }
+
+    fn netting_account_after(cost_timing: CostTiming, fills: &[(OrderSide, u32)]) -> Account {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        account.set_cost_timing(cost_timing);
+        for (i, &(side, qty)) in fills.iter().enumerate() {
+            account
+                .process_fill(fill(i as u64 + 1, side, qty, false), &contract)
+                .unwrap();
+        }
+        account
+    }
+
+    #[test]
+    fn round_turn_on_entry_charges_once_per_trade() {
+        let trades = [
+            (OrderSide::Buy, 2),
+            (OrderSide::Sell, 2),
+            (OrderSide::Sell, 1),
+            (OrderSide::Buy, 1),
+        ];
+        let on_entry = netting_account_after(CostTiming::RoundTurnOnEntry, &trades);
+        let per_fill = netting_account_after(CostTiming::PerFill, &trades);
+
+        //entries carry both sides, exits carry nothing
+        let commissions: Vec<f64> = on_entry
+            .trade_log
+            .fills()
+            .iter()
+            .map(|fill| fill.commission)
+            .collect();
+        assert_eq!(commissions, vec![10.0, 0.0, 5.0, 0.0]);
+        assert_eq!(
+            on_entry.trade_log.total_commission(),
+            per_fill.trade_log.total_commission()
+        );
+    }
+
+    #[test]
+    fn round_turn_on_entry_charges_the_opening_part_of_a_reversal() {
+        let account = netting_account_after(
+            CostTiming::RoundTurnOnEntry,
+            &[(OrderSide::Buy, 1), (OrderSide::Sell, 2)],
+        );
+
+        //the reversal closes one contract and opens one
+        assert_eq!(account.trade_log.fills()[1].commission, 5.0);
+    }
+
+    #[test]
+    fn fill_for_another_contract_is_rejected_untouched() {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        let order = Order::market(1, Utc::now(), "NQ".to_string(), 1, OrderSide::Buy);
+        let nq_fill = Fill::from_order(1, &order, 17000.0, 0.0);
+
+        assert_eq!(
+            account.process_fill(nq_fill, &contract),
+            Err(AccountError::ContractMismatch {
+                fill_symbol: "NQ".to_string(),
+                contract_symbol: "ES".to_string(),
+            })
+        );
+        assert_eq!(account.cash, 100000.0);
+        assert_eq!(account.margin_used, 0.0);
+        assert!(account.get_position("NQ").is_none());
+        assert!(account.trade_log.fills().is_empty());
+    }
+
+    #[test]
+    fn snapshot_mid_run_reports_open_and_closed_pnl() {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        let priced = |id, side, qty, price| {
+            let order = Order::market(id, Utc::now(), "ES".to_string(), qty, side);
+            Fill::from_order(id, &order, price, 0.0)
+        };
+        account
+            .process_fill(priced(1, OrderSide::Buy, 2, 4000.0), &contract)
+            .unwrap();
+        account
+            .process_fill(priced(2, OrderSide::Sell, 1, 4010.0), &contract)
+            .unwrap();
+
+        let prices = HashMap::from([("ES".to_string(), 4020.0)]);
+        let contracts = HashMap::from([("ES".to_string(), contract.clone())]);
+        let snapshot = account.snapshot(&prices, &contracts);
+
+        //three contracts of commission; 10 points banked on one, 20 open on the other
+        assert_eq!(snapshot.cash, 100000.0 - 7.5 + 500.0);
+        assert_eq!(snapshot.realized_pnl, 500.0);
+        assert_eq!(snapshot.unrealized_pnl, 1000.0);
+        assert_eq!(snapshot.equity, snapshot.cash + 1000.0);
+        assert_eq!(snapshot.margin_used, 13000.0);
+        assert_eq!(snapshot.buying_power, snapshot.cash - 13000.0);
+        assert_eq!(snapshot.positions.len(), 1);
+        assert_eq!(snapshot.positions[0].net_qty, 1);
+    }
+
+    fn reduce_only(id: u64, side: OrderSide, qty: u32) -> Fill {
+        let mut fill = fill(id, side, qty, false);
+        fill.reduce_only = true;
+        fill
+    }
+
+    #[test]
+    fn reduce_only_sell_larger_than_the_long_only_closes_it() {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        account
+            .process_fill(fill(1, OrderSide::Buy, 2, false), &contract)
+            .unwrap();
+        account
+            .process_fill(reduce_only(2, OrderSide::Sell, 5), &contract)
+            .unwrap();
+
+        assert_eq!(account.get_position("ES").map_or(0, |p| p.net_qty), 0);
+        assert_eq!(account.trade_log.fills()[1].qty, -2);
+        assert_eq!(account.margin_used, 0.0);
+    }
+
+    #[test]
+    fn reduce_only_without_an_opposite_position_is_dropped() {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        account
+            .process_fill(reduce_only(1, OrderSide::Sell, 1), &contract)
+            .unwrap();
+        account
+            .process_fill(fill(2, OrderSide::Buy, 1, false), &contract)
+            .unwrap();
+        account
+            .process_fill(reduce_only(3, OrderSide::Buy, 1), &contract)
+            .unwrap();
+
+        assert_eq!(account.trade_log.fills().len(), 1);
+        assert_eq!(account.get_position("ES").unwrap().net_qty, 1);
+        assert_eq!(account.cash, 100000.0 - 2.5);
+    }
+
+    #[test]
+    fn hedged_reduce_only_closes_lots_instead_of_opening_one() {
+        let contract = FuturesContract::es("H24");
+        let mut account = hedging_account();
+        account
+            .process_fill(fill(1, OrderSide::Buy, 2, false), &contract)
+            .unwrap();
+        account
+            .process_fill(reduce_only(2, OrderSide::Sell, 3), &contract)
+            .unwrap();
+
+        assert!(account.get_position("ES").is_none_or(|p| p.is_flat()));
+    }
+
+    #[test]
+    fn commission_and_slippage_sum_to_the_lumped_cost() {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 1.25);
+        account
+            .process_fill(fill(1, OrderSide::Buy, 3, false), &contract)
+            .unwrap();
+        account
+            .process_fill(fill(2, OrderSide::Sell, 3, false), &contract)
+            .unwrap();
+
+        //the old single fee was (commission + slippage) per contract per side
+        for booked in account.trade_log.fills() {
+            assert_eq!((booked.commission, booked.slippage), (7.5, 3.75));
+            assert_eq!(booked.fees(), 3.0 * (2.5 + 1.25));
+        }
+        assert_eq!(
+            account.trade_log.total_commission() + account.trade_log.total_slippage(),
+            account.trade_log.total_fees()
+        );
+        assert_eq!(account.cash, 100000.0 - 2.0 * 3.0 * (2.5 + 1.25));
+    }
+
+    #[test]
+    fn fill_at_a_quote_records_commission_but_no_slippage() {
+        let contract = FuturesContract::es("H24");
+        let mut account = Account::new(100000.0, 2.5, 1.25);
+        let mut quoted = fill(1, OrderSide::Buy, 2, false);
+        quoted.filled_at_quote = true;
+        account.process_fill(quoted, &contract).unwrap();
+        account
+            .process_fill(fill(2, OrderSide::Sell, 2, false), &contract)
+            .unwrap();
+
+        let fills = account.trade_log.fills();
+        assert_eq!((fills[0].commission, fills[0].slippage), (5.0, 0.0));
+        assert_eq!((fills[1].commission, fills[1].slippage), (5.0, 2.5));
+        assert_eq!(account.trade_log.total_slippage(), 2.5);
+    }
+
+    fn commission_for(qty: u32, min_per_order: f64, per_order_fee: f64) -> f64 {
+        let mut account = Account::new(100000.0, 2.5, 0.0);
+        account.set_order_commission(min_per_order, per_order_fee);
+        account
+            .process_fill(
+                fill(1, OrderSide::Buy, qty, false),
+                &FuturesContract::es("H24"),
+            )
+            .unwrap();
+        account.trade_log.fills()[0].commission
+    }
+
+    #[test]
+    fn small_orders_pay_the_minimum_and_large_ones_the_rate() {
+        assert_eq!(commission_for(1, 10.0, 0.0), 10.0);
+        assert_eq!(commission_for(10, 10.0, 0.0), 25.0);
+        assert_eq!(commission_for(1, 0.0, 0.0), 2.5);
+    }
+
+    #[test]
+    fn ticket_fee_is_charged_once_per_order_on_top() {
+        assert_eq!(commission_for(1, 0.0, 1.5), 4.0);
+        assert_eq!(commission_for(10, 0.0, 1.5), 26.5);
+        assert_eq!(commission_for(1, 10.0, 1.5), 11.5);
+    }
+}
@@ -1,5 +1,7 @@
 pub mod account;
 pub mod position;
+pub mod trade_log;
 
-pub use account::Account;
-pub use position::Position;
+pub use account::{Account, AccountError, AccountMode, AccountSnapshot, CostTiming, FeeSettlement};
+pub use position::{Lot, Position};
+pub use trade_log::{round_trips, write_round_trips_csv, RoundTrip, TradeLog};
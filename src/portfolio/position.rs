@@ -1,6 +1,14 @@
-use crate::instrument::FuturesContract;
+use crate::instrument::{ContractKind, FuturesContract};
 use serde::{Deserialize, Serialize};
 
+//an independently held lot in a hedging account
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Lot {
+    //signed quantity (positive long, negative short)
+    pub qty: i32,
+    pub entry_price: f64,
+}
+
 //represents a position in a futures contract
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Position {
@@ -15,6 +23,16 @@ pub struct Position {
 
     //realized pnl from closed trades
     pub realized_pnl: f64,
+
+    //open lots, oldest first (hedging accounts only; empty when netting)
+    //net_qty is their sum and avg_entry_price the average of lots on the net side
+    #[serde(default)]
+    pub lots: Vec<Lot>,
+
+    //first protective stop set for the open position, the basis of its initial risk
+    //cleared when the position goes flat or reverses
+    #[serde(default)]
+    pub initial_stop: Option<f64>,
 }
 
 impl Position {
@@ -25,22 +43,62 @@ impl Position {
             net_qty: 0,
             avg_entry_price: 0.0,
             realized_pnl: 0.0,
+            lots: Vec::new(),
+            initial_stop: None,
         }
     }
 
     //calculates unrealized pnl at a given price
     pub fn unrealized_pnl(&self, current_price: f64, contract: &FuturesContract) -> f64 {
+        if !self.lots.is_empty() {
+            return self
+                .lots
+                .iter()
+                .map(|lot| contract.pnl_between(lot.entry_price, current_price, lot.qty))
+                .sum();
+        }
+
+        if self.net_qty == 0 {
+            return 0.0;
+        }
+
+        contract.pnl_between(self.avg_entry_price, current_price, self.net_qty)
+    }
+
+    //returns the open profit per contract in ticks at a given price
+    //positive when the price has moved in the position's favor, 0 when flat
+    pub fn pnl_ticks(&self, current_price: f64, contract: &FuturesContract) -> f64 {
         if self.net_qty == 0 {
             return 0.0;
         }
 
         let price_diff = current_price - self.avg_entry_price;
-        contract.pnl_from_price_move(price_diff, self.net_qty)
+        contract.price_to_ticks(price_diff) * self.net_qty.signum() as f64
     }
 
     //returns true if the position is flat (no open position)
+    //offsetting lots in a hedging account net to zero but are still open
     pub fn is_flat(&self) -> bool {
-        self.net_qty == 0
+        self.net_qty == 0 && self.lots.is_empty()
+    }
+
+    //returns the total long contracts held (the long lots when hedging)
+    pub fn long_qty(&self) -> u32 {
+        if self.lots.is_empty() {
+            return self.net_qty.max(0) as u32;
+        }
+        self.lots.iter().map(|lot| lot.qty.max(0) as u32).sum()
+    }
+
+    //returns the total short contracts held (the short lots when hedging)
+    pub fn short_qty(&self) -> u32 {
+        if self.lots.is_empty() {
+            return self.net_qty.min(0).unsigned_abs();
+        }
+        self.lots
+            .iter()
+            .map(|lot| lot.qty.min(0).unsigned_abs())
+            .sum()
     }
 
     //returns true if the position is long
@@ -63,6 +121,11 @@ impl Position {
     ) -> f64 {
         let mut realized_pnl = 0.0;
 
+        //a zero-size fill changes nothing (and mustn't give a flat position an entry price)
+        if fill_qty == 0 {
+            return realized_pnl;
+        }
+
         //if position is flat, just establish new position
         if self.net_qty == 0 {
             self.net_qty = fill_qty;
@@ -76,46 +139,233 @@ impl Position {
 
         if same_direction {
             //adding to position - update average entry price
-            let total_qty = self.net_qty + fill_qty;
-            let total_cost =
-                self.avg_entry_price * self.net_qty as f64 + fill_price * fill_qty as f64;
-            self.avg_entry_price = total_cost / total_qty as f64;
-            self.net_qty = total_qty;
+            //weights by contract counts so longs and shorts average the same way
+            //inverse contracts average in 1/price (harmonic mean) so pnl stays exact
+            //both quantities are non-zero here, so the combined size can't be
+            let held = self.net_qty.unsigned_abs() as f64;
+            let added = fill_qty.unsigned_abs() as f64;
+            self.avg_entry_price = match contract.kind {
+                ContractKind::Linear => {
+                    (self.avg_entry_price * held + fill_price * added) / (held + added)
+                }
+                ContractKind::Inverse => {
+                    (held + added) / (held / self.avg_entry_price + added / fill_price)
+                }
+            };
+            self.net_qty += fill_qty;
         } else {
             //reducing or reversing position
             let close_qty = fill_qty.abs().min(self.net_qty.abs());
 
-            //calculate realized pnl for the closed portion
-            let price_diff = if self.net_qty > 0 {
-                //closing long
-                fill_price - self.avg_entry_price
-            } else {
-                //closing short
-                self.avg_entry_price - fill_price
-            };
-
-            realized_pnl = contract.pnl_from_price_move(price_diff, close_qty);
+            //calculate realized pnl for the closed portion (signed like the position)
+            realized_pnl = contract.pnl_between(
+                self.avg_entry_price,
+                fill_price,
+                close_qty * self.net_qty.signum(),
+            );
             self.realized_pnl += realized_pnl;
 
             //update net quantity
             self.net_qty += fill_qty;
 
-            //if we've reversed the position, set new entry price
-            if (self.net_qty > 0 && fill_qty > 0) || (self.net_qty < 0 && fill_qty < 0) {
+            //exactly flat resets the entry price; a reversal opens the remainder at the fill
+            //price; a partial reduction keeps the entry price of what's left
+            if self.net_qty == 0 {
+                self.avg_entry_price = 0.0;
+                self.initial_stop = None;
+            } else if self.net_qty.signum() == fill_qty.signum() {
                 self.avg_entry_price = fill_price;
+                self.initial_stop = None;
             }
+        }
 
-            //if flat, reset entry price
-            if self.net_qty == 0 {
-                self.avg_entry_price = 0.0;
+        debug_assert!(
+            self.net_qty != 0 || self.avg_entry_price == 0.0,
+            "flat position kept an entry price"
+        );
+        realized_pnl
+    }
+
+    //updates the lots of a hedging account with a new fill
+    //a closing fill closes lots on the other side oldest first, realizing pnl per lot, and any
+    //remainder opens a new lot; a non-closing fill always opens a new lot, so opposing
+    //orders don't net
+    //returns the realized pnl from this fill
+    pub fn update_lots_with_fill(
+        &mut self,
+        fill_qty: i32,
+        fill_price: f64,
+        closing: bool,
+        contract: &FuturesContract,
+    ) -> f64 {
+        let mut realized_pnl = 0.0;
+        let mut remaining = fill_qty;
+
+        if closing {
+            for lot in self.lots.iter_mut() {
+                if remaining == 0 {
+                    break;
+                }
+                if lot.qty.signum() == remaining.signum() {
+                    continue;
+                }
+
+                let close_qty = remaining.abs().min(lot.qty.abs());
+                realized_pnl +=
+                    contract.pnl_between(lot.entry_price, fill_price, close_qty * lot.qty.signum());
+                lot.qty += remaining.signum() * close_qty;
+                remaining -= remaining.signum() * close_qty;
             }
+            self.lots.retain(|lot| lot.qty != 0);
+        }
+
+        if remaining != 0 {
+            self.lots.push(Lot {
+                qty: remaining,
+                entry_price: fill_price,
+            });
+        }
+
+        self.realized_pnl += realized_pnl;
+        self.net_qty = self.lots.iter().map(|lot| lot.qty).sum();
+        self.avg_entry_price = self.net_side_entry_price(contract);
+        if self.is_flat() {
+            self.initial_stop = None;
         }
 
         realized_pnl
     }
 
+    //average entry price of the lots on the net side (0 when the lots net to flat)
+    fn net_side_entry_price(&self, contract: &FuturesContract) -> f64 {
+        let side = self.net_qty.signum();
+        if side == 0 {
+            return 0.0;
+        }
+
+        let lots = self.lots.iter().filter(|lot| lot.qty.signum() == side);
+        let qty: f64 = lots.clone().map(|lot| lot.qty.unsigned_abs() as f64).sum();
+        match contract.kind {
+            ContractKind::Linear => {
+                lots.map(|lot| lot.entry_price * lot.qty.unsigned_abs() as f64)
+                    .sum::<f64>()
+                    / qty
+            }
+            ContractKind::Inverse => {
+                qty / lots
+                    .map(|lot| lot.qty.unsigned_abs() as f64 / lot.entry_price)
+                    .sum::<f64>()
+            }
+        }
+    }
+
     //returns the notional value of the position
     pub fn notional_value(&self, current_price: f64, contract: &FuturesContract) -> f64 {
         contract.notional_value(current_price, self.net_qty)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn short_add_at_a_worse_price_lowers_the_average() {
+        let contract = FuturesContract::es("H24");
+        let mut position = Position::new("ES".to_string());
+        position.update_with_fill(-2, 4000.0, &contract);
+        position.update_with_fill(-1, 3990.0, &contract);
+
+        assert_eq!(position.net_qty, -3);
+        assert_close(position.avg_entry_price, (2.0 * 4000.0 + 3990.0) / 3.0);
+
+        //covering at the average realizes nothing
+        let realized = position.update_with_fill(3, position.avg_entry_price, &contract);
+        assert_close(realized, 0.0);
+        assert!(position.is_flat());
+    }
+
+    #[test]
+    fn long_adds_across_several_fills_average_by_contracts() {
+        let contract = FuturesContract::es("H24");
+        let mut position = Position::new("ES".to_string());
+        for (qty, price) in [(1, 4000.0), (2, 4010.0), (1, 4030.0)] {
+            position.update_with_fill(qty, price, &contract);
+        }
+
+        assert_eq!(position.net_qty, 4);
+        assert_close(position.avg_entry_price, 4012.5);
+
+        //selling all four at 4020 makes 7.5 points on each, at 50 dollars a point
+        let realized = position.update_with_fill(-4, 4020.0, &contract);
+        assert_close(realized, 4.0 * 7.5 * 50.0);
+    }
+
+    #[test]
+    fn exact_flatten_resets_the_entry_price_on_both_sides() {
+        let contract = FuturesContract::es("H24");
+        for side in [1, -1] {
+            let mut position = Position::new("ES".to_string());
+            position.update_with_fill(3 * side, 4000.0, &contract);
+            position.initial_stop = Some(4000.0 - 10.0 * side as f64);
+
+            let realized = position.update_with_fill(-3 * side, 4010.0, &contract);
+            assert_close(realized, 3.0 * 10.0 * 50.0 * side as f64);
+            assert!(position.is_flat());
+            assert_eq!(position.avg_entry_price, 0.0);
+            assert_eq!(position.initial_stop, None);
+        }
+    }
+
+    #[test]
+    fn zero_size_fill_leaves_the_position_untouched() {
+        let contract = FuturesContract::es("H24");
+        let mut position = Position::new("ES".to_string());
+        assert_eq!(position.update_with_fill(0, 4000.0, &contract), 0.0);
+        assert!(position.is_flat());
+        assert_eq!(position.avg_entry_price, 0.0);
+
+        position.update_with_fill(2, 4000.0, &contract);
+        assert_eq!(position.update_with_fill(0, 4100.0, &contract), 0.0);
+        assert_eq!(position.net_qty, 2);
+        assert_eq!(position.avg_entry_price, 4000.0);
+    }
+
+    #[test]
+    fn reversal_opens_the_remainder_at_the_fill_price() {
+        let contract = FuturesContract::es("H24");
+        let mut position = Position::new("ES".to_string());
+        position.update_with_fill(3, 4000.0, &contract);
+        let realized = position.update_with_fill(-5, 3990.0, &contract);
+
+        assert_close(realized, -3.0 * 10.0 * 50.0);
+        assert_eq!(position.net_qty, -2);
+        assert_eq!(position.avg_entry_price, 3990.0);
+    }
+
+    #[test]
+    fn hedged_lots_netting_to_flat_have_no_entry_price() {
+        let contract = FuturesContract::es("H24");
+        let mut position = Position::new("ES".to_string());
+        position.update_lots_with_fill(2, 4000.0, false, &contract);
+        position.update_lots_with_fill(-2, 4010.0, false, &contract);
+
+        assert_eq!(position.net_qty, 0);
+        assert_eq!(position.avg_entry_price, 0.0);
+        assert!(!position.is_flat());
+
+        position.update_lots_with_fill(2, 4020.0, true, &contract);
+        position.update_lots_with_fill(-2, 4020.0, true, &contract);
+        assert!(position.is_flat());
+        assert_eq!(position.avg_entry_price, 0.0);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::engine::execution::{ExitReason, Fill, OrderSide};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::ops::Deref;
+use std::path::Path;
+
+//a completed trade: from flat, through any adds and partial exits, back to flat (or reversed)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoundTrip {
+    pub symbol: String,
+    //side of the entry (buy for a long trade, sell for a short trade)
+    pub side: OrderSide,
+    //total contracts entered
+    pub qty: u32,
+    pub entry_time: DateTime<Utc>,
+    pub exit_time: DateTime<Utc>,
+    //quantity-weighted average entry and exit prices
+    pub entry_price: f64,
+    pub exit_price: f64,
+    //realized pnl before fees
+    pub pnl: f64,
+    //fees on the entry and exit fills (prorated for reversing fills)
+    pub fees: f64,
+    //reason tagged on the fill that closed the trade, if any
+    #[serde(default)]
+    pub exit_reason: Option<ExitReason>,
+    //loss at the initial stop across the contracts entered (none if no stop was set)
+    #[serde(default)]
+    pub initial_risk: Option<f64>,
+}
+
+impl RoundTrip {
+    //realized pnl after fees
+    pub fn net_pnl(&self) -> f64 {
+        self.pnl - self.fees
+    }
+
+    //net pnl in units of initial risk (none without a stop)
+    pub fn r_multiple(&self) -> Option<f64> {
+        self.initial_risk
+            .filter(|&risk| risk > 0.0)
+            .map(|risk| self.net_pnl() / risk)
+    }
+
+    pub fn is_long(&self) -> bool {
+        self.side == OrderSide::Buy
+    }
+}
+
+//fills of a trade that hasn't returned to flat yet
+struct OpenTrip {
+    side: OrderSide,
+    net_qty: i32,
+    entry_qty: u32,
+    entry_value: f64,
+    exit_qty: u32,
+    exit_value: f64,
+    entry_time: DateTime<Utc>,
+    pnl: f64,
+    fees: f64,
+    risk_per_contract: Option<f64>,
+}
+
+impl OpenTrip {
+    fn open(fill: &Fill, qty: i32, fees: f64) -> Self {
+        OpenTrip {
+            side: fill.side,
+            net_qty: qty,
+            entry_qty: qty.unsigned_abs(),
+            entry_value: fill.fill_price * qty.unsigned_abs() as f64,
+            exit_qty: 0,
+            exit_value: 0.0,
+            entry_time: fill.timestamp,
+            pnl: 0.0,
+            fees,
+            risk_per_contract: None,
+        }
+    }
+
+    fn close(self, fill: &Fill) -> RoundTrip {
+        RoundTrip {
+            symbol: fill.symbol.clone(),
+            side: self.side,
+            qty: self.entry_qty,
+            entry_time: self.entry_time,
+            exit_time: fill.timestamp,
+            entry_price: self.entry_value / self.entry_qty as f64,
+            exit_price: self.exit_value / self.exit_qty.max(1) as f64,
+            pnl: self.pnl,
+            fees: self.fees,
+            exit_reason: fill.exit_reason,
+            initial_risk: self
+                .risk_per_contract
+                .map(|risk| risk * self.entry_qty as f64),
+        }
+    }
+}
+
+//groups fills into round trips, tracking each symbol's position separately
+//pnl comes from the realized pnl the account booked on each closing fill
+//trades still open at the end of the log are not included
+pub fn round_trips(fills: &[Fill]) -> Vec<RoundTrip> {
+    let mut open: Vec<(String, OpenTrip)> = Vec::new();
+    let mut trips = Vec::new();
+
+    for fill in fills.iter().filter(|f| f.qty != 0) {
+        let slot = open.iter().position(|(symbol, _)| *symbol == fill.symbol);
+
+        let slot = match slot {
+            Some(slot) => slot,
+            None => {
+                open.push((
+                    fill.symbol.clone(),
+                    OpenTrip::open(fill, fill.qty, fill.fees()),
+                ));
+                continue;
+            }
+        };
+        let trip = &mut open[slot].1;
+
+        //adding in the same direction
+        if trip.net_qty.signum() == fill.qty.signum() {
+            trip.net_qty += fill.qty;
+            trip.entry_qty += fill.qty.unsigned_abs();
+            trip.entry_value += fill.fill_price * fill.qty.unsigned_abs() as f64;
+            trip.fees += fill.fees();
+            continue;
+        }
+
+        //reducing, closing or reversing
+        let close_qty = fill.qty.abs().min(trip.net_qty.abs());
+        let close_share = close_qty as f64 / fill.qty.abs() as f64;
+
+        trip.net_qty += fill.qty.signum() * close_qty;
+        trip.exit_qty += close_qty as u32;
+        trip.exit_value += fill.fill_price * close_qty as f64;
+        trip.pnl += fill.realized_pnl;
+        trip.fees += fill.fees() * close_share;
+        trip.risk_per_contract = trip.risk_per_contract.or(fill.risk_per_contract);
+
+        if trip.net_qty == 0 {
+            let (_, trip) = open.swap_remove(slot);
+            trips.push(trip.close(fill));
+
+            //any remainder opens a trade the other way
+            let remainder = fill.qty - fill.qty.signum() * close_qty;
+            if remainder != 0 {
+                open.push((
+                    fill.symbol.clone(),
+                    OpenTrip::open(fill, remainder, fill.fees() * (1.0 - close_share)),
+                ));
+            }
+        }
+    }
+
+    trips
+}
+
+//writes one row per round trip, the layout trade journals import (unlike the per-fill csv)
+pub fn write_round_trips_csv(trips: &[RoundTrip], path: &Path) -> Result<()> {
+    let mut file = std::fs::File::create(path)?;
+    writeln!(
+        file,
+        "entry_time,exit_time,symbol,side,qty,entry_price,exit_price,gross_pnl,fees,net_pnl,exit_reason,r_multiple"
+    )?;
+
+    for trip in trips {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{},{},{},{}",
+            trip.entry_time.to_rfc3339(),
+            trip.exit_time.to_rfc3339(),
+            trip.symbol,
+            if trip.is_long() { "Long" } else { "Short" },
+            trip.qty,
+            trip.entry_price,
+            trip.exit_price,
+            trip.pnl,
+            trip.fees,
+            trip.net_pnl(),
+            trip.exit_reason
+                .map(|reason| format!("{:?}", reason))
+                .unwrap_or_default(),
+            trip.r_multiple().map(|r| r.to_string()).unwrap_or_default()
+        )?;
+    }
+
+    Ok(())
+}
+
+//the fills an account has made, with query helpers for analysis
+//derefs to a slice of fills, so it can be passed wherever &[Fill] is expected
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TradeLog {
+    fills: Vec<Fill>,
+}
+
+impl TradeLog {
+    pub fn new() -> Self {
+        TradeLog { fills: Vec::new() }
+    }
+
+    //appends a fill
+    pub fn push(&mut self, fill: Fill) {
+        self.fills.push(fill);
+    }
+
+    //returns the fills as a slice
+    pub fn fills(&self) -> &[Fill] {
+        &self.fills
+    }
+
+    //returns the fills for one symbol
+    pub fn by_symbol(&self, symbol: &str) -> TradeLog {
+        self.fills
+            .iter()
+            .filter(|fill| fill.symbol == symbol)
+            .cloned()
+            .collect()
+    }
+
+    //groups the fills into completed round trips
+    pub fn round_trips(&self) -> Vec<RoundTrip> {
+        round_trips(&self.fills)
+    }
+
+    //total fees paid across all fills
+    pub fn total_fees(&self) -> f64 {
+        self.fills.iter().map(|fill| fill.fees()).sum()
+    }
+
+    //commission part of the fees
+    pub fn total_commission(&self) -> f64 {
+        self.fills.iter().map(|fill| fill.commission).sum()
+    }
+
+    //slippage part of the fees
+    pub fn total_slippage(&self) -> f64 {
+        self.fills.iter().map(|fill| fill.slippage).sum()
+    }
+
+    //realized pnl before fees
+    pub fn gross_pnl(&self) -> f64 {
+        self.fills.iter().map(|fill| fill.realized_pnl).sum()
+    }
+
+    //realized pnl after fees
+    pub fn net_pnl(&self) -> f64 {
+        self.gross_pnl() - self.total_fees()
+    }
+
+    //consumes the log and returns the fills
+    pub fn into_fills(self) -> Vec<Fill> {
+        self.fills
+    }
+}
+
+impl Deref for TradeLog {
+    type Target = [Fill];
+
+    fn deref(&self) -> &[Fill] {
+        &self.fills
+    }
+}
+
+impl From<Vec<Fill>> for TradeLog {
+    fn from(fills: Vec<Fill>) -> Self {
+        TradeLog { fills }
+    }
+}
+
+impl FromIterator<Fill> for TradeLog {
+    fn from_iter<I: IntoIterator<Item = Fill>>(iter: I) -> Self {
+        TradeLog {
+            fills: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a TradeLog {
+    type Item = &'a Fill;
+    type IntoIter = std::slice::Iter<'a, Fill>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.fills.iter()
+    }
+}
@@ -0,0 +1,228 @@
+use crate::data::Bar;
+use crate::engine::execution::{ExitReason, OrderSide};
+use crate::strategy::{atr, ema, sizing::PositionSizing, Strategy, StrategyContext};
+use serde::{Deserialize, Serialize};
+
+//how the keltner strategy reacts to the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum KeltnerMode {
+    //go long on a close above the upper band, short below the lower band
+    #[default]
+    Breakout,
+    //fade closes outside the bands and exit at the midline
+    Reversion,
+}
+
+impl KeltnerMode {
+    //parse keltner mode from string
+    pub fn parse(s: &str) -> Option<Self> {
+        match s.to_lowercase().as_str() {
+            "breakout" => Some(KeltnerMode::Breakout),
+            "reversion" | "mean_reversion" => Some(KeltnerMode::Reversion),
+            _ => None,
+        }
+    }
+}
+
+//keltner channel bands: ema midline with atr-based upper and lower bands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeltnerBands {
+    pub lower: f64,
+    pub middle: f64,
+    pub upper: f64,
+}
+
+//computes keltner bands from the most recent bars
+//returns none if there aren't enough bars for both the ema and the atr
+pub fn keltner_bands(
+    bars: &[&Bar],
+    ema_period: usize,
+    atr_period: usize,
+    multiplier: f64,
+) -> Option<KeltnerBands> {
+    let closes: Vec<f64> = bars.iter().map(|bar| bar.close).collect();
+    let middle = ema(&closes, ema_period)?;
+    let range = atr(bars, atr_period)?;
+
+    Some(KeltnerBands {
+        lower: middle - multiplier * range,
+        middle,
+        upper: middle + multiplier * range,
+    })
+}
+
+//keltner channel strategy
+//trades breakouts of, or reversions into, an ema channel sized by atr
+#[derive(Debug, Clone)]
+pub struct KeltnerStrategy {
+    symbol: String,
+    ema_period: usize,
+    atr_period: usize,
+    multiplier: f64,
+    mode: KeltnerMode,
+    sizing: PositionSizing,
+}
+
+impl KeltnerStrategy {
+    pub fn new(
+        symbol: String,
+        ema_period: usize,
+        atr_period: usize,
+        multiplier: f64,
+        mode: KeltnerMode,
+        qty: u32,
+    ) -> Self {
+        KeltnerStrategy {
+            symbol,
+            ema_period,
+            atr_period,
+            multiplier,
+            mode,
+            sizing: PositionSizing::Fixed(qty),
+        }
+    }
+
+    //sizes entries with the given mode instead of the fixed quantity
+    pub fn with_sizing(mut self, sizing: PositionSizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+
+    //moves the position to target_qty contracts (signed)
+    //an order that reduces the current position is tagged with the exit reason
+    fn move_to(&self, context: &mut StrategyContext, target_qty: i32, reason: ExitReason) {
+        let current_quantity = context.current_position().map(|p| p.net_qty).unwrap_or(0);
+        let delta = target_qty - current_quantity;
+
+        let order = if delta > 0 {
+            context.market_order(self.symbol.clone(), delta as u32, OrderSide::Buy)
+        } else if delta < 0 {
+            context.market_order(self.symbol.clone(), delta.unsigned_abs(), OrderSide::Sell)
+        } else {
+            return;
+        };
+
+        if let (Ok(id), true) = (order, current_quantity * delta < 0) {
+            context.tag_order(id, reason);
+        }
+    }
+}
+
+impl Strategy for KeltnerStrategy {
+    fn on_start(&mut self, _context: &mut StrategyContext) {
+        //no initialization needed
+    }
+
+    fn on_bar(&mut self, context: &mut StrategyContext, bar: &Bar) {
+        let required = self.required_lookback();
+        if context.bar_count() < required {
+            return;
+        }
+
+        let bands = match keltner_bands(
+            &context.get_bars(required),
+            self.ema_period,
+            self.atr_period,
+            self.multiplier,
+        ) {
+            Some(bands) => bands,
+            None => return,
+        };
+
+        let qty = self.sizing.entry_qty(context) as i32;
+        let current_quantity = context.current_position().map(|p| p.net_qty).unwrap_or(0);
+
+        match self.mode {
+            KeltnerMode::Breakout => {
+                if bar.close > bands.upper {
+                    self.move_to(context, qty, ExitReason::SignalFlip);
+                } else if bar.close < bands.lower {
+                    self.move_to(context, -qty, ExitReason::SignalFlip);
+                }
+            }
+            KeltnerMode::Reversion => {
+                if bar.close < bands.lower {
+                    self.move_to(context, qty, ExitReason::SignalFlip);
+                } else if bar.close > bands.upper {
+                    self.move_to(context, -qty, ExitReason::SignalFlip);
+                } else if (current_quantity > 0 && bar.close >= bands.middle)
+                    || (current_quantity < 0 && bar.close <= bands.middle)
+                {
+                    //reverted to the midline - take profit
+                    self.move_to(context, 0, ExitReason::TakeProfit);
+                }
+            }
+        }
+    }
+
+    fn on_end(&mut self, context: &mut StrategyContext) {
+        //close any open positions
+        for id in context.flatten(&self.symbol).unwrap_or_default() {
+            context.tag_order(id, ExitReason::EndOfRun);
+        }
+    }
+
+    fn name(&self) -> &str {
+        "Keltner Channel"
+    }
+
+    fn required_lookback(&self) -> usize {
+        //atr needs one extra bar for the first true range
+        self.ema_period.max(self.atr_period + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    //daily bars closing at the given prices, each spanning 2 points either side
+    fn bars(closes: &[f64]) -> Vec<Bar> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        closes
+            .iter()
+            .enumerate()
+            .map(|(i, &close)| {
+                Bar::new(
+                    start + chrono::Duration::days(i as i64),
+                    close,
+                    close + 2.0,
+                    close - 2.0,
+                    close,
+                    1000.0,
+                    None,
+                    "ES".to_string(),
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bands_sit_multiplier_atrs_around_the_ema() {
+        let bars = bars(&[4000.0, 4002.0, 4004.0, 4006.0]);
+        let refs: Vec<&Bar> = bars.iter().collect();
+
+        //ema(3) seeds at 4002 and steps halfway to 4006; every true range is 4
+        let bands = keltner_bands(&refs, 3, 3, 2.0).unwrap();
+        assert_eq!(
+            bands,
+            KeltnerBands {
+                lower: 3996.0,
+                middle: 4004.0,
+                upper: 4012.0,
+            }
+        );
+    }
+
+    #[test]
+    fn bands_need_history_for_both_indicators() {
+        let bars = bars(&[4000.0, 4002.0, 4004.0]);
+        let refs: Vec<&Bar> = bars.iter().collect();
+
+        //enough closes for the ema, one bar short for the atr
+        assert!(keltner_bands(&refs, 3, 3, 2.0).is_none());
+        assert!(keltner_bands(&refs, 3, 2, 2.0).is_some());
+    }
+}
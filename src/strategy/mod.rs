@@ -1,14 +1,42 @@
+pub mod keltner;
+pub mod protective;
+pub mod registry;
 pub mod rsi_reversion;
+pub mod sizing;
 pub mod sma_crossover;
 
 use crate::data::Bar;
-use crate::engine::execution::{ExecutionEngine, OrderSide};
+use crate::engine::execution::{
+    ExecutionEngine, ExitReason, OrderRejection, OrderSide, OrderType, PlacementCheck,
+};
+use crate::instrument::FuturesContract;
 use crate::portfolio::{Account, Position};
+use crate::strategy::sizing::VolTarget;
 use chrono::{DateTime, Utc};
-use std::collections::VecDeque;
+use log::{debug, warn};
+use std::any::Any;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+//gives every strategy as_any, so a Box<dyn Strategy> can be downcast after a run to read
+//state the concrete strategy accumulated
+//implemented for all 'static types; strategies never implement it themselves
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
 
 //strategy interface that all strategies must implement
-pub trait Strategy: Send {
+pub trait Strategy: Send + AsAny {
     //called once at the start of the backtest
     fn on_start(&mut self, context: &mut StrategyContext);
 
@@ -20,6 +48,24 @@ pub trait Strategy: Send {
 
     //returns the strategy name
     fn name(&self) -> &str;
+
+    //returns the number of bars of history the strategy needs
+    //the engine keeps at least this many bars, on top of its configured max_lookback
+    fn required_lookback(&self) -> usize {
+        0
+    }
+}
+
+impl dyn Strategy {
+    //returns the strategy as its concrete type, if it is a T
+    //call this rather than as_any on a Box<dyn Strategy>, which would see the box itself
+    pub fn downcast_ref<T: Strategy + 'static>(&self) -> Option<&T> {
+        self.as_any().downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Strategy + 'static>(&mut self) -> Option<&mut T> {
+        self.as_any_mut().downcast_mut()
+    }
 }
 
 //context providing access to market data and order submission
@@ -27,6 +73,9 @@ pub struct StrategyContext {
     //symbol being traded
     pub symbol: String,
 
+    //contract specification for the traded symbol
+    contract: FuturesContract,
+
     //historical bars (ring buffer with limited lookback)
     bar_history: VecDeque<Bar>,
 
@@ -41,29 +90,157 @@ pub struct StrategyContext {
 
     //reference to account
     account: *mut Account,
+
+    //maximum add-on entries in the direction of an open position (none = unlimited)
+    max_pyramid_entries: Option<usize>,
+
+    //orders whose fills added to each symbol's open position, since it was opened
+    //(order ids, so the partial fills of one add-on count once)
+    pyramid_entries: HashMap<String, HashSet<u64>>,
+
+    //true once the engine has halted trading (drawdown stop or equity floor)
+    halted: bool,
+
+    //number of bars pushed so far
+    bars_seen: usize,
+
+    //entries are rejected for this many bars, counting the bar of the last fill (none = off)
+    min_bars_between_trades: Option<usize>,
+
+    //bars_seen when the last fill was booked
+    last_fill_bar: Option<usize>,
+
+    //how limit/stop prices on the wrong side of the last close are handled
+    placement_check: PlacementCheck,
+
+    //bars every order is held back after submission (signal-to-order latency)
+    signal_delay_bars: u32,
+
+    //caps the position in the traded symbol to the volatility target's size (none = no cap)
+    vol_target: Option<VolTarget>,
+
+    //symbols orders may be sent for: the traded symbol, plus any added by the caller
+    tradable_symbols: HashSet<String>,
 }
 
 impl StrategyContext {
     //creates a new strategy context
     pub fn new(
-        symbol: String,
+        contract: FuturesContract,
         max_history: usize,
         execution_engine: *mut ExecutionEngine,
         account: *mut Account,
     ) -> Self {
         StrategyContext {
-            symbol,
+            symbol: contract.symbol.clone(),
+            tradable_symbols: HashSet::from([contract.symbol.clone()]),
+            contract,
             bar_history: VecDeque::with_capacity(max_history),
             max_history,
             current_time: Utc::now(),
             execution_engine,
             account,
+            max_pyramid_entries: None,
+            vol_target: None,
+            pyramid_entries: HashMap::new(),
+            halted: false,
+            bars_seen: 0,
+            min_bars_between_trades: None,
+            last_fill_bar: None,
+            placement_check: PlacementCheck::default(),
+            signal_delay_bars: 0,
+        }
+    }
+
+    //allows orders for another symbol, for callers that book its fills with its own contract
+    //(the backtest engine books only the traded symbol, so it never adds any)
+    pub fn add_tradable_symbol(&mut self, symbol: &str) {
+        self.tradable_symbols.insert(symbol.to_string());
+    }
+
+    //sets the volatility target that caps (or sizes) positions from the atr
+    pub fn set_vol_target(&mut self, vol_target: Option<VolTarget>) {
+        self.vol_target = vol_target;
+    }
+
+    //returns the contracts whose one-atr move is worth the target's risk, from the bar history
+    //none until there are atr_period + 1 bars, or if the atr is zero
+    pub fn vol_target_qty(&self, target: VolTarget) -> Option<u32> {
+        let price = self.last_bar()?.close;
+        let atr = atr(&self.get_bars(target.atr_period + 1), target.atr_period)?;
+        let risk_per_contract = self.contract.pnl_between(price, price + atr, 1).abs();
+        if risk_per_contract <= 0.0 {
+            return None;
+        }
+        Some((target.risk / risk_per_contract).floor().max(0.0) as u32)
+    }
+
+    //returns this bar's cap on the traded symbol's position under the volatility target
+    pub fn position_cap(&self) -> Option<u32> {
+        self.vol_target
+            .and_then(|target| self.vol_target_qty(target))
+    }
+
+    //sets the maximum number of same-direction add-on entries
+    pub fn set_max_pyramid_entries(&mut self, max_pyramid_entries: Option<usize>) {
+        self.max_pyramid_entries = max_pyramid_entries;
+    }
+
+    //sets the entry cooldown after a fill, in bars
+    pub fn set_min_bars_between_trades(&mut self, min_bars_between_trades: Option<usize>) {
+        self.min_bars_between_trades = min_bars_between_trades;
+    }
+
+    //sets how inverted limit/stop prices are handled at submission
+    pub fn set_placement_check(&mut self, placement_check: PlacementCheck) {
+        self.placement_check = placement_check;
+    }
+
+    //sets how many bars orders wait after submission before they can fill
+    pub fn set_signal_delay_bars(&mut self, signal_delay_bars: u32) {
+        self.signal_delay_bars = signal_delay_bars;
+    }
+
+    //holds a just-submitted order back by the signal delay
+    fn apply_signal_delay(&mut self, order_id: u64) -> u64 {
+        if self.signal_delay_bars > 0 {
+            unsafe { (*self.execution_engine).delay_order(order_id, self.signal_delay_bars) };
+        }
+        order_id
+    }
+
+    //records that fills were booked on the current bar, starting the entry cooldown
+    pub fn record_fill(&mut self) {
+        self.last_fill_bar = Some(self.bars_seen);
+    }
+
+    //counts a booked fill towards the pyramiding limit, given the net position before it
+    //a fill that grows an open position is an add-on; flat or reversed starts a new count
+    pub fn record_pyramid_fill(&mut self, symbol: &str, order_id: u64, net_before: i32) {
+        let net_after = self.net_qty(symbol);
+        let entries = self.pyramid_entries.entry(symbol.to_string()).or_default();
+
+        if net_after == 0 || net_after.signum() != net_before.signum() {
+            entries.clear();
+        } else if net_after.abs() > net_before.abs() {
+            entries.insert(order_id);
         }
     }
 
+    //halts trading, so every further order is rejected
+    pub fn halt(&mut self) {
+        self.halted = true;
+    }
+
+    //returns true if trading has been halted
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
     //adds a bar to the history
     pub fn push_bar(&mut self, bar: Bar) {
         self.current_time = bar.timestamp;
+        self.bars_seen += 1;
 
         if self.bar_history.len() >= self.max_history {
             self.bar_history.pop_front();
@@ -94,27 +271,367 @@ impl StrategyContext {
         bars.iter().map(|b| b.close).collect()
     }
 
-    //submits a market order
-    pub fn market_order(&mut self, symbol: String, qty: u32, side: OrderSide) -> u64 {
-        unsafe { (*self.execution_engine).market_order(self.current_time, symbol, qty, side) }
+    //submits a market order and returns its id, or the reason it was rejected
+    pub fn market_order(
+        &mut self,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+    ) -> Result<u64, OrderRejection> {
+        let qty = self.check_order(&symbol, qty, side)?;
+        let id =
+            unsafe { (*self.execution_engine).market_order(self.current_time, symbol, qty, side) };
+        Ok(self.apply_signal_delay(id))
     }
 
-    //submits a limit order
+    //submits a limit order and returns its id, or the reason it was rejected
     pub fn limit_order(
         &mut self,
         symbol: String,
         qty: u32,
         side: OrderSide,
         limit_price: f64,
-    ) -> u64 {
-        unsafe {
+    ) -> Result<u64, OrderRejection> {
+        self.check_placement(OrderType::Limit, side, limit_price)?;
+        let qty = self.check_order(&symbol, qty, side)?;
+        let id = unsafe {
             (*self.execution_engine).limit_order(self.current_time, symbol, qty, side, limit_price)
+        };
+        Ok(self.apply_signal_delay(id))
+    }
+
+    //submits a passive limit order offset_ticks better than the last close (below it for buys,
+    //above it for sells), snapped to the tick grid; it fills only if price comes back to it
+    //prices come from the traded contract, so the symbol should be the context's own
+    pub fn limit_order_offset(
+        &mut self,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+        offset_ticks: u32,
+    ) -> Result<u64, OrderRejection> {
+        let close = match self.last_bar() {
+            Some(bar) => bar.close,
+            None => return Err(OrderRejection::NoReferencePrice),
+        };
+        let offset = offset_ticks as f64 * self.contract.tick_size;
+        let limit_price = match side {
+            OrderSide::Buy => self.contract.round_to_tick(close - offset),
+            OrderSide::Sell => self.contract.round_to_tick(close + offset),
+        };
+        self.limit_order(symbol, qty, side, limit_price)
+    }
+
+    //submits a market order that can only reduce the position: the filled quantity is clamped
+    //to the opposite position held when it fills, so it never flips or adds to it
+    pub fn reduce_only_order(
+        &mut self,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+    ) -> Result<u64, OrderRejection> {
+        let id = self.market_order(symbol, qty, side)?;
+        unsafe { (*self.execution_engine).set_reduce_only(id) };
+        Ok(id)
+    }
+
+    //submits the reduce-only market orders closing the position in symbol: a sell for the
+    //long contracts and a buy for the short ones (both legs when hedging)
+    //returns the order ids, empty if the position is already flat
+    pub fn flatten(&mut self, symbol: &str) -> Result<Vec<u64>, OrderRejection> {
+        let (long_qty, short_qty) = match self.position(symbol) {
+            Some(position) => (position.long_qty(), position.short_qty()),
+            None => return Ok(Vec::new()),
+        };
+
+        let mut ids = Vec::new();
+        for (qty, side) in [(long_qty, OrderSide::Sell), (short_qty, OrderSide::Buy)] {
+            if qty > 0 {
+                ids.push(self.reduce_only_order(symbol.to_string(), qty, side)?);
+            }
+        }
+        Ok(ids)
+    }
+
+    //flattens every open position in the account, in symbol order
+    pub fn flatten_all(&mut self) -> Result<Vec<u64>, OrderRejection> {
+        let mut symbols: Vec<String> = unsafe { (*self.account).open_positions.keys() }
+            .cloned()
+            .collect();
+        symbols.sort();
+
+        let mut ids = Vec::new();
+        for symbol in symbols {
+            ids.extend(self.flatten(&symbol)?);
+        }
+        Ok(ids)
+    }
+
+    //submits a stop order and returns its id, or the reason it was rejected
+    pub fn stop_order(
+        &mut self,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+        stop_price: f64,
+    ) -> Result<u64, OrderRejection> {
+        self.check_placement(OrderType::Stop, side, stop_price)?;
+        let qty = self.check_order(&symbol, qty, side)?;
+        let id = unsafe {
+            (*self.execution_engine).stop_order(self.current_time, symbol, qty, side, stop_price)
+        };
+        Ok(self.apply_signal_delay(id))
+    }
+
+    //tags a pending order as an exit, so its fill records why the position was reduced
+    pub fn tag_order(&mut self, order_id: u64, reason: ExitReason) -> bool {
+        unsafe { (*self.execution_engine).tag_order(order_id, reason) }
+    }
+
+    //submits a bracket exit (take-profit limit + protective stop, one-cancels-other)
+    //returns the (take-profit, stop) order ids, or the reason it was rejected
+    pub fn bracket_order(
+        &mut self,
+        symbol: String,
+        qty: u32,
+        side: OrderSide,
+        take_profit: f64,
+        stop_price: f64,
+    ) -> Result<(u64, u64), OrderRejection> {
+        self.check_placement(OrderType::Limit, side, take_profit)?;
+        self.check_placement(OrderType::Stop, side, stop_price)?;
+        let qty = self.check_order(&symbol, qty, side)?;
+        let (target, stop) = unsafe {
+            (*self.execution_engine).bracket_order(
+                self.current_time,
+                symbol,
+                qty,
+                side,
+                take_profit,
+                stop_price,
+            )
+        };
+        Ok((
+            self.apply_signal_delay(target),
+            self.apply_signal_delay(stop),
+        ))
+    }
+
+    //checks an order before submission: halt, quantity, cooldown, position cap, then pyramiding
+    //returns the quantity to submit, which the position cap may have cut
+    fn check_order(
+        &mut self,
+        symbol: &str,
+        qty: u32,
+        side: OrderSide,
+    ) -> Result<u32, OrderRejection> {
+        let result = if self.halted {
+            Err(OrderRejection::TradingHalted)
+        } else if qty == 0 {
+            Err(OrderRejection::ZeroQuantity)
+        } else if !self.tradable_symbols.contains(symbol) {
+            Err(OrderRejection::UnknownSymbol {
+                symbol: symbol.to_string(),
+            })
+        } else if let Some(min_bars) = self.cooldown_blocks(symbol, side) {
+            Err(OrderRejection::Cooldown { min_bars })
+        } else {
+            self.capped_qty(symbol, qty, side)
+                .and_then(|qty| self.check_pyramiding(symbol, side).map(|_| qty))
+        };
+
+        if let Err(rejection) = &result {
+            debug!("{:?} {} {} rejected: {}", side, qty, symbol, rejection);
         }
+        result
+    }
+
+    //checks that a limit or stop price sits on the right side of the last close
+    //a buy limit above it or a sell stop above it (and their mirrors) would trigger at once
+    fn check_placement(
+        &self,
+        order_type: OrderType,
+        side: OrderSide,
+        price: f64,
+    ) -> Result<(), OrderRejection> {
+        let reference = match self.last_bar() {
+            Some(bar) => bar.close,
+            None => return Ok(()),
+        };
+
+        let inverted = match (order_type, side) {
+            (OrderType::Limit, OrderSide::Buy) | (OrderType::Stop, OrderSide::Sell) => {
+                price > reference
+            }
+            (OrderType::Limit, OrderSide::Sell) | (OrderType::Stop, OrderSide::Buy) => {
+                price < reference
+            }
+            (OrderType::Market, _) => false,
+        };
+        if !inverted {
+            return Ok(());
+        }
+
+        let rejection = OrderRejection::InvertedPrice {
+            order_type,
+            side,
+            price,
+            reference,
+        };
+        match self.placement_check {
+            PlacementCheck::Off => Ok(()),
+            PlacementCheck::Warn => {
+                warn!("{}: {}", self.current_time, rejection);
+                Ok(())
+            }
+            PlacementCheck::Reject => {
+                debug!("{} rejected: {}", self.symbol, rejection);
+                Err(rejection)
+            }
+        }
+    }
+
+    //returns true if the order opens a position from flat or adds to it
+    fn is_entry(&self, symbol: &str, side: OrderSide) -> bool {
+        let net_qty = unsafe { (*self.account).get_position(symbol) }
+            .map(|p| p.net_qty)
+            .unwrap_or(0);
+        net_qty == 0 || net_qty.signum() == side.to_qty_sign()
+    }
+
+    //returns the cooldown length if it blocks this order
+    //only entries are throttled; exits and reversals always go through
+    fn cooldown_blocks(&self, symbol: &str, side: OrderSide) -> Option<usize> {
+        let min_bars = self.min_bars_between_trades?;
+        let last_fill_bar = self.last_fill_bar?;
+        (self.bars_seen - last_fill_bar < min_bars && self.is_entry(symbol, side))
+            .then_some(min_bars)
+    }
+
+    //cuts the part of an order that opens contracts so the filled position stays within the
+    //position cap; the closing part always goes through
+    //pending orders aren't counted, only the position held
+    fn capped_qty(&self, symbol: &str, qty: u32, side: OrderSide) -> Result<u32, OrderRejection> {
+        let cap = match self.position_cap() {
+            Some(cap) if symbol == self.symbol => cap,
+            _ => return Ok(qty),
+        };
+        let net_qty = self.net_qty(symbol);
+
+        let (closing, held) = if net_qty != 0 && net_qty.signum() != side.to_qty_sign() {
+            (qty.min(net_qty.unsigned_abs()), 0)
+        } else {
+            (0, net_qty.unsigned_abs())
+        };
+        let opening = (qty - closing).min(cap.saturating_sub(held));
+
+        if closing + opening == 0 {
+            return Err(OrderRejection::VolatilityCap { max_qty: cap });
+        }
+        if closing + opening < qty {
+            debug!(
+                "{:?} {} {} cut to {} by the position cap of {}",
+                side,
+                qty,
+                symbol,
+                closing + opening,
+                cap
+            );
+        }
+        Ok(closing + opening)
+    }
+
+    //checks an order against the pyramiding limit
+    //only add-ons that filled count (see record_pyramid_fill), not pending orders
+    fn check_pyramiding(&self, symbol: &str, side: OrderSide) -> Result<(), OrderRejection> {
+        let max_entries = match self.max_pyramid_entries {
+            Some(max_entries) => max_entries,
+            None => return Ok(()),
+        };
+
+        //orders from flat, and exits or reversals, aren't add-ons
+        let net_qty = self.net_qty(symbol);
+        if net_qty == 0 || net_qty.signum() != side.to_qty_sign() {
+            return Ok(());
+        }
+
+        let entries = self.pyramid_entries.get(symbol).map_or(0, HashSet::len);
+        if entries >= max_entries {
+            return Err(OrderRejection::PyramidLimit { max_entries });
+        }
+        Ok(())
     }
 
     //returns the current position for the strategy's symbol
     pub fn current_position(&self) -> Option<&Position> {
-        unsafe { (*self.account).get_position(&self.symbol) }
+        self.position(&self.symbol)
+    }
+
+    //returns the current position for any symbol (eg the second leg of a spread)
+    pub fn position(&self, symbol: &str) -> Option<&Position> {
+        unsafe { (*self.account).get_position(symbol) }
+    }
+
+    //records the stop an open position was entered with, the basis of its trades' r-multiples
+    //only the first stop of a position counts; returns false if it's flat or already has one
+    pub fn set_initial_stop(&mut self, symbol: &str, stop_price: f64) -> bool {
+        unsafe { (*self.account).set_initial_stop(symbol, stop_price) }
+    }
+
+    //returns the signed net quantity held in a symbol (0 when flat)
+    pub fn net_qty(&self, symbol: &str) -> i32 {
+        self.position(symbol).map(|p| p.net_qty).unwrap_or(0)
+    }
+
+    //returns the contract specification for the traded symbol
+    pub fn contract(&self) -> &FuturesContract {
+        &self.contract
+    }
+
+    //returns the unrealized pnl of the current position, marked at the last close
+    pub fn unrealized_pnl(&self) -> f64 {
+        match (self.current_position(), self.last_bar()) {
+            (Some(position), Some(bar)) => position.unrealized_pnl(bar.close, &self.contract),
+            _ => 0.0,
+        }
+    }
+
+    //returns the average entry price of the open position, or none if flat
+    pub fn entry_price(&self) -> Option<f64> {
+        self.entry_price_for(&self.symbol)
+    }
+
+    //returns the average entry price of a symbol's open position, or none if flat
+    pub fn entry_price_for(&self, symbol: &str) -> Option<f64> {
+        self.position(symbol)
+            .filter(|p| !p.is_flat())
+            .map(|p| p.avg_entry_price)
+    }
+
+    //returns the open profit per contract in ticks, marked at the last close
+    pub fn pnl_ticks(&self) -> f64 {
+        match (self.current_position(), self.last_bar()) {
+            (Some(position), Some(bar)) => position.pnl_ticks(bar.close, &self.contract),
+            _ => 0.0,
+        }
+    }
+
+    //returns the realized pnl accumulated on the traded symbol
+    pub fn realized_pnl(&self) -> f64 {
+        self.realized_pnl_for(&self.symbol)
+    }
+
+    //returns the realized pnl accumulated on a symbol
+    pub fn realized_pnl_for(&self, symbol: &str) -> f64 {
+        self.position(symbol).map(|p| p.realized_pnl).unwrap_or(0.0)
+    }
+
+    //returns the unrealized pnl of a symbol's position marked at price
+    //other legs have their own contract spec, so the caller supplies it
+    pub fn unrealized_pnl_for(&self, symbol: &str, price: f64, contract: &FuturesContract) -> f64 {
+        self.position(symbol)
+            .map(|p| p.unrealized_pnl(price, contract))
+            .unwrap_or(0.0)
     }
 
     //returns the current cash balance
@@ -127,11 +644,22 @@ impl StrategyContext {
         unsafe { (*self.account).equity }
     }
 
-    //returns the number of bars in history
+    //returns the number of bars in history (capped at max_history)
     pub fn bar_count(&self) -> usize {
         self.bar_history.len()
     }
 
+    //returns the zero-based index of the current bar in the run, which keeps counting past
+    //max_history (the engine's bar index; 0 before the first bar)
+    pub fn bar_index(&self) -> usize {
+        self.bars_seen.saturating_sub(1)
+    }
+
+    //cancels a pending order; returns false if it already filled or was cancelled
+    pub fn cancel_order(&mut self, order_id: u64) -> bool {
+        unsafe { (*self.execution_engine).cancel_order(order_id) }
+    }
+
     //cancels all pending orders
     pub fn cancel_all_orders(&mut self) {
         unsafe {
@@ -148,32 +676,67 @@ pub fn sma(prices: &[f64]) -> Option<f64> {
     Some(prices.iter().sum::<f64>() / prices.len() as f64)
 }
 
+//helper function to calculate exponential moving average
+//seeds with the sma of the first period prices, then smooths over the rest
+pub fn ema(prices: &[f64], period: usize) -> Option<f64> {
+    if period == 0 || prices.len() < period {
+        return None;
+    }
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let seed = sma(&prices[..period])?;
+
+    Some(
+        prices[period..]
+            .iter()
+            .fold(seed, |ema, price| alpha * price + (1.0 - alpha) * ema),
+    )
+}
+
+//helper function to calculate average true range over the last period bars
+//needs period + 1 bars since each true range uses the previous close
+pub fn atr(bars: &[&Bar], period: usize) -> Option<f64> {
+    if period == 0 || bars.len() < period + 1 {
+        return None;
+    }
+
+    let true_ranges: Vec<f64> = bars
+        .windows(2)
+        .map(|pair| {
+            let (prev, bar) = (pair[0], pair[1]);
+            (bar.high - bar.low)
+                .max((bar.high - prev.close).abs())
+                .max((bar.low - prev.close).abs())
+        })
+        .collect();
+
+    sma(&true_ranges[true_ranges.len() - period..])
+}
+
 //helper function to calculate relative strength index
+//uses only the last period + 1 prices (period changes); any earlier history is ignored
+//returns none if period is zero or fewer than period + 1 prices are given
 pub fn rsi(prices: &[f64], period: usize) -> Option<f64> {
-    if prices.len() < period + 1 {
+    if period == 0 || prices.len() < period + 1 {
         return None;
     }
 
-    let mut gains = Vec::new();
-    let mut losses = Vec::new();
+    let window = &prices[prices.len() - (period + 1)..];
 
-    for i in 1..prices.len() {
-        let change = prices[i] - prices[i - 1];
+    let mut total_gain = 0.0;
+    let mut total_loss = 0.0;
+
+    for pair in window.windows(2) {
+        let change = pair[1] - pair[0];
         if change > 0.0 {
-            gains.push(change);
-            losses.push(0.0);
+            total_gain += change;
         } else {
-            gains.push(0.0);
-            losses.push(-change);
+            total_loss -= change;
         }
     }
 
-    if gains.len() < period {
-        return None;
-    }
-
-    let avg_gain: f64 = gains.iter().rev().take(period).sum::<f64>() / period as f64;
-    let avg_loss: f64 = losses.iter().rev().take(period).sum::<f64>() / period as f64;
+    let avg_gain = total_gain / period as f64;
+    let avg_loss = total_loss / period as f64;
 
     if avg_loss == 0.0 {
         return Some(100.0);
@@ -182,3 +745,541 @@ pub fn rsi(prices: &[f64], period: usize) -> Option<f64> {
     let rs = avg_gain / avg_loss;
     Some(100.0 - (100.0 / (1.0 + rs)))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{BacktestConfig, BacktestEngine};
+    use crate::portfolio::AccountMode;
+    use chrono::TimeZone;
+
+    //counts the bars it sees, read back after the run through a downcast
+    struct BarCounter {
+        bars: usize,
+    }
+
+    impl Strategy for BarCounter {
+        fn on_start(&mut self, _context: &mut StrategyContext) {}
+
+        fn on_bar(&mut self, _context: &mut StrategyContext, _bar: &Bar) {
+            self.bars += 1;
+        }
+
+        fn on_end(&mut self, _context: &mut StrategyContext) {}
+
+        fn name(&self) -> &str {
+            "Bar Counter"
+        }
+    }
+
+    fn bars(n: usize) -> Vec<Bar> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        (0..n)
+            .map(|i| {
+                let close = 4000.0 + i as f64;
+                Bar::new(
+                    start + chrono::Duration::days(i as i64),
+                    close,
+                    close + 1.0,
+                    close - 1.0,
+                    close,
+                    1000.0,
+                    None,
+                    "ES".to_string(),
+                )
+                .unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn boxed_strategy_downcasts_after_run() {
+        let mut engine = BacktestEngine::new(
+            BacktestConfig::default(),
+            bars(10),
+            FuturesContract::es("H24"),
+        );
+        let mut strategy: Box<dyn Strategy> = Box::new(BarCounter { bars: 0 });
+        engine.run(&mut strategy);
+
+        assert_eq!(strategy.downcast_ref::<BarCounter>().unwrap().bars, 10);
+        assert!(strategy
+            .downcast_ref::<crate::strategy::sma_crossover::SmaCrossoverStrategy>()
+            .is_none());
+
+        strategy.downcast_mut::<BarCounter>().unwrap().bars = 0;
+        assert_eq!(strategy.downcast_ref::<BarCounter>().unwrap().bars, 0);
+    }
+
+    //fills the pending market orders at price and books them the way the engine does
+    fn book_market_fills(
+        context: &mut StrategyContext,
+        execution: &mut ExecutionEngine,
+        account: &mut Account,
+        price: f64,
+    ) {
+        for fill in execution.fill_market_orders_at(price, context.current_time) {
+            let (symbol, order_id) = (fill.symbol.clone(), fill.order_id);
+            let net_before = account.get_position(&symbol).map_or(0, |p| p.net_qty);
+            account.process_fill(fill, context.contract()).unwrap();
+            context.record_pyramid_fill(&symbol, order_id, net_before);
+        }
+    }
+
+    #[test]
+    fn fourth_add_on_is_rejected_with_a_limit_of_three() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.set_max_pyramid_entries(Some(3));
+
+        //the entry from flat, then three add-ons
+        for _ in 0..4 {
+            context
+                .market_order("ES".to_string(), 1, OrderSide::Buy)
+                .unwrap();
+            book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        }
+
+        assert_eq!(
+            context.market_order("ES".to_string(), 1, OrderSide::Buy),
+            Err(OrderRejection::PyramidLimit { max_entries: 3 })
+        );
+        //exits still go through
+        assert!(context
+            .market_order("ES".to_string(), 1, OrderSide::Sell)
+            .is_ok());
+    }
+
+    #[test]
+    fn protective_stops_do_not_reset_the_pyramid_count() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.set_max_pyramid_entries(Some(3));
+
+        for _ in 0..4 {
+            context
+                .market_order("ES".to_string(), 1, OrderSide::Buy)
+                .unwrap();
+            book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+            //an accepted opposite-side order that hasn't filled is not an exit
+            context
+                .stop_order("ES".to_string(), 1, OrderSide::Sell, 3990.0)
+                .unwrap();
+        }
+
+        assert_eq!(
+            context.market_order("ES".to_string(), 1, OrderSide::Buy),
+            Err(OrderRejection::PyramidLimit { max_entries: 3 })
+        );
+    }
+
+    #[test]
+    fn pyramid_count_restarts_after_the_position_is_closed() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.set_max_pyramid_entries(Some(1));
+
+        for _ in 0..2 {
+            context
+                .market_order("ES".to_string(), 1, OrderSide::Buy)
+                .unwrap();
+            book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        }
+        context.flatten("ES").unwrap();
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        assert_eq!(context.net_qty("ES"), 0);
+
+        for _ in 0..2 {
+            context
+                .market_order("ES".to_string(), 1, OrderSide::Buy)
+                .unwrap();
+            book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        }
+        assert_eq!(context.net_qty("ES"), 2);
+    }
+
+    #[test]
+    fn winning_position_reports_positive_unrealized_pnl() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(100000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        let mut history = bars(3).into_iter();
+
+        context.push_bar(history.next().unwrap());
+        context
+            .market_order("ES".to_string(), 1, OrderSide::Buy)
+            .unwrap();
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        for bar in history {
+            context.push_bar(bar);
+        }
+
+        //two points up on a 50-dollar point value
+        assert_eq!(context.unrealized_pnl(), 100.0);
+        assert_eq!(context.pnl_ticks(), 8.0);
+        assert_eq!(context.realized_pnl(), 0.0);
+
+        context
+            .market_order("ES".to_string(), 1, OrderSide::Sell)
+            .unwrap();
+        book_market_fills(&mut context, &mut execution, &mut account, 4002.0);
+        assert_eq!(context.unrealized_pnl(), 0.0);
+        assert_eq!(context.realized_pnl(), 100.0);
+    }
+
+    #[test]
+    fn zero_quantity_orders_are_rejected_and_never_filled() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(100000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.push_bar(bars(1).remove(0));
+
+        assert_eq!(
+            context.market_order("ES".to_string(), 0, OrderSide::Buy),
+            Err(OrderRejection::ZeroQuantity)
+        );
+        assert_eq!(
+            context.limit_order("ES".to_string(), 0, OrderSide::Buy, 3990.0),
+            Err(OrderRejection::ZeroQuantity)
+        );
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+
+        assert_eq!(execution.pending_order_count(), 0);
+        assert!(account.trade_log.fills().is_empty());
+        assert_eq!(context.net_qty("ES"), 0);
+    }
+
+    #[test]
+    fn accessors_read_the_second_leg_of_a_spread() {
+        let (es, nq) = (FuturesContract::es("H24"), FuturesContract::nq("H24"));
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context = StrategyContext::new(es.clone(), 10, &mut execution, &mut account);
+        context.add_tradable_symbol("NQ");
+
+        context
+            .market_order("ES".to_string(), 1, OrderSide::Buy)
+            .unwrap();
+        context
+            .market_order("NQ".to_string(), 2, OrderSide::Sell)
+            .unwrap();
+        for fill in execution.fill_market_orders_at(4000.0, context.current_time) {
+            let contract = if fill.symbol == "NQ" { &nq } else { &es };
+            account.process_fill(fill, contract).unwrap();
+        }
+
+        assert_eq!(context.net_qty("ES"), 1);
+        assert_eq!(context.net_qty("NQ"), -2);
+        assert_eq!(context.net_qty("CL"), 0);
+        assert_eq!(context.current_position().unwrap().net_qty, 1);
+        assert_eq!(context.entry_price_for("NQ"), Some(4000.0));
+        //short two nq contracts, 10 points in favor at 20 dollars a point
+        assert_eq!(context.unrealized_pnl_for("NQ", 3990.0, &nq), 400.0);
+        assert_eq!(context.realized_pnl_for("NQ"), 0.0);
+    }
+
+    #[test]
+    fn entries_within_the_cooldown_are_rejected() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.set_min_bars_between_trades(Some(3));
+        let mut bars = bars(5).into_iter();
+
+        //enter on the first bar and exit straight away; the exit isn't throttled
+        context.push_bar(bars.next().unwrap());
+        context
+            .market_order("ES".to_string(), 1, OrderSide::Buy)
+            .unwrap();
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        context.record_fill();
+        assert!(context
+            .market_order("ES".to_string(), 1, OrderSide::Sell)
+            .is_ok());
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+
+        //the fill bar and the two after it reject entries in either direction
+        for _ in 0..2 {
+            context.push_bar(bars.next().unwrap());
+            for side in [OrderSide::Buy, OrderSide::Sell] {
+                assert_eq!(
+                    context.market_order("ES".to_string(), 1, side),
+                    Err(OrderRejection::Cooldown { min_bars: 3 })
+                );
+            }
+        }
+
+        context.push_bar(bars.next().unwrap());
+        assert!(context
+            .market_order("ES".to_string(), 1, OrderSide::Buy)
+            .is_ok());
+    }
+
+    //submits a limit or stop at price after a bar closing at 4000
+    fn place(
+        check: PlacementCheck,
+        order_type: OrderType,
+        side: OrderSide,
+        price: f64,
+    ) -> Result<u64, OrderRejection> {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.set_placement_check(check);
+        context.push_bar(bars(1).remove(0));
+
+        match order_type {
+            OrderType::Stop => context.stop_order("ES".to_string(), 1, side, price),
+            _ => context.limit_order("ES".to_string(), 1, side, price),
+        }
+    }
+
+    const INVERTED: [(OrderType, OrderSide, f64); 4] = [
+        (OrderType::Limit, OrderSide::Buy, 4010.0),
+        (OrderType::Limit, OrderSide::Sell, 3990.0),
+        (OrderType::Stop, OrderSide::Buy, 3990.0),
+        (OrderType::Stop, OrderSide::Sell, 4010.0),
+    ];
+
+    #[test]
+    fn reject_refuses_each_inverted_placement() {
+        for (order_type, side, price) in INVERTED {
+            assert_eq!(
+                place(PlacementCheck::Reject, order_type, side, price),
+                Err(OrderRejection::InvertedPrice {
+                    order_type,
+                    side,
+                    price,
+                    reference: 4000.0,
+                })
+            );
+
+            //the mirror price is on the right side
+            let mirrored = 8000.0 - price;
+            assert!(place(PlacementCheck::Reject, order_type, side, mirrored).is_ok());
+        }
+    }
+
+    #[test]
+    fn warn_and_off_accept_inverted_placements() {
+        for (order_type, side, price) in INVERTED {
+            assert!(place(PlacementCheck::Warn, order_type, side, price).is_ok());
+            assert!(place(PlacementCheck::Off, order_type, side, price).is_ok());
+        }
+    }
+
+    #[test]
+    fn limit_offset_improves_on_the_close_and_snaps_to_the_tick() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        assert_eq!(
+            context.limit_order_offset("ES".to_string(), 1, OrderSide::Buy, 2),
+            Err(OrderRejection::NoReferencePrice)
+        );
+
+        //an off-tick close: two ticks either side lands between ticks and is snapped
+        let close = 4000.1;
+        let bar = Bar::new(
+            Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            close,
+            close + 1.0,
+            close - 1.0,
+            close,
+            1000.0,
+            None,
+            "ES".to_string(),
+        )
+        .unwrap();
+        context.push_bar(bar);
+        context
+            .limit_order_offset("ES".to_string(), 1, OrderSide::Buy, 2)
+            .unwrap();
+        context
+            .limit_order_offset("ES".to_string(), 1, OrderSide::Sell, 2)
+            .unwrap();
+
+        let prices: Vec<_> = execution
+            .pending_orders()
+            .iter()
+            .map(|order| (order.side, order.limit_price))
+            .collect();
+        assert_eq!(
+            prices,
+            vec![
+                (OrderSide::Buy, Some(3999.5)),
+                (OrderSide::Sell, Some(4000.5))
+            ]
+        );
+    }
+
+    #[test]
+    fn flatten_a_short_submits_a_reduce_only_buy_of_its_size() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.push_bar(bars(1).remove(0));
+        assert_eq!(context.flatten("ES"), Ok(Vec::new()));
+
+        context
+            .market_order("ES".to_string(), 3, OrderSide::Sell)
+            .unwrap();
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+
+        let ids = context.flatten("ES").unwrap();
+        let pending = execution.pending_orders();
+        assert_eq!(ids, vec![pending[0].id]);
+        assert_eq!((pending[0].side, pending[0].qty), (OrderSide::Buy, 3));
+        assert!(pending[0].reduce_only);
+    }
+
+    #[test]
+    fn flatten_all_closes_both_hedged_legs() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        account.set_mode(AccountMode::Hedging);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.push_bar(bars(1).remove(0));
+
+        context
+            .market_order("ES".to_string(), 2, OrderSide::Buy)
+            .unwrap();
+        context
+            .market_order("ES".to_string(), 1, OrderSide::Sell)
+            .unwrap();
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+
+        assert_eq!(context.flatten_all().unwrap().len(), 2);
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+        assert!(account.get_position("ES").is_none_or(|p| p.is_flat()));
+    }
+
+    //flat closes at 4000 with every bar spanning range points, so the atr is range
+    fn push_ranged_bars(context: &mut StrategyContext, range: f64, n: usize) {
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        for i in 0..n {
+            let bar = Bar::new(
+                start + chrono::Duration::days(i as i64),
+                4000.0,
+                4000.0 + range / 2.0,
+                4000.0 - range / 2.0,
+                4000.0,
+                1000.0,
+                None,
+                "ES".to_string(),
+            )
+            .unwrap();
+            context.push_bar(bar);
+        }
+    }
+
+    #[test]
+    fn higher_atr_gives_a_smaller_vol_target_cap() {
+        let target = VolTarget::new(1000.0, 5);
+        let cap_at = |range: f64| {
+            let mut execution = ExecutionEngine::new();
+            let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+            let mut context =
+                StrategyContext::new(FuturesContract::es("H24"), 20, &mut execution, &mut account);
+            push_ranged_bars(&mut context, range, 6);
+            context.vol_target_qty(target)
+        };
+
+        //one es point is 50 dollars: a 2 point atr risks 100 a contract, an 8 point atr 400
+        assert_eq!(cap_at(2.0), Some(10));
+        assert_eq!(cap_at(8.0), Some(2));
+    }
+
+    #[test]
+    fn vol_target_cuts_entries_to_the_cap() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 20, &mut execution, &mut account);
+        context.set_vol_target(Some(VolTarget::new(1000.0, 5)));
+        push_ranged_bars(&mut context, 8.0, 4);
+        //too few bars for the atr, so no cap yet
+        assert_eq!(context.position_cap(), None);
+        push_ranged_bars(&mut context, 8.0, 2);
+        assert_eq!(context.position_cap(), Some(2));
+
+        context
+            .market_order("ES".to_string(), 5, OrderSide::Buy)
+            .unwrap();
+        assert_eq!(execution.pending_orders()[0].qty, 2);
+        book_market_fills(&mut context, &mut execution, &mut account, 4000.0);
+
+        assert_eq!(
+            context.market_order("ES".to_string(), 1, OrderSide::Buy),
+            Err(OrderRejection::VolatilityCap { max_qty: 2 })
+        );
+        //closing is never capped
+        context
+            .market_order("ES".to_string(), 2, OrderSide::Sell)
+            .unwrap();
+    }
+
+    #[test]
+    fn orders_for_a_symbol_without_a_contract_are_rejected() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(1_000_000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+        context.set_min_bars_between_trades(Some(3));
+        context.push_bar(bars(1).remove(0));
+
+        assert_eq!(
+            context.market_order("CL".to_string(), 1, OrderSide::Buy),
+            Err(OrderRejection::UnknownSymbol {
+                symbol: "CL".to_string()
+            })
+        );
+        assert_eq!(
+            context.stop_order("CL".to_string(), 1, OrderSide::Buy, 4010.0),
+            Err(OrderRejection::UnknownSymbol {
+                symbol: "CL".to_string()
+            })
+        );
+        assert_eq!(execution.pending_order_count(), 0);
+        assert_eq!(execution.order_stats().submitted, 0);
+
+        //nothing was submitted, so the traded symbol isn't held back by the cooldown
+        context
+            .market_order("ES".to_string(), 1, OrderSide::Buy)
+            .unwrap();
+    }
+
+    #[test]
+    fn rsi_needs_period_plus_one_prices() {
+        let prices = [10.0, 11.0, 10.0, 12.0];
+        assert_eq!(rsi(&prices[..3], 3), None);
+        //gains 1 + 2 and a loss of 1 over three changes: rs 3, rsi 75
+        assert_eq!(rsi(&prices, 3), Some(75.0));
+    }
+
+    #[test]
+    fn rsi_of_a_longer_slice_uses_only_its_trailing_window() {
+        let prices = [50.0, 20.0, 10.0, 11.0, 10.0, 12.0];
+        assert_eq!(rsi(&prices, 3), rsi(&prices[2..], 3));
+        assert_eq!(rsi(&prices, 3), Some(75.0));
+    }
+
+    #[test]
+    fn rsi_with_a_zero_period_is_none() {
+        assert_eq!(rsi(&[10.0, 11.0, 12.0], 0), None);
+        assert_eq!(rsi(&[], 0), None);
+    }
+}
@@ -0,0 +1,131 @@
+use crate::engine::execution::{ExitReason, OrderSide};
+use crate::strategy::StrategyContext;
+use log::debug;
+
+//protective exits a fixed number of ticks from a position's entry price
+//a stop-loss, a take-profit, or both as a one-cancels-other bracket
+//the orders go in once the strategy sees the entry filled, so they work from the next bar
+#[derive(Debug, Clone, Default)]
+pub struct ProtectiveExits {
+    stop_loss_ticks: Option<u32>,
+    take_profit_ticks: Option<u32>,
+
+    //state
+    //resting protective orders and the net position they cover
+    order_ids: Vec<u64>,
+    protected_qty: i32,
+}
+
+impl ProtectiveExits {
+    pub fn new(stop_loss_ticks: Option<u32>, take_profit_ticks: Option<u32>) -> Self {
+        ProtectiveExits {
+            stop_loss_ticks,
+            take_profit_ticks,
+            order_ids: Vec::new(),
+            protected_qty: 0,
+        }
+    }
+
+    //returns true if a stop-loss or take-profit distance is set
+    pub fn is_enabled(&self) -> bool {
+        self.stop_loss_ticks.is_some() || self.take_profit_ticks.is_some()
+    }
+
+    //forgets orders from a previous run
+    pub fn reset(&mut self) {
+        self.order_ids.clear();
+        self.protected_qty = 0;
+    }
+
+    //cancels the resting protective orders
+    //call before the strategy exits or reverses on its own signal
+    pub fn cancel(&mut self, context: &mut StrategyContext) {
+        for id in self.order_ids.drain(..) {
+            context.cancel_order(id);
+        }
+        self.protected_qty = 0;
+    }
+
+    //places protective orders for a newly opened or resized position, and drops stale ones
+    //once the position is flat (eg after a protective fill)
+    //a level the last close has already crossed is exited at market instead
+    pub fn update(&mut self, context: &mut StrategyContext, symbol: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let net_qty = context.net_qty(symbol);
+        if net_qty == self.protected_qty {
+            return;
+        }
+        self.cancel(context);
+
+        let (entry_price, close) = match (context.entry_price_for(symbol), context.last_bar()) {
+            (Some(entry_price), Some(bar)) if net_qty != 0 => (entry_price, bar.close),
+            _ => return,
+        };
+
+        let tick_size = context.contract().tick_size;
+        let direction = net_qty.signum() as f64;
+        let stop_price = self
+            .stop_loss_ticks
+            .map(|ticks| entry_price - direction * ticks as f64 * tick_size);
+        let take_profit = self
+            .take_profit_ticks
+            .map(|ticks| entry_price + direction * ticks as f64 * tick_size);
+
+        if let Some(stop_price) = stop_price {
+            context.set_initial_stop(symbol, stop_price);
+        }
+
+        let qty = net_qty.unsigned_abs();
+        let side = if net_qty > 0 {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        self.protected_qty = net_qty;
+
+        let stop_crossed = stop_price.is_some_and(|stop| (close - stop) * direction <= 0.0);
+        let target_crossed = take_profit.is_some_and(|target| (target - close) * direction <= 0.0);
+        if stop_crossed || target_crossed {
+            let reason = if stop_crossed {
+                ExitReason::StopLoss
+            } else {
+                ExitReason::TakeProfit
+            };
+            debug!(
+                "{} already beyond its protective level at {}, exiting at market",
+                symbol, close
+            );
+            if let Ok(id) = context.market_order(symbol.to_string(), qty, side) {
+                context.tag_order(id, reason);
+                self.order_ids.push(id);
+            }
+            return;
+        }
+
+        match (stop_price, take_profit) {
+            (Some(stop_price), Some(take_profit)) => {
+                if let Ok((target, stop)) =
+                    context.bracket_order(symbol.to_string(), qty, side, take_profit, stop_price)
+                {
+                    self.order_ids.extend([target, stop]);
+                }
+            }
+            (Some(stop_price), None) => {
+                if let Ok(id) = context.stop_order(symbol.to_string(), qty, side, stop_price) {
+                    context.tag_order(id, ExitReason::StopLoss);
+                    self.order_ids.push(id);
+                }
+            }
+            (None, Some(take_profit)) => {
+                if let Ok(id) = context.limit_order(symbol.to_string(), qty, side, take_profit) {
+                    context.tag_order(id, ExitReason::TakeProfit);
+                    self.order_ids.push(id);
+                }
+            }
+            (None, None) => {}
+        }
+    }
+}
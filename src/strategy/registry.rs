@@ -0,0 +1,160 @@
+use crate::strategy::Strategy;
+use std::collections::HashMap;
+use std::sync::{OnceLock, RwLock};
+
+//generic parameter bag for strategies registered at runtime
+pub type ParamMap = HashMap<String, f64>;
+
+//builds a strategy from its parameter bag, symbol and quantity
+pub type StrategyFactory =
+    Box<dyn Fn(&ParamMap, &str, u32) -> anyhow::Result<Box<dyn Strategy>> + Send + Sync>;
+
+//registry of strategy factories keyed by (case-insensitive) name
+#[derive(Default)]
+pub struct StrategyRegistry {
+    factories: HashMap<String, StrategyFactory>,
+}
+
+impl StrategyRegistry {
+    //creates an empty registry
+    pub fn new() -> Self {
+        StrategyRegistry {
+            factories: HashMap::new(),
+        }
+    }
+
+    //registers a factory under a name, replacing any previous one
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(&ParamMap, &str, u32) -> anyhow::Result<Box<dyn Strategy>> + Send + Sync + 'static,
+    {
+        self.factories
+            .insert(name.to_lowercase(), Box::new(factory));
+    }
+
+    //returns true if a factory is registered under the name
+    pub fn contains(&self, name: &str) -> bool {
+        self.factories.contains_key(&name.to_lowercase())
+    }
+
+    //returns the registered names, sorted
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.factories.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    //builds a strategy using the factory registered under the name
+    pub fn create(
+        &self,
+        name: &str,
+        params: &ParamMap,
+        symbol: &str,
+        qty: u32,
+    ) -> anyhow::Result<Box<dyn Strategy>> {
+        let factory = self
+            .factories
+            .get(&name.to_lowercase())
+            .ok_or_else(|| anyhow::anyhow!("No strategy registered as '{}'", name))?;
+
+        factory(params, symbol, qty)
+    }
+}
+
+//process-wide registry used by the config and cli flow
+fn global_registry() -> &'static RwLock<StrategyRegistry> {
+    static REGISTRY: OnceLock<RwLock<StrategyRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| RwLock::new(StrategyRegistry::new()))
+}
+
+//registers a strategy factory in the process-wide registry so it can be selected by name
+//from BacktestConfiguration (StrategyParams::Custom) and the cli
+pub fn register_strategy<F>(name: &str, factory: F)
+where
+    F: Fn(&ParamMap, &str, u32) -> anyhow::Result<Box<dyn Strategy>> + Send + Sync + 'static,
+{
+    global_registry()
+        .write()
+        .unwrap_or_else(|e| e.into_inner())
+        .register(name, factory);
+}
+
+//returns true if a strategy is registered under the name in the process-wide registry
+pub fn is_registered(name: &str) -> bool {
+    global_registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .contains(name)
+}
+
+//builds a strategy from the process-wide registry
+pub fn create_registered(
+    name: &str,
+    params: &ParamMap,
+    symbol: &str,
+    qty: u32,
+) -> anyhow::Result<Box<dyn Strategy>> {
+    global_registry()
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .create(name, params, symbol, qty)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::strategy::sma_crossover::SmaCrossoverStrategy;
+
+    //builds an sma crossover from "fast" and "slow" params
+    fn sma_factory(params: &ParamMap, symbol: &str, qty: u32) -> anyhow::Result<Box<dyn Strategy>> {
+        let window = |name: &str| {
+            params
+                .get(name)
+                .map(|&v| v as usize)
+                .ok_or_else(|| anyhow::anyhow!("missing param {}", name))
+        };
+        Ok(Box::new(SmaCrossoverStrategy::new(
+            symbol.to_string(),
+            window("fast")?,
+            window("slow")?,
+            None,
+            qty,
+        )))
+    }
+
+    #[test]
+    fn registered_factory_builds_by_name_in_any_case() {
+        let mut registry = StrategyRegistry::new();
+        registry.register("My_Sma", sma_factory);
+        let params = ParamMap::from([("fast".to_string(), 3.0), ("slow".to_string(), 8.0)]);
+
+        let strategy = registry.create("my_sma", &params, "ES", 1).unwrap();
+        assert_eq!(strategy.name(), "SMA Crossover");
+        assert_eq!(strategy.required_lookback(), 8);
+        assert_eq!(registry.names(), vec!["my_sma".to_string()]);
+    }
+
+    #[test]
+    fn unknown_name_and_factory_errors_are_reported() {
+        let mut registry = StrategyRegistry::new();
+        registry.register("my_sma", sma_factory);
+
+        assert!(registry.create("nope", &ParamMap::new(), "ES", 1).is_err());
+        //the factory's own error comes back as is
+        let err = registry
+            .create("my_sma", &ParamMap::new(), "ES", 1)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("missing param fast"));
+    }
+
+    #[test]
+    fn process_wide_registration_is_visible_to_create_registered() {
+        register_strategy("registry_test_sma", sma_factory);
+        let params = ParamMap::from([("fast".to_string(), 2.0), ("slow".to_string(), 5.0)]);
+
+        assert!(is_registered("REGISTRY_TEST_SMA"));
+        let strategy = create_registered("registry_test_sma", &params, "NQ", 2).unwrap();
+        assert_eq!(strategy.required_lookback(), 5);
+    }
+}
@@ -1,17 +1,28 @@
 use crate::data::Bar;
-use crate::engine::execution::OrderSide;
-use crate::strategy::{rsi, Strategy, StrategyContext};
+use crate::engine::execution::{ExitReason, OrderSide};
+use crate::strategy::{
+    protective::ProtectiveExits, rsi, sizing::PositionSizing, Strategy, StrategyContext,
+};
 
 //rsi mean reversion strategy
 //buys when rsi drops below oversold threshold
 //sells when rsi rises above overbought threshold
+//with confirmation_bars > 1, rsi must stay beyond the threshold that many bars in a row
+//optional protective stop-loss / take-profit orders guard each position until the next signal
 #[derive(Debug, Clone)]
 pub struct RsiReversionStrategy {
     symbol: String,
     lookback: usize,
     oversold: f64,
     overbought: f64,
-    qty: u32,
+    confirmation_bars: usize,
+    sizing: PositionSizing,
+    exits: ProtectiveExits,
+
+    //state
+    //consecutive bars (including the current one) rsi has been below oversold / above overbought
+    bars_oversold: usize,
+    bars_overbought: usize,
 }
 
 impl RsiReversionStrategy {
@@ -21,10 +32,36 @@ impl RsiReversionStrategy {
             lookback,
             oversold,
             overbought,
-            qty,
+            confirmation_bars: 1,
+            sizing: PositionSizing::Fixed(qty),
+            exits: ProtectiveExits::default(),
+            bars_oversold: 0,
+            bars_overbought: 0,
         }
     }
 
+    //requires rsi to stay beyond a threshold for this many consecutive bars before entering
+    pub fn with_confirmation_bars(mut self, confirmation_bars: usize) -> Self {
+        self.confirmation_bars = confirmation_bars.max(1);
+        self
+    }
+
+    //sizes entries with the given mode instead of the fixed quantity
+    pub fn with_sizing(mut self, sizing: PositionSizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+
+    //places a stop-loss and/or take-profit this many ticks from the entry price of each position
+    pub fn with_protective_exits(
+        mut self,
+        stop_loss_ticks: Option<u32>,
+        take_profit_ticks: Option<u32>,
+    ) -> Self {
+        self.exits = ProtectiveExits::new(stop_loss_ticks, take_profit_ticks);
+        self
+    }
+
     //default rsi strategy with standard parameters
     pub fn default(symbol: String, qty: u32) -> Self {
         Self::new(symbol, 14, 30.0, 70.0, qty)
@@ -33,10 +70,16 @@ impl RsiReversionStrategy {
 
 impl Strategy for RsiReversionStrategy {
     fn on_start(&mut self, _context: &mut StrategyContext) {
-        //no initialization needed
+        //initialize state
+        self.bars_oversold = 0;
+        self.bars_overbought = 0;
+        self.exits.reset();
     }
 
     fn on_bar(&mut self, context: &mut StrategyContext, _bar: &Bar) {
+        //protect a newly filled entry
+        self.exits.update(context, &self.symbol);
+
         //need at least lookback + 1 bars for rsi calculation
         if context.bar_count() < self.lookback + 1 {
             return;
@@ -51,67 +94,146 @@ impl Strategy for RsiReversionStrategy {
             None => return,
         };
 
+        //track how long rsi has stayed beyond each threshold
+        self.bars_oversold = if rsi_value < self.oversold {
+            self.bars_oversold + 1
+        } else {
+            0
+        };
+        self.bars_overbought = if rsi_value > self.overbought {
+            self.bars_overbought + 1
+        } else {
+            0
+        };
+
+        let entry_qty = self.sizing.entry_qty(context);
+
         //get current position
         let current_position = context.current_position();
         let current_quantity = current_position.map(|p| p.net_qty).unwrap_or(0);
 
         //trading logic
         if rsi_value < self.oversold {
-            //oversold - go long if not already
-            if current_quantity <= 0 {
+            //oversold (for enough bars) - go long if not already
+            if current_quantity <= 0 && self.bars_oversold >= self.confirmation_bars {
+                self.exits.cancel(context);
                 let quantity_to_buy = if current_quantity < 0 {
                     //close short and open long
-                    (current_quantity.abs() + self.qty as i32) as u32
+                    (current_quantity.abs() + entry_qty as i32) as u32
                 } else {
                     //just open long
-                    self.qty
+                    entry_qty
                 };
 
-                context.market_order(self.symbol.clone(), quantity_to_buy, OrderSide::Buy);
+                let order =
+                    context.market_order(self.symbol.clone(), quantity_to_buy, OrderSide::Buy);
+                if let (Ok(id), true) = (order, current_quantity < 0) {
+                    context.tag_order(id, ExitReason::SignalFlip);
+                }
             }
         } else if rsi_value > self.overbought {
-            //overbought - go short if not already
-            if current_quantity >= 0 {
+            //overbought (for enough bars) - go short if not already
+            if current_quantity >= 0 && self.bars_overbought >= self.confirmation_bars {
+                self.exits.cancel(context);
                 let quantity_to_sell = if current_quantity > 0 {
                     //close long and open short
-                    (current_quantity.abs() + self.qty as i32) as u32
+                    (current_quantity.abs() + entry_qty as i32) as u32
                 } else {
                     //just open short
-                    self.qty
+                    entry_qty
                 };
 
-                context.market_order(self.symbol.clone(), quantity_to_sell, OrderSide::Sell);
+                let order =
+                    context.market_order(self.symbol.clone(), quantity_to_sell, OrderSide::Sell);
+                if let (Ok(id), true) = (order, current_quantity > 0) {
+                    context.tag_order(id, ExitReason::SignalFlip);
+                }
             }
         } else {
             //in neutral zone - close positions if open
             if current_quantity != 0 {
-                let quantity = current_quantity.unsigned_abs();
-                let side = if current_quantity > 0 {
-                    OrderSide::Sell
-                } else {
-                    OrderSide::Buy
-                };
-                context.market_order(self.symbol.clone(), quantity, side);
+                self.exits.cancel(context);
+                for id in context.flatten(&self.symbol).unwrap_or_default() {
+                    context.tag_order(id, ExitReason::NeutralZone);
+                }
             }
         }
     }
 
     fn on_end(&mut self, context: &mut StrategyContext) {
         //close any open positions
-        if let Some(position) = context.current_position() {
-            if !position.is_flat() {
-                let quantity = position.net_qty.unsigned_abs();
-                let side = if position.net_qty > 0 {
-                    OrderSide::Sell
-                } else {
-                    OrderSide::Buy
-                };
-                context.market_order(self.symbol.clone(), quantity, side);
-            }
+        self.exits.cancel(context);
+        for id in context.flatten(&self.symbol).unwrap_or_default() {
+            context.tag_order(id, ExitReason::EndOfRun);
         }
     }
 
     fn name(&self) -> &str {
         "RSI Reversion"
     }
+
+    fn required_lookback(&self) -> usize {
+        self.lookback + 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ExecutionEngine;
+    use crate::instrument::FuturesContract;
+    use crate::portfolio::Account;
+    use chrono::{TimeZone, Utc};
+
+    //feeds the closes to the strategy one bar at a time and returns the orders it left pending
+    fn orders_after(mut strategy: RsiReversionStrategy, closes: &[f64]) -> usize {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(100000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 50, &mut execution, &mut account);
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        strategy.on_start(&mut context);
+        for (i, &close) in closes.iter().enumerate() {
+            let bar = Bar::new(
+                start + chrono::Duration::days(i as i64),
+                close,
+                close + 1.0,
+                close - 1.0,
+                close,
+                1000.0,
+                None,
+                "ES".to_string(),
+            )
+            .unwrap();
+            context.push_bar(bar.clone());
+            strategy.on_bar(&mut context, &bar);
+        }
+        execution.pending_order_count()
+    }
+
+    fn confirmed(confirmation_bars: usize) -> RsiReversionStrategy {
+        RsiReversionStrategy::new("ES".to_string(), 3, 30.0, 70.0, 1)
+            .with_confirmation_bars(confirmation_bars)
+    }
+
+    //choppy closes keep the 3-bar rsi between 30 and 70 until the drop
+    const ONE_BAR_SPIKE: [f64; 7] = [4000.0, 4005.0, 4000.0, 4005.0, 4000.0, 3950.0, 4010.0];
+    const TWO_BAR_DROP: [f64; 8] = [
+        4000.0, 4005.0, 4000.0, 4005.0, 4000.0, 3950.0, 3900.0, 4010.0,
+    ];
+
+    #[test]
+    fn one_bar_spike_needs_no_confirmation_by_default() {
+        assert_eq!(orders_after(confirmed(1), &ONE_BAR_SPIKE), 1);
+    }
+
+    #[test]
+    fn one_bar_spike_is_ignored_with_two_bar_confirmation() {
+        assert_eq!(orders_after(confirmed(2), &ONE_BAR_SPIKE), 0);
+    }
+
+    #[test]
+    fn two_bars_beyond_the_threshold_confirm_an_entry() {
+        assert_eq!(orders_after(confirmed(2), &TWO_BAR_DROP), 1);
+    }
 }
@@ -0,0 +1,140 @@
+use crate::strategy::StrategyContext;
+use serde::{Deserialize, Serialize};
+
+//how many contracts a strategy enters with
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PositionSizing {
+    //always the same number of contracts
+    Fixed(u32),
+    //as many contracts as fit in this fraction of equity, by notional value at the last close
+    //recomputed on every entry, so size compounds with the account
+    PercentOfEquity(f64),
+    //as many contracts as keep one atr of movement within a dollar risk
+    //recomputed on every entry, so size shrinks as volatility rises
+    VolatilityTarget(VolTarget),
+}
+
+//a dollar risk per atr of movement: position x atr x point value ~ risk
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct VolTarget {
+    //dollars one atr of movement may make or lose on the whole position
+    pub risk: f64,
+    //bars in the atr
+    pub atr_period: usize,
+}
+
+impl VolTarget {
+    pub fn new(risk: f64, atr_period: usize) -> Self {
+        VolTarget { risk, atr_period }
+    }
+}
+
+impl Default for PositionSizing {
+    fn default() -> Self {
+        PositionSizing::Fixed(1)
+    }
+}
+
+impl PositionSizing {
+    //builds sizing from a fixed quantity and an optional equity fraction (the fraction wins)
+    pub fn from_params(qty: u32, size_fraction: Option<f64>) -> Self {
+        match size_fraction {
+            Some(fraction) => PositionSizing::PercentOfEquity(fraction),
+            None => PositionSizing::Fixed(qty),
+        }
+    }
+
+    //returns the number of contracts for a new entry (may be 0 if equity is too small)
+    pub fn entry_qty(&self, context: &StrategyContext) -> u32 {
+        match *self {
+            PositionSizing::Fixed(qty) => qty,
+            PositionSizing::PercentOfEquity(fraction) => {
+                let price = match context.last_bar() {
+                    Some(bar) => bar.close,
+                    None => return 0,
+                };
+                let notional_per_contract = context.contract().notional_value(price, 1);
+                if notional_per_contract <= 0.0 {
+                    return 0;
+                }
+
+                let qty = (context.equity() * fraction / notional_per_contract).floor();
+                if qty > 0.0 {
+                    qty as u32
+                } else {
+                    0
+                }
+            }
+            PositionSizing::VolatilityTarget(target) => context.vol_target_qty(target).unwrap_or(0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::Bar;
+    use crate::engine::execution::{Fill, Order, OrderSide};
+    use crate::engine::ExecutionEngine;
+    use crate::instrument::FuturesContract;
+    use crate::portfolio::Account;
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    fn bar(close: f64) -> Bar {
+        Bar::new(
+            Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap(),
+            close,
+            close + 1.0,
+            close - 1.0,
+            close,
+            1000.0,
+            None,
+            "ES".to_string(),
+        )
+        .unwrap()
+    }
+
+    fn fill(id: u64, side: OrderSide, price: f64) -> Fill {
+        let order = Order::market(id, Utc::now(), "ES".to_string(), 4, side);
+        Fill::from_order(id, &order, price, 0.0)
+    }
+
+    #[test]
+    fn growing_account_takes_larger_positions() {
+        let contract = FuturesContract::es("H24");
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(300000.0, 0.0, 0.0);
+        let mut context = StrategyContext::new(contract.clone(), 10, &mut execution, &mut account);
+        context.push_bar(bar(4000.0));
+        let sizing = PositionSizing::PercentOfEquity(1.0);
+
+        //one es contract at 4000 is 200000 of notional
+        assert_eq!(sizing.entry_qty(&context), 1);
+
+        //a 500-point win on four contracts adds 100000
+        account
+            .process_fill(fill(1, OrderSide::Buy, 4000.0), &contract)
+            .unwrap();
+        account
+            .process_fill(fill(2, OrderSide::Sell, 4500.0), &contract)
+            .unwrap();
+        account.update_equity(&HashMap::new(), &HashMap::new());
+
+        assert_eq!(sizing.entry_qty(&context), 2);
+        assert_eq!(PositionSizing::Fixed(1).entry_qty(&context), 1);
+    }
+
+    #[test]
+    fn small_account_sizes_to_zero() {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(100000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 10, &mut execution, &mut account);
+
+        //no close to price the notional yet
+        assert_eq!(PositionSizing::PercentOfEquity(1.0).entry_qty(&context), 0);
+        context.push_bar(bar(4000.0));
+        assert_eq!(PositionSizing::PercentOfEquity(1.0).entry_qty(&context), 0);
+    }
+}
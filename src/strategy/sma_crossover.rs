@@ -1,61 +1,126 @@
 use crate::data::Bar;
-use crate::engine::execution::OrderSide;
-use crate::strategy::{sma, Strategy, StrategyContext};
+use crate::engine::execution::{ExitReason, OrderSide};
+use crate::strategy::{
+    protective::ProtectiveExits, sizing::PositionSizing, sma, Strategy, StrategyContext,
+};
+use std::cmp::Ordering;
 
 //sma crossover strategy
 //goes long when fast sma crosses above slow sma
 //goes short when fast sma crosses below slow sma
+//an optional trend filter only takes longs above and shorts below a long-term sma
+//optional protective stop-loss / take-profit orders guard each position until the next signal
 #[derive(Debug, Clone)]
 pub struct SmaCrossoverStrategy {
     symbol: String,
     fast_window: usize,
     slow_window: usize,
-    qty: u32,
+    trend_window: Option<usize>,
+    sizing: PositionSizing,
+    exits: ProtectiveExits,
 
     //state
-    last_fast_sma: Option<f64>,
-    last_slow_sma: Option<f64>,
+    //last strict relation of fast to slow (less or greater); bars with equal smas don't update it
+    last_relation: Option<Ordering>,
 }
 
 impl SmaCrossoverStrategy {
-    pub fn new(symbol: String, fast_window: usize, slow_window: usize, qty: u32) -> Self {
+    pub fn new(
+        symbol: String,
+        fast_window: usize,
+        slow_window: usize,
+        trend_window: Option<usize>,
+        qty: u32,
+    ) -> Self {
         SmaCrossoverStrategy {
             symbol,
             fast_window,
             slow_window,
-            qty,
-            last_fast_sma: None,
-            last_slow_sma: None,
+            trend_window,
+            sizing: PositionSizing::Fixed(qty),
+            exits: ProtectiveExits::default(),
+            last_relation: None,
         }
     }
 
+    //sizes entries with the given mode instead of the fixed quantity
+    pub fn with_sizing(mut self, sizing: PositionSizing) -> Self {
+        self.sizing = sizing;
+        self
+    }
+
+    //places a stop-loss and/or take-profit this many ticks from the entry price of each position
+    pub fn with_protective_exits(
+        mut self,
+        stop_loss_ticks: Option<u32>,
+        take_profit_ticks: Option<u32>,
+    ) -> Self {
+        self.exits = ProtectiveExits::new(stop_loss_ticks, take_profit_ticks);
+        self
+    }
+
     //checks for crossover and returns signal
     //returns some(orderside buy) for bullish crossover
     //returns some(orderside sell) for bearish crossover
     //returns none for no crossover
-    fn check_crossover(&self, fast_sma: f64, slow_sma: f64) -> Option<OrderSide> {
-        if let (Some(prev_fast), Some(prev_slow)) = (self.last_fast_sma, self.last_slow_sma) {
+    //a cross is a strict change of side: fast moves from below slow to above it or back,
+    //possibly through bars where they are equal; equal-to-equal bars and touching the slow sma
+    //then returning to the same side never signal, and neither does the first strict relation
+    fn check_crossover(&mut self, fast_sma: f64, slow_sma: f64) -> Option<OrderSide> {
+        let relation = match fast_sma.partial_cmp(&slow_sma) {
+            Some(Ordering::Equal) | None => return None,
+            Some(relation) => relation,
+        };
+
+        let previous = self.last_relation.replace(relation);
+        match (previous, relation) {
             //bullish crossover fast crosses above slow
-            if prev_fast <= prev_slow && fast_sma > slow_sma {
-                return Some(OrderSide::Buy);
-            }
+            (Some(Ordering::Less), Ordering::Greater) => Some(OrderSide::Buy),
             //bearish crossover fast crosses below slow
-            if prev_fast >= prev_slow && fast_sma < slow_sma {
-                return Some(OrderSide::Sell);
-            }
+            (Some(Ordering::Greater), Ordering::Less) => Some(OrderSide::Sell),
+            _ => None,
+        }
+    }
+
+    //returns true if the signal agrees with the trend filter (or no filter is set)
+    //signals are suppressed until enough bars exist to compute the trend sma
+    fn with_trend(&self, context: &StrategyContext, signal: OrderSide) -> bool {
+        let trend_window = match self.trend_window {
+            Some(window) => window,
+            None => return true,
+        };
+
+        if context.bar_count() < trend_window {
+            return false;
+        }
+
+        let trend_sma = match sma(&context.get_close_prices(trend_window)) {
+            Some(v) => v,
+            None => return false,
+        };
+        let close = match context.last_bar() {
+            Some(bar) => bar.close,
+            None => return false,
+        };
+
+        match signal {
+            OrderSide::Buy => close > trend_sma,
+            OrderSide::Sell => close < trend_sma,
         }
-        None
     }
 }
 
 impl Strategy for SmaCrossoverStrategy {
     fn on_start(&mut self, _context: &mut StrategyContext) {
         //initialize state
-        self.last_fast_sma = None;
-        self.last_slow_sma = None;
+        self.last_relation = None;
+        self.exits.reset();
     }
 
     fn on_bar(&mut self, context: &mut StrategyContext, _bar: &Bar) {
+        //protect a newly filled entry
+        self.exits.update(context, &self.symbol);
+
         //need at least slow_window bars to calculate
         if context.bar_count() < self.slow_window {
             return;
@@ -78,8 +143,14 @@ impl Strategy for SmaCrossoverStrategy {
             None => return,
         };
 
-        //check for crossover
-        if let Some(signal) = self.check_crossover(fast_sma, slow_sma) {
+        //check for crossover, dropping counter-trend signals
+        let signal = self
+            .check_crossover(fast_sma, slow_sma)
+            .filter(|&side| self.with_trend(context, side));
+
+        if let Some(signal) = signal {
+            let entry_qty = self.sizing.entry_qty(context);
+
             //get current position
             let current_position = context.current_position();
             let current_quantity = current_position.map(|p| p.net_qty).unwrap_or(0);
@@ -88,55 +159,217 @@ impl Strategy for SmaCrossoverStrategy {
                 OrderSide::Buy => {
                     //go long if flat or short, buy to establish long position
                     if current_quantity <= 0 {
+                        self.exits.cancel(context);
                         let quantity_to_buy = if current_quantity < 0 {
                             //close short and open long
-                            (current_quantity.abs() + self.qty as i32) as u32
+                            (current_quantity.abs() + entry_qty as i32) as u32
                         } else {
                             //just open long
-                            self.qty
+                            entry_qty
                         };
 
-                        context.market_order(self.symbol.clone(), quantity_to_buy, OrderSide::Buy);
+                        let order = context.market_order(
+                            self.symbol.clone(),
+                            quantity_to_buy,
+                            OrderSide::Buy,
+                        );
+                        if let (Ok(id), true) = (order, current_quantity < 0) {
+                            context.tag_order(id, ExitReason::SignalFlip);
+                        }
                     }
                 }
                 OrderSide::Sell => {
                     //go short if flat or long, sell to establish short position
                     if current_quantity >= 0 {
+                        self.exits.cancel(context);
                         let quantity_to_sell = if current_quantity > 0 {
                             //close long and open short
-                            (current_quantity.abs() + self.qty as i32) as u32
+                            (current_quantity.abs() + entry_qty as i32) as u32
                         } else {
                             //just open short
-                            self.qty
+                            entry_qty
                         };
 
-                        context.market_order(self.symbol.clone(), quantity_to_sell, OrderSide::Sell);
+                        let order = context.market_order(
+                            self.symbol.clone(),
+                            quantity_to_sell,
+                            OrderSide::Sell,
+                        );
+                        if let (Ok(id), true) = (order, current_quantity > 0) {
+                            context.tag_order(id, ExitReason::SignalFlip);
+                        }
                     }
                 }
             }
         }
-
-        //update state
-        self.last_fast_sma = Some(fast_sma);
-        self.last_slow_sma = Some(slow_sma);
     }
 
     fn on_end(&mut self, context: &mut StrategyContext) {
         //close any open positions
-        if let Some(position) = context.current_position() {
-            if !position.is_flat() {
-                let quantity = position.net_qty.unsigned_abs();
-                let side = if position.net_qty > 0 {
-                    OrderSide::Sell
-                } else {
-                    OrderSide::Buy
-                };
-                context.market_order(self.symbol.clone(), quantity, side);
-            }
+        self.exits.cancel(context);
+        for id in context.flatten(&self.symbol).unwrap_or_default() {
+            context.tag_order(id, ExitReason::EndOfRun);
         }
     }
 
     fn name(&self) -> &str {
         "SMA Crossover"
     }
+
+    fn required_lookback(&self) -> usize {
+        self.slow_window
+            .max(self.fast_window)
+            .max(self.trend_window.unwrap_or(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::ExecutionEngine;
+    use crate::instrument::FuturesContract;
+    use crate::portfolio::Account;
+    use chrono::{TimeZone, Utc};
+
+    //feeds the closes to the strategy one bar at a time and returns the orders it left pending
+    fn orders_after(mut strategy: SmaCrossoverStrategy, closes: &[f64]) -> usize {
+        let mut execution = ExecutionEngine::new();
+        let mut account = Account::new(100000.0, 0.0, 0.0);
+        let mut context =
+            StrategyContext::new(FuturesContract::es("H24"), 50, &mut execution, &mut account);
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        for (i, &close) in closes.iter().enumerate() {
+            let bar = Bar::new(
+                start + chrono::Duration::days(i as i64),
+                close,
+                close + 1.0,
+                close - 1.0,
+                close,
+                1000.0,
+                None,
+                "ES".to_string(),
+            )
+            .unwrap();
+            context.push_bar(bar.clone());
+            strategy.on_bar(&mut context, &bar);
+        }
+        execution.pending_order_count()
+    }
+
+    //a downtrend with a two-bar bounce at the end: the fast sma crosses above the slow one
+    //while price is still below the trend sma
+    fn bounce_in_downtrend() -> Vec<f64> {
+        let mut closes: Vec<f64> = (0..10).map(|i| 4100.0 - 10.0 * i as f64).collect();
+        closes.extend([4030.0, 4040.0]);
+        closes
+    }
+
+    #[test]
+    fn bullish_crossover_below_the_trend_is_suppressed() {
+        let unfiltered = SmaCrossoverStrategy::new("ES".to_string(), 2, 3, None, 1);
+        assert_eq!(orders_after(unfiltered, &bounce_in_downtrend()), 1);
+
+        let filtered = SmaCrossoverStrategy::new("ES".to_string(), 2, 3, Some(10), 1);
+        assert_eq!(orders_after(filtered, &bounce_in_downtrend()), 0);
+    }
+
+    //feeds (fast, slow) pairs to check_crossover and returns the signals
+    fn signals(pairs: &[(f64, f64)]) -> Vec<Option<OrderSide>> {
+        let mut strategy = SmaCrossoverStrategy::new("ES".to_string(), 2, 3, None, 1);
+        pairs
+            .iter()
+            .map(|&(fast, slow)| strategy.check_crossover(fast, slow))
+            .collect()
+    }
+
+    #[test]
+    fn cross_through_equal_bars_signals_once() {
+        assert_eq!(
+            signals(&[(1.0, 2.0), (2.0, 2.0), (2.0, 2.0), (3.0, 2.0), (4.0, 2.0)]),
+            vec![None, None, None, Some(OrderSide::Buy), None]
+        );
+    }
+
+    #[test]
+    fn touching_and_returning_does_not_signal() {
+        assert_eq!(
+            signals(&[(3.0, 2.0), (2.0, 2.0), (3.0, 2.0), (1.0, 2.0)]),
+            vec![None, None, None, Some(OrderSide::Sell)]
+        );
+    }
+
+    #[test]
+    fn no_signal_without_a_prior_strict_relation() {
+        assert_eq!(
+            signals(&[(2.0, 2.0), (2.0, 2.0), (3.0, 2.0)]),
+            vec![None, None, None]
+        );
+    }
+
+    //integer prices: the averages fall below, sit equal for two bars, cross above, then sit
+    //equal again before moving further above
+    #[test]
+    fn flat_stretches_produce_no_repeated_entries() {
+        let closes = [
+            4010.0, 4005.0, 4000.0, 4000.0, 4000.0, 4000.0, 4010.0, 4010.0, 4010.0, 4010.0, 4020.0,
+        ];
+        let strategy = SmaCrossoverStrategy::new("ES".to_string(), 2, 3, None, 1);
+
+        assert_eq!(orders_after(strategy, &closes), 1);
+    }
+
+    //a bullish cross on the fourth bar goes long at 4030; the sixth bar dips through the
+    //5-point stop while the fast sma stays above the slow one, and the bearish cross only
+    //comes on the last bar
+    #[test]
+    fn long_is_stopped_out_before_a_reversal_signal() {
+        use crate::engine::{BacktestConfig, BacktestEngine};
+
+        let start = Utc.with_ymd_and_hms(2024, 1, 2, 0, 0, 0).unwrap();
+        let bars: Vec<Bar> = [
+            (4020.0, 4021.0, 4019.0, 4020.0),
+            (4010.0, 4011.0, 4009.0, 4010.0),
+            (4000.0, 4001.0, 3999.0, 4000.0),
+            (4030.0, 4031.0, 4029.0, 4030.0),
+            (4030.0, 4033.0, 4029.0, 4032.0),
+            (4028.0, 4030.0, 4020.0, 4029.0),
+            (4029.0, 4031.0, 4028.0, 4030.0),
+        ]
+        .iter()
+        .enumerate()
+        .map(|(i, &(open, high, low, close))| {
+            Bar::new(
+                start + chrono::Duration::days(i as i64),
+                open,
+                high,
+                low,
+                close,
+                1000.0,
+                None,
+                "ES".to_string(),
+            )
+            .unwrap()
+        })
+        .collect();
+
+        let strategy = SmaCrossoverStrategy::new("ES".to_string(), 2, 3, None, 1)
+            .with_protective_exits(Some(20), None);
+        let mut strategy: Box<dyn Strategy> = Box::new(strategy);
+        let mut engine =
+            BacktestEngine::new(BacktestConfig::default(), bars, FuturesContract::es("H24"));
+        let result = engine.run(&mut strategy);
+
+        let trips = result.round_trips();
+        assert_eq!(trips.len(), 2);
+        assert_eq!(trips[0].side, OrderSide::Buy);
+        assert_eq!(
+            (trips[0].entry_price, trips[0].exit_price),
+            (4030.0, 4025.0)
+        );
+        assert_eq!(trips[0].exit_reason, Some(ExitReason::StopLoss));
+        assert!((trips[0].pnl + 250.0).abs() < 1e-6);
+        //the reversal signal opens a short after the stop-out
+        assert_eq!(trips[1].side, OrderSide::Sell);
+        assert!(trips[0].exit_time < trips[1].entry_time);
+    }
 }